@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use sketched::document::Document;
+use sketched::geometry::{adaptive_smooth, relax, simplify};
+use sketched::layer::{Layer, LayerCaches};
+use sketched::mesh::{stroke_to_mesh, strokes_to_mesh, Mesh};
+use sketched::stroke::Stroke;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+/// A wavy freehand-like stroke of `points` points.
+fn synthetic_stroke(points: usize) -> Stroke {
+    let mut stroke = Stroke::new([0, 0, 0], 0.01);
+    for i in 0..points {
+        let t = i as f32 / points as f32;
+        let point = [
+            t * 2.0 - 1.0,
+            (t * 40.0).sin() * 0.2 + (t * 7.0).cos() * 0.1,
+        ];
+        stroke.push(point, Duration::from_millis(i as u64 * 8));
+    }
+    stroke
+}
+
+fn bench_mesh(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stroke_to_mesh");
+    for &size in &SIZES {
+        let stroke = synthetic_stroke(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &stroke, |b, s| {
+            b.iter(|| stroke_to_mesh(black_box(s)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_smoothing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adaptive_smooth");
+    for &size in &SIZES {
+        let stroke = synthetic_stroke(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &stroke.points, |b, p| {
+            b.iter(|| adaptive_smooth(black_box(p), 0.001))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("relax_4_passes");
+    for &size in &SIZES {
+        let stroke = synthetic_stroke(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &stroke.points, |b, p| {
+            b.iter(|| relax(black_box(p), 4))
+        });
+    }
+    group.finish();
+}
+
+fn bench_simplify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simplify");
+    for &size in &SIZES {
+        let stroke = synthetic_stroke(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &stroke.points, |b, p| {
+            b.iter(|| simplify(black_box(p), 0.001))
+        });
+    }
+    group.finish();
+}
+
+/// Editing one layer of a 32-layer document, re-meshing every layer each
+/// frame versus only the dirty one.
+fn bench_layer_cache(c: &mut Criterion) {
+    let layers: Vec<Layer> = (0..32)
+        .map(|i| Layer {
+            strokes: (0..20).map(|_| synthetic_stroke(500)).collect(),
+            ..Layer::new(format!("Layer {}", i))
+        })
+        .collect();
+    let mut group = c.benchmark_group("many_layers");
+    group.bench_function("full_redraw", |b| {
+        b.iter(|| {
+            let mut mesh = Mesh::default();
+            for layer in black_box(&layers) {
+                mesh.append(&strokes_to_mesh(&layer.strokes));
+            }
+            mesh
+        })
+    });
+    let mut caches = LayerCaches::new();
+    caches.update(&layers, |l| strokes_to_mesh(&l.strokes));
+    group.bench_function("one_dirty_layer", |b| {
+        b.iter(|| {
+            caches.mark_dirty(7);
+            caches.update(black_box(&layers), |l| strokes_to_mesh(&l.strokes))
+        })
+    });
+    group.finish();
+}
+
+/// Loading the same large document from JSON and from the binary format.
+fn bench_load(c: &mut Criterion) {
+    let document = Document {
+        layers: (0..4)
+            .map(|i| Layer {
+                strokes: (0..250).map(|_| synthetic_stroke(200)).collect(),
+                ..Layer::new(format!("Layer {}", i))
+            })
+            .collect(),
+        ..Document::default()
+    };
+    let json = document.to_json().unwrap();
+    let binary = document.to_binary().unwrap();
+    let mut group = c.benchmark_group("load");
+    group.bench_function("json", |b| {
+        b.iter(|| Document::from_json(black_box(&json)).unwrap())
+    });
+    group.bench_function("binary", |b| {
+        b.iter(|| Document::from_binary(black_box(&binary)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_mesh,
+    bench_smoothing,
+    bench_simplify,
+    bench_layer_cache,
+    bench_load
+);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sketched::document::Document;
+
+// Corrupt or hostile files must produce an error, never a panic. Seed
+// inputs, including files that used to panic, are in fuzz/seeds/load_json.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(json) = std::str::from_utf8(data) {
+        let _ = Document::from_json(json);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sketched::document::Page;
+use sketched::svg::{parse_path_data, parse_svg, CURVE_SAMPLES};
+
+// Seed inputs are in fuzz/seeds/parse_svg. Malformed markup and path data
+// must produce an error, never a panic.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(svg) = std::str::from_utf8(data) {
+        let _ = parse_svg(svg, &Page::default(), [0, 0, 0], 0.01);
+        let _ = parse_path_data(svg, CURVE_SAMPLES);
+    }
+});
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::distance;
+
+type Rect = ([f32; 2], [f32; 2]);
+
+/// Snap found while moving a selection: the offset to add to the move so
+/// that an edge or center lines up, and the world coordinate of the vertical
+/// and horizontal guide lines to draw, if any.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapResult {
+    pub offset: [f32; 2],
+    pub guide_x: Option<f32>,
+    pub guide_y: Option<f32>,
+}
+
+/// Left/right edges and center of `rect` along `axis`.
+fn anchors(rect: Rect, axis: usize) -> [f32; 3] {
+    let (min, max) = rect;
+    [min[axis], (min[axis] + max[axis]) / 2.0, max[axis]]
+}
+
+/// Closest alignment of the anchors of `moving` with those of `others` along
+/// `axis`, within `threshold`, as `(offset, guide)`.
+fn align_axis(moving: Rect, others: &[Rect], threshold: f32, axis: usize) -> Option<(f32, f32)> {
+    let mut best: Option<(f32, f32)> = None;
+    for &a in &anchors(moving, axis) {
+        for other in others {
+            for &b in &anchors(*other, axis) {
+                let offset = b - a;
+                let closer = best.is_none_or(|(o, _)| offset.abs() < o.abs());
+                if offset.abs() <= threshold && closer {
+                    best = Some((offset, b));
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Finds how to nudge `moving_bbox` so that its edges or center align with
+/// those of the `others` bounding boxes, each axis independently.
+pub fn find_alignment(moving_bbox: Rect, others: &[Rect], threshold: f32) -> Option<SnapResult> {
+    let x = align_axis(moving_bbox, others, threshold, 0);
+    let y = align_axis(moving_bbox, others, threshold, 1);
+    if x.is_none() && y.is_none() {
+        return None;
+    }
+    Some(SnapResult {
+        offset: [x.map_or(0.0, |(o, _)| o), y.map_or(0.0, |(o, _)| o)],
+        guide_x: x.map(|(_, g)| g),
+        guide_y: y.map(|(_, g)| g),
+    })
+}
+
+/// User-placed infinite guide line through `origin` along `direction`,
+/// which strokes snap to like ruler guides.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Guide {
+    pub origin: [f32; 2],
+    /// Unit vector.
+    pub direction: [f32; 2],
+}
+
+impl Guide {
+    pub fn horizontal(y: f32) -> Self {
+        Self {
+            origin: [0.0, y],
+            direction: [1.0, 0.0],
+        }
+    }
+
+    pub fn vertical(x: f32) -> Self {
+        Self {
+            origin: [x, 0.0],
+            direction: [0.0, 1.0],
+        }
+    }
+
+    /// Guide through `a` and `b`, or `None` if they coincide.
+    pub fn through(a: [f32; 2], b: [f32; 2]) -> Option<Self> {
+        let len = distance(a, b);
+        if len <= f32::EPSILON {
+            return None;
+        }
+        Some(Self {
+            origin: a,
+            direction: [(b[0] - a[0]) / len, (b[1] - a[1]) / len],
+        })
+    }
+
+    /// Part of the guide within `view_rect`, to draw, or `None` if it
+    /// misses the view.
+    pub fn segment_in(&self, view_rect: Rect) -> Option<([f32; 2], [f32; 2])> {
+        let (min, max) = view_rect;
+        let (mut t0, mut t1) = (f32::NEG_INFINITY, f32::INFINITY);
+        for axis in 0..2 {
+            let (o, d) = (self.origin[axis], self.direction[axis]);
+            if d.abs() <= f32::EPSILON {
+                if o < min[axis] || o > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min[axis] - o) / d, (max[axis] - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 || !t0.is_finite() || !t1.is_finite() {
+            return None;
+        }
+        let at = |t: f32| {
+            [
+                self.origin[0] + self.direction[0] * t,
+                self.origin[1] + self.direction[1] * t,
+            ]
+        };
+        Some((at(t0), at(t1)))
+    }
+}
+
+/// Closest point to `point` on `guide`.
+pub fn project_onto_guide(point: [f32; 2], guide: &Guide) -> [f32; 2] {
+    let [dx, dy] = guide.direction;
+    let t = (point[0] - guide.origin[0]) * dx + (point[1] - guide.origin[1]) * dy;
+    [guide.origin[0] + dx * t, guide.origin[1] + dy * t]
+}
+
+/// The guides of a drawing and whether captured points snap to them.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guides {
+    pub guides: Vec<Guide>,
+    pub snapping: bool,
+}
+
+impl Guides {
+    /// Index of the guide nearest to `point` within `threshold`.
+    pub fn nearest(&self, point: [f32; 2], threshold: f32) -> Option<usize> {
+        self.guides
+            .iter()
+            .map(|g| distance(point, project_onto_guide(point, g)))
+            .enumerate()
+            .filter(|&(_, d)| d <= threshold)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Projects `point` onto the nearest guide within `threshold`, in world
+    /// units, when snapping is on; other points are returned unchanged.
+    pub fn snap(&self, point: [f32; 2], threshold: f32) -> [f32; 2] {
+        if !self.snapping {
+            return point;
+        }
+        match self.nearest(point, threshold) {
+            Some(i) => project_onto_guide(point, &self.guides[i]),
+            None => point,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::camera::has_area;
+use crate::input::InputEvent;
+
+/// Window, input and presentation surface the app runs on, so that the core
+/// does not depend on a particular windowing library.
+pub trait Backend {
+    /// Input received since the previous call.
+    fn poll_events(&mut self) -> Vec<InputEvent>;
+    /// Presents the frame that was just rendered.
+    fn swap(&mut self);
+    fn framebuffer_size(&self) -> (u32, u32);
+    fn should_close(&self) -> bool;
+
+    /// Time of the frame last polled, to hand to time-dependent input
+    /// handling such as double-clicks and undo coalescing instead of reading
+    /// the clock there, so that replays see the recorded timing.
+    fn frame_time(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Whether there is a framebuffer to render to; false while minimized,
+    /// when the frame should be skipped without touching the back buffer.
+    fn can_render(&self) -> bool {
+        has_area(self.framebuffer_size())
+    }
+}
+
+/// Interval between the frames of `Headless::new`, 60 per second.
+pub const SCRIPTED_FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// One frame of a headless script: when it happens, the window size from
+/// then on and the events delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedFrame {
+    /// Time since the script started.
+    pub time: Duration,
+    pub size: (u32, u32),
+    pub events: Vec<InputEvent>,
+}
+
+/// Backend without a window, replaying scripted frames of events, one per
+/// poll, each taking effect with its own window size and time. It closes
+/// once the script is exhausted.
+#[derive(Debug, Clone)]
+pub struct Headless {
+    pub size: (u32, u32),
+    frames: VecDeque<ScriptedFrame>,
+    pub swapped: usize,
+    started: Instant,
+    /// Time of the frame last polled, since `started`.
+    time: Duration,
+}
+
+impl Headless {
+    /// Replays `frames` of events at a fixed `size`, `SCRIPTED_FRAME_INTERVAL`
+    /// apart.
+    pub fn new(size: (u32, u32), frames: Vec<Vec<InputEvent>>) -> Self {
+        let frames = frames
+            .into_iter()
+            .enumerate()
+            .map(|(i, events)| ScriptedFrame {
+                time: SCRIPTED_FRAME_INTERVAL * i as u32,
+                size,
+                events,
+            })
+            .collect();
+        Self::scripted(size, frames)
+    }
+
+    /// Replays `frames`, starting at `size` until the first one is polled.
+    pub fn scripted(size: (u32, u32), frames: Vec<ScriptedFrame>) -> Self {
+        Self {
+            size,
+            frames: frames.into(),
+            swapped: 0,
+            started: Instant::now(),
+            time: Duration::from_secs(0),
+        }
+    }
+
+    /// Time of the frame last polled, since the script started.
+    pub fn elapsed(&self) -> Duration {
+        self.time
+    }
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self::scripted((0, 0), Vec::new())
+    }
+}
+
+impl Backend for Headless {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        match self.frames.pop_front() {
+            Some(frame) => {
+                self.size = frame.size;
+                self.time = self.time.max(frame.time);
+                frame.events
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn swap(&mut self) {
+        self.swapped += 1;
+    }
+
+    fn framebuffer_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn should_close(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn frame_time(&self) -> Instant {
+        self.started + self.time
+    }
+}
@@ -0,0 +1,517 @@
+use serde::{Deserialize, Serialize};
+
+use crate::coords::{Ndc, ScreenPos, WorldPos};
+use crate::input::Gesture;
+
+/// Zoom factor applied per scroll unit.
+pub const SCROLL_ZOOM_STEP: f32 = 1.1;
+/// Distance panned per scroll unit, in NDC.
+pub const SCROLL_PAN_STEP: f32 = 0.05;
+
+/// What plain scrolling does. Mouse wheels only report vertical scroll and
+/// are most useful for zooming; trackpads report both axes, so they pan and
+/// zoom with Ctrl held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollMode {
+    Zoom,
+    Trackpad,
+}
+
+/// Whether a window of `size` pixels can be drawn into. Minimized windows
+/// report a 0x0 framebuffer; rendering and coordinate conversions should be
+/// skipped until the window is restored.
+pub fn has_area(size: (u32, u32)) -> bool {
+    size.0 > 0 && size.1 > 0
+}
+
+/// Converts a window pixel position (origin at the top-left, y down) to NDC
+/// for a window of `size` pixels. A zero-size window is treated as one pixel
+/// wide so the result stays finite.
+pub fn pixel_to_ndc(pixel: ScreenPos, size: (u32, u32)) -> Ndc {
+    Ndc([
+        2.0 * pixel.x() / size.0.max(1) as f32 - 1.0,
+        1.0 - 2.0 * pixel.y() / size.1.max(1) as f32,
+    ])
+}
+
+/// Inverse of `pixel_to_ndc`.
+pub fn ndc_to_pixel(ndc: Ndc, size: (u32, u32)) -> ScreenPos {
+    ScreenPos([
+        (ndc.x() + 1.0) / 2.0 * size.0 as f32,
+        (1.0 - ndc.y()) / 2.0 * size.1 as f32,
+    ])
+}
+
+/// A position expressed in each space of the conversion chain, for
+/// diagnosing placement bugs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateReadout {
+    pub pixel: ScreenPos,
+    pub ndc: Ndc,
+    pub world: WorldPos,
+}
+
+impl CoordinateReadout {
+    pub fn new(pixel: ScreenPos, size: (u32, u32), camera: &Camera) -> Self {
+        let ndc = pixel_to_ndc(pixel, size);
+        Self {
+            pixel,
+            ndc,
+            world: camera.ndc_to_world(ndc),
+        }
+    }
+}
+
+impl std::fmt::Display for CoordinateReadout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pixel ({:.1}, {:.1}) ndc ({:.4}, {:.4}) world ({:.4}, {:.4})",
+            self.pixel.x(),
+            self.pixel.y(),
+            self.ndc.x(),
+            self.ndc.y(),
+            self.world.x(),
+            self.world.y()
+        )
+    }
+}
+
+/// Zooms by `factor` while keeping the world point `cursor_world` at the same
+/// place on screen, returning the new pan and zoom.
+pub fn zoom_at(pan: [f32; 2], zoom: f32, factor: f32, cursor_world: WorldPos) -> ([f32; 2], f32) {
+    let cursor_world = cursor_world.0;
+    let new_zoom = zoom * factor;
+    // The cursor's offset from the view center scales with 1 / zoom.
+    let keep = zoom / new_zoom;
+    let new_pan = [
+        cursor_world[0] - (cursor_world[0] - pan[0]) * keep,
+        cursor_world[1] - (cursor_world[1] - pan[1]) * keep,
+    ];
+    (new_pan, new_zoom)
+}
+
+/// Whether a stroke with bounding box `bbox` may be on screen, given the
+/// world rectangle `view_rect` covered by the view. Strokes failing this
+/// test can be skipped when meshing and drawing.
+pub fn stroke_visible(bbox: ([f32; 2], [f32; 2]), view_rect: ([f32; 2], [f32; 2])) -> bool {
+    let (min, max) = bbox;
+    let (view_min, view_max) = view_rect;
+    min[0] <= view_max[0] && max[0] >= view_min[0] && min[1] <= view_max[1] && max[1] >= view_min[1]
+}
+
+/// Closest pan to `pan` that keeps some of `bounds` on screen, `viewport`
+/// being the half-extent of the view in world units. The view center may move
+/// up to one half-extent past each edge, where the content touches the border.
+pub fn clamp_pan(pan: [f32; 2], bounds: ([f32; 2], [f32; 2]), viewport: [f32; 2]) -> [f32; 2] {
+    let (min, max) = bounds;
+    [
+        pan[0].clamp(min[0] - viewport[0], max[0] + viewport[0]),
+        pan[1].clamp(min[1] - viewport[1], max[1] + viewport[1]),
+    ]
+}
+
+/// Eases `from` toward `to`, closing the gap exponentially at `stiffness` per
+/// second so the motion is frame-rate independent.
+pub fn elastic_ease(from: [f32; 2], to: [f32; 2], stiffness: f32, dt: f32) -> [f32; 2] {
+    let t = 1.0 - (-stiffness * dt).exp();
+    [
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+    ]
+}
+
+/// Optional limit on panning. While the user drags, the pan is free to go out
+/// of bounds; once released, `settle` pulls it back elastically. Disabled by
+/// default, keeping the canvas infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanBounds {
+    pub enabled: bool,
+    pub stiffness: f32,
+}
+
+impl PanBounds {
+    /// Moves `camera` one step of `dt` seconds back toward `bounds`.
+    pub fn settle(&self, camera: &mut Camera, bounds: ([f32; 2], [f32; 2]), dt: f32) {
+        if !self.enabled {
+            return;
+        }
+        let half = 1.0 / camera.zoom;
+        let target = clamp_pan(camera.pan, bounds, [half, half]);
+        camera.pan = elastic_ease(camera.pan, target, self.stiffness, dt);
+    }
+}
+
+impl Default for PanBounds {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stiffness: 12.0,
+        }
+    }
+}
+
+/// View transform from world space to NDC: points are translated so that
+/// `pan` lands at the center of the view, rotated by `rotation` radians, then
+/// scaled by `zoom`.
+///
+/// World units span the window like NDC do, so a unit is wider than tall in
+/// a landscape window. The rotation is done with x scaled by `aspect` so the
+/// canvas turns rigidly on screen instead of shearing.
+/// The part of the camera saved with a document, restored when it is
+/// reopened. The aspect ratio follows whatever window it is opened in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct View {
+    /// World point at the center of the window.
+    pub center: [f32; 2],
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+impl Default for View {
+    /// Centered on the origin at unit zoom, as files without a view open.
+    fn default() -> Self {
+        let camera = Camera::default();
+        Self {
+            center: camera.pan,
+            zoom: camera.zoom,
+            rotation: camera.rotation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub pan: [f32; 2],
+    pub zoom: f32,
+    pub rotation: f32,
+    /// Width over height of the window, kept up to date by `set_viewport`.
+    pub aspect: f32,
+}
+
+impl Camera {
+    /// Follows a resize to `size` pixels. Minimized windows keep the last
+    /// aspect ratio.
+    pub fn set_viewport(&mut self, size: (u32, u32)) {
+        if has_area(size) {
+            self.aspect = size.0 as f32 / size.1 as f32;
+        }
+    }
+
+    pub fn world_to_ndc(&self, p: WorldPos) -> Ndc {
+        let (sin, cos) = self.rotation.sin_cos();
+        let aspect = self.aspect;
+        let x = (p.x() - self.pan[0]) * aspect;
+        let y = p.y() - self.pan[1];
+        Ndc([
+            self.zoom * (cos * x - sin * y) / aspect,
+            self.zoom * (sin * x + cos * y),
+        ])
+    }
+
+    /// Inverse of `world_to_ndc`, used to map the cursor back onto the canvas.
+    pub fn ndc_to_world(&self, p: Ndc) -> WorldPos {
+        let (sin, cos) = self.rotation.sin_cos();
+        let aspect = self.aspect;
+        let x = p.x() * aspect / self.zoom;
+        let y = p.y() / self.zoom;
+        WorldPos([
+            (cos * x + sin * y) / aspect + self.pan[0],
+            -sin * x + cos * y + self.pan[1],
+        ])
+    }
+
+    pub fn screen_to_world(&self, p: ScreenPos, size: (u32, u32)) -> WorldPos {
+        self.ndc_to_world(pixel_to_ndc(p, size))
+    }
+
+    pub fn world_to_screen(&self, p: WorldPos, size: (u32, u32)) -> ScreenPos {
+        ndc_to_pixel(self.world_to_ndc(p), size)
+    }
+
+    /// The transform as a column-major 3x3 matrix, suitable for a shader
+    /// uniform operating on homogeneous 2D coordinates.
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let origin = self.world_to_ndc(WorldPos::default()).0;
+        [
+            [self.zoom * cos, self.zoom * sin * self.aspect, 0.0],
+            [-self.zoom * sin / self.aspect, self.zoom * cos, 0.0],
+            [origin[0], origin[1], 1.0],
+        ]
+    }
+
+    /// World rectangle covered by the view, as `(min, max)`. With a rotated
+    /// view this is the bounding box of the visible area.
+    pub fn view_rect(&self) -> ([f32; 2], [f32; 2]) {
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+        let mut min = [f32::INFINITY; 2];
+        let mut max = [f32::NEG_INFINITY; 2];
+        for &corner in &corners {
+            let p = self.ndc_to_world(Ndc(corner)).0;
+            for i in 0..2 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        (min, max)
+    }
+
+    /// Camera showing the whole `bounds` rectangle, leaving `margin` (as a
+    /// fraction of the view) free around it.
+    pub fn fit(bounds: ([f32; 2], [f32; 2]), margin: f32) -> Self {
+        let (min, max) = bounds;
+        let size = (max[0] - min[0]).max(max[1] - min[1]);
+        let zoom = if size > f32::EPSILON {
+            2.0 * (1.0 - margin.clamp(0.0, 0.9)) / size
+        } else {
+            1.0
+        };
+        Self {
+            pan: [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0],
+            zoom,
+            ..Self::default()
+        }
+    }
+
+    /// Moves the view by the NDC vector `delta`, whatever the zoom and rotation.
+    pub fn pan_by_ndc(&mut self, delta: [f32; 2]) {
+        let origin = self.ndc_to_world(Ndc::default()).0;
+        let moved = self.ndc_to_world(Ndc(delta)).0;
+        self.pan[0] += moved[0] - origin[0];
+        self.pan[1] += moved[1] - origin[1];
+    }
+
+    /// Zooms by `factor` around the world point under the cursor.
+    pub fn zoom_at(&mut self, factor: f32, cursor_world: WorldPos) {
+        let (pan, zoom) = zoom_at(self.pan, self.zoom, factor, cursor_world);
+        self.pan = pan;
+        self.zoom = zoom;
+    }
+
+    /// Handles a scroll of `delta` units with the cursor over `cursor_world`,
+    /// `control` telling whether Ctrl is held.
+    pub fn scroll(
+        &mut self,
+        delta: [f32; 2],
+        control: bool,
+        mode: ScrollMode,
+        cursor_world: WorldPos,
+    ) {
+        match mode {
+            ScrollMode::Trackpad if !control => {
+                self.pan_by_ndc([delta[0] * SCROLL_PAN_STEP, delta[1] * SCROLL_PAN_STEP])
+            }
+            _ => self.zoom_at(SCROLL_ZOOM_STEP.powf(delta[1]), cursor_world),
+        }
+    }
+
+    /// Applies a two-finger pinch and pan so the content follows the fingers.
+    /// Other gestures, and any gesture while the window has no area, leave
+    /// the camera alone.
+    pub fn apply_gesture(&mut self, gesture: Gesture, size: (u32, u32)) {
+        if !has_area(size) {
+            return;
+        }
+        self.set_viewport(size);
+        if let Gesture::PinchPan { center, scale, pan } = gesture {
+            let delta = [2.0 * pan[0] / size.0 as f32, -2.0 * pan[1] / size.1 as f32];
+            self.pan_by_ndc([-delta[0], -delta[1]]);
+            let cursor = self.screen_to_world(center, size);
+            self.zoom_at(scale, cursor);
+        }
+    }
+
+    /// The view to save with the document.
+    pub fn view(&self) -> View {
+        View {
+            center: self.pan,
+            zoom: self.zoom,
+            rotation: self.rotation,
+        }
+    }
+
+    /// Shows a saved `view`, keeping the window's aspect ratio. Zooms that
+    /// are not positive and finite fall back to unit zoom.
+    pub fn restore(&mut self, view: View) {
+        self.pan = view.center;
+        self.zoom = if view.zoom.is_finite() && view.zoom > 0.0 {
+            view.zoom
+        } else {
+            1.0
+        };
+        self.rotation = view.rotation;
+    }
+
+    /// Keeps the zoom within `[min, max]`.
+    pub fn clamp_zoom(&mut self, min: f32, max: f32) {
+        self.zoom = self.zoom.clamp(min, max);
+    }
+
+    /// Recenters the view on the origin at unit zoom, keeping the rotation.
+    pub fn reset(&mut self) {
+        self.pan = [0.0, 0.0];
+        self.zoom = 1.0;
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation = (self.rotation + angle) % std::f32::consts::TAU;
+    }
+
+    pub fn reset_rotation(&mut self) {
+        self.rotation = 0.0;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            pan: [0.0, 0.0],
+            zoom: 1.0,
+            rotation: 0.0,
+            aspect: 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (800, 800);
+
+    fn assert_near(a: ScreenPos, b: ScreenPos) {
+        assert!(
+            (a.x() - b.x()).abs() < 1e-2 && (a.y() - b.y()).abs() < 1e-2,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn zoom_at_keeps_the_cursor_point_on_screen() {
+        let cursor = WorldPos([0.3, -0.2]);
+        for &factor in &[2.0, 0.5, SCROLL_ZOOM_STEP] {
+            let mut camera = Camera {
+                pan: [0.1, 0.4],
+                zoom: 1.5,
+                ..Camera::default()
+            };
+            let before = camera.world_to_screen(cursor, SIZE);
+            camera.zoom_at(factor, cursor);
+            assert!((camera.zoom - 1.5 * factor).abs() < 1e-6);
+            assert_near(camera.world_to_screen(cursor, SIZE), before);
+        }
+    }
+
+    #[test]
+    fn zoom_at_keeps_the_cursor_point_on_a_rotated_view() {
+        let cursor = WorldPos([-0.6, 0.25]);
+        let mut camera = Camera {
+            pan: [0.2, 0.0],
+            zoom: 0.8,
+            rotation: 0.7,
+            aspect: 1.6,
+        };
+        let before = camera.world_to_screen(cursor, SIZE);
+        camera.zoom_at(3.0, cursor);
+        assert_near(camera.world_to_screen(cursor, SIZE), before);
+    }
+
+    #[test]
+    fn zoom_at_the_view_center_keeps_the_pan() {
+        let (pan, zoom) = zoom_at([0.5, -0.5], 1.0, 4.0, WorldPos([0.5, -0.5]));
+        assert_eq!(pan, [0.5, -0.5]);
+        assert_eq!(zoom, 4.0);
+    }
+
+    #[test]
+    fn zero_size_conversions_stay_finite() {
+        let camera = Camera::default();
+        for &size in &[(0, 0), (0, 600), (800, 0)] {
+            let ndc = pixel_to_ndc(ScreenPos([12.0, 34.0]), size);
+            assert!(ndc.x().is_finite() && ndc.y().is_finite());
+            let world = camera.screen_to_world(ScreenPos([12.0, 34.0]), size);
+            assert!(world.x().is_finite() && world.y().is_finite());
+            assert!(!has_area(size));
+        }
+    }
+
+    #[test]
+    fn gestures_are_ignored_without_area() {
+        let mut camera = Camera::default();
+        let gesture = Gesture::PinchPan {
+            center: ScreenPos([10.0, 10.0]),
+            scale: 2.0,
+            pan: [5.0, 5.0],
+        };
+        camera.apply_gesture(gesture, (0, 0));
+        assert_eq!(camera, Camera::default());
+        camera.apply_gesture(gesture, SIZE);
+        assert_ne!(camera, Camera::default());
+    }
+
+    const WIDE: (u32, u32) = (1600, 800);
+
+    fn wide_camera(rotation: f32) -> Camera {
+        let mut camera = Camera {
+            pan: [0.2, -0.1],
+            zoom: 1.3,
+            rotation,
+            ..Camera::default()
+        };
+        camera.set_viewport(WIDE);
+        camera
+    }
+
+    fn screen_offset(camera: &Camera, a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+        let a = camera.world_to_screen(WorldPos(a), WIDE);
+        let b = camera.world_to_screen(WorldPos(b), WIDE);
+        [b.x() - a.x(), b.y() - a.y()]
+    }
+
+    #[test]
+    fn rotation_is_rigid_on_wide_windows() {
+        let (a, b, c) = ([0.0, 0.0], [0.3, 0.0], [0.0, 0.5]);
+        let flat = wide_camera(0.0);
+        for &angle in &[0.3, std::f32::consts::FRAC_PI_2, 2.5] {
+            let turned = wide_camera(angle);
+            for &(p, q) in &[(a, b), (a, c), (b, c)] {
+                let before = screen_offset(&flat, p, q);
+                let after = screen_offset(&turned, p, q);
+                let len = |v: [f32; 2]| (v[0] * v[0] + v[1] * v[1]).sqrt();
+                assert!((len(before) - len(after)).abs() < 1e-2);
+            }
+            // The angle between two edges survives the rotation.
+            let (u, v) = (screen_offset(&turned, a, b), screen_offset(&turned, a, c));
+            let (u0, v0) = (screen_offset(&flat, a, b), screen_offset(&flat, a, c));
+            let dot = u[0] * v[0] + u[1] * v[1];
+            let dot0 = u0[0] * v0[0] + u0[1] * v0[1];
+            assert!((dot - dot0).abs() < 1e-1);
+        }
+    }
+
+    #[test]
+    fn world_ndc_conversions_invert_each_other() {
+        let camera = wide_camera(1.1);
+        let p = WorldPos([0.4, -0.7]);
+        let back = camera.ndc_to_world(camera.world_to_ndc(p));
+        assert!((back.x() - p.x()).abs() < 1e-5 && (back.y() - p.y()).abs() < 1e-5);
+
+        let m = camera.matrix();
+        let ndc = camera.world_to_ndc(p);
+        let x = m[0][0] * p.x() + m[1][0] * p.y() + m[2][0];
+        let y = m[0][1] * p.x() + m[1][1] * p.y() + m[2][1];
+        assert!((x - ndc.x()).abs() < 1e-5 && (y - ndc.y()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn minimized_windows_keep_the_aspect() {
+        let mut camera = wide_camera(0.0);
+        assert_eq!(camera.aspect, 2.0);
+        camera.set_viewport((0, 0));
+        assert_eq!(camera.aspect, 2.0);
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+/// Most updates run for a single frame, so that a long stall does not make
+/// the loop fall further and further behind.
+pub const MAX_STEPS_PER_FRAME: u32 = 8;
+
+/// Fixed timestep accumulator: state updates run at a constant rate however
+/// fast frames are rendered.
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    pub step: Duration,
+    accumulated: Duration,
+}
+
+impl FixedStep {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            accumulated: Duration::from_secs(0),
+        }
+    }
+
+    /// Adds the time `elapsed` since the previous frame and returns how many
+    /// updates of `step` to run now. Time beyond `MAX_STEPS_PER_FRAME` steps
+    /// is dropped.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        if self.step == Duration::from_secs(0) {
+            return 1;
+        }
+        self.accumulated += elapsed;
+        let mut steps = 0;
+        while self.accumulated >= self.step && steps < MAX_STEPS_PER_FRAME {
+            self.accumulated -= self.step;
+            steps += 1;
+        }
+        if steps == MAX_STEPS_PER_FRAME {
+            self.accumulated = self.accumulated.min(self.step);
+        }
+        steps
+    }
+
+    /// Fraction of a step accumulated since the last update, for
+    /// interpolating what is rendered between two updates.
+    pub fn alpha(&self) -> f32 {
+        if self.step == Duration::from_secs(0) {
+            return 0.0;
+        }
+        self.accumulated.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+impl Default for FixedStep {
+    /// 120 updates per second.
+    fn default() -> Self {
+        Self::new(Duration::from_micros(8_333))
+    }
+}
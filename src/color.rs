@@ -0,0 +1,299 @@
+//! Color conversions.
+//!
+//! Stroke colors are stored as 8-bit sRGB. Blending those values directly
+//! darkens semi-transparent overlaps and muddies mixed edges; converting to
+//! linear light before blending and back to sRGB for display gives the
+//! physically expected, brighter result.
+
+use serde::{Deserialize, Serialize};
+
+/// Converts an sRGB encoded channel in `[0, 1]` to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear light channel in `[0, 1]` to sRGB encoding.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts an 8-bit sRGB color to linear floating point components.
+pub fn to_linear(color: [u8; 3]) -> [f32; 3] {
+    let mut linear = [0.0; 3];
+    for (l, &c) in linear.iter_mut().zip(&color) {
+        *l = srgb_to_linear(f32::from(c) / 255.0);
+    }
+    linear
+}
+
+/// Converts linear floating point components back to an 8-bit sRGB color.
+pub fn from_linear(linear: [f32; 3]) -> [u8; 3] {
+    let mut color = [0; 3];
+    for (c, &l) in color.iter_mut().zip(&linear) {
+        *c = (linear_to_srgb(l.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    }
+    color
+}
+
+/// How a stroke's color combines with what is already on the canvas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Source painted over the destination.
+    #[default]
+    Normal,
+    /// Source times destination; only ever darkens, used for shading.
+    Multiply,
+    /// Source added to destination; only ever brightens, used for glow.
+    Add,
+    /// Inverse of multiplying the inverses; brightens without clipping as
+    /// quickly as `Add`.
+    Screen,
+}
+
+impl BlendMode {
+    /// Composites `src` with coverage `alpha` over `dst`. Components are
+    /// linear and in `[0, 1]`.
+    pub fn composite(self, src: [f32; 3], alpha: f32, dst: [f32; 3]) -> [f32; 3] {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let mut out = [0.0; 3];
+        for i in 0..3 {
+            let (s, d) = (src[i], dst[i]);
+            let blended = match self {
+                BlendMode::Normal => s,
+                BlendMode::Multiply => s * d,
+                BlendMode::Add => (s + d).min(1.0),
+                BlendMode::Screen => 1.0 - (1.0 - s) * (1.0 - d),
+            };
+            out[i] = d + (blended - d) * alpha;
+        }
+        out
+    }
+}
+
+/// Watercolor-like pigment build-up: each pass of the brush deposits `flow`
+/// of the opacity still missing, so overlapping passes darken gradually and
+/// never exceed `max_opacity`. Applied per pixel to an offscreen coverage
+/// buffer, which is then composited with the buffer's value as alpha.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WetBrush {
+    pub flow: f32,
+    pub max_opacity: f32,
+}
+
+impl WetBrush {
+    /// Accumulated opacity after depositing one pass with `coverage` (the
+    /// brush footprint's antialiased coverage) over `current`.
+    pub fn deposit(&self, current: f32, coverage: f32) -> f32 {
+        let max = self.max_opacity.clamp(0.0, 1.0);
+        let added = self.flow.clamp(0.0, 1.0) * coverage.clamp(0.0, 1.0);
+        (current + (max - current) * added).clamp(current.min(max), max)
+    }
+}
+
+impl Default for WetBrush {
+    fn default() -> Self {
+        Self {
+            flow: 0.25,
+            max_opacity: 0.8,
+        }
+    }
+}
+
+/// Converts an 8-bit RGB color to hue in degrees `[0, 360)`, saturation and
+/// value in `[0, 1]`.
+pub fn rgb_to_hsv(color: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = [
+        f32::from(color[0]) / 255.0,
+        f32::from(color[1]) / 255.0,
+        f32::from(color[2]) / 255.0,
+    ];
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta <= f32::EPSILON {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max <= f32::EPSILON {
+        0.0
+    } else {
+        delta / max
+    };
+    [hue, saturation, max]
+}
+
+/// Color under `pos` on a hue/saturation wheel of `radius` around `center`,
+/// all in the same coordinate space: hue follows the angle counterclockwise
+/// from the positive x axis, saturation the distance from the center, and
+/// value is full. `None` outside the wheel.
+pub fn wheel_color_at(pos: [f32; 2], center: [f32; 2], radius: f32) -> Option<[u8; 3]> {
+    let (dx, dy) = (pos[0] - center[0], pos[1] - center[1]);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if radius <= 0.0 || distance > radius {
+        return None;
+    }
+    let hue = dy.atan2(dx).to_degrees();
+    Some(hsv_to_rgb([hue, distance / radius, 1.0]))
+}
+
+/// Where `color` sits on the wheel of `wheel_color_at`, for drawing the
+/// marker of the current color. Brightness is ignored.
+pub fn wheel_position(color: [u8; 3], center: [f32; 2], radius: f32) -> [f32; 2] {
+    let [hue, saturation, _] = rgb_to_hsv(color);
+    let (sin, cos) = hue.to_radians().sin_cos();
+    let r = saturation * radius;
+    [center[0] + r * cos, center[1] + r * sin]
+}
+
+/// Inverse of `rgb_to_hsv`. The hue wraps around.
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> [u8; 3] {
+    let hue = hsv[0].rem_euclid(360.0);
+    let saturation = hsv[1].clamp(0.0, 1.0);
+    let value = hsv[2].clamp(0.0, 1.0);
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
+    ]
+}
+
+/// Interpolates between `a` and `b` in HSV space, going around the hue circle
+/// the short way, which avoids the greyish midpoints of RGB interpolation.
+pub fn lerp_hsv(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    let (a, b) = (rgb_to_hsv(a), rgb_to_hsv(b));
+    let mut hue_delta = (b[0] - a[0]).rem_euclid(360.0);
+    if hue_delta > 180.0 {
+        hue_delta -= 360.0;
+    }
+    hsv_to_rgb([
+        a[0] + hue_delta * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ])
+}
+
+pub fn lerp_rgb(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    let mut out = [0; 3];
+    for i in 0..3 {
+        let (a, b) = (f32::from(a[i]), f32::from(b[i]));
+        out[i] = (a + (b - a) * t).round().clamp(0.0, 255.0) as u8;
+    }
+    out
+}
+
+/// Color space in which gradients are interpolated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorInterpolation {
+    #[default]
+    Rgb,
+    Hsv,
+}
+
+impl ColorInterpolation {
+    pub fn lerp(self, a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+        match self {
+            ColorInterpolation::Rgb => lerp_rgb(a, b, t),
+            ColorInterpolation::Hsv => lerp_hsv(a, b, t),
+        }
+    }
+}
+
+/// "Rainbow pen" color source: every call to `next_color` shifts the hue by
+/// `speed` degrees, so successive points of a stroke cycle through the
+/// spectrum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HueCycle {
+    pub hue: f32,
+    /// Hue shift per point, in degrees.
+    pub speed: f32,
+    pub saturation: f32,
+    pub value: f32,
+}
+
+impl HueCycle {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            hue: 0.0,
+            speed,
+            saturation: 1.0,
+            value: 1.0,
+        }
+    }
+
+    pub fn next_color(&mut self) -> [u8; 3] {
+        let color = hsv_to_rgb([self.hue, self.saturation, self.value]);
+        self.hue = (self.hue + self.speed).rem_euclid(360.0);
+        color
+    }
+}
+
+/// Global tonal adjustment applied on top of the rendered canvas, leaving the
+/// stored stroke colors untouched. This is the reference for the post-process
+/// shader and is applied as-is when exporting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustment {
+    /// Added to every channel, in `[-1, 1]`.
+    pub brightness: f32,
+    /// Scale around mid grey; 1 leaves the image unchanged.
+    pub contrast: f32,
+    /// Hue rotation in degrees.
+    pub hue_shift: f32,
+}
+
+impl Adjustment {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, color: [u8; 3]) -> [u8; 3] {
+        if self.is_identity() {
+            return color;
+        }
+        let mut shifted = color;
+        if self.hue_shift != 0.0 {
+            let [h, s, v] = rgb_to_hsv(color);
+            shifted = hsv_to_rgb([h + self.hue_shift, s, v]);
+        }
+        let mut out = [0; 3];
+        for i in 0..3 {
+            let c = f32::from(shifted[i]) / 255.0;
+            let c = (c - 0.5) * self.contrast + 0.5 + self.brightness;
+            out[i] = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        out
+    }
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            hue_shift: 0.0,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Distinct types for the coordinate spaces positions pass through, so that
+//! the compiler rejects mixing them up. Conversions go through the functions
+//! in `camera`:
+//!
+//! - `ScreenPos`: window pixels, origin at the top-left, y down.
+//! - `Ndc`: normalized device coordinates, `[-1, 1]` across the window, y up.
+//! - `WorldPos`: canvas coordinates, in which strokes are stored.
+//!
+//! Stroke geometry keeps using plain `[f32; 2]` world points; these types
+//! guard the boundaries between spaces.
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! position {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+        pub struct $name(pub [f32; 2]);
+
+        impl $name {
+            pub fn new(x: f32, y: f32) -> Self {
+                Self([x, y])
+            }
+
+            pub fn x(self) -> f32 {
+                self.0[0]
+            }
+
+            pub fn y(self) -> f32 {
+                self.0[1]
+            }
+        }
+
+        impl From<$name> for [f32; 2] {
+            fn from(p: $name) -> Self {
+                p.0
+            }
+        }
+    };
+}
+
+position!(
+    /// A position in window pixels.
+    ScreenPos
+);
+position!(
+    /// A position in normalized device coordinates.
+    Ndc
+);
+position!(
+    /// A position on the canvas.
+    WorldPos
+);
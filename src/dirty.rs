@@ -0,0 +1,58 @@
+use crate::camera::Camera;
+use crate::coords::WorldPos;
+use crate::geometry;
+
+/// World-space area changed since the last frame, to limit clearing and
+/// redrawing to the part of the screen that needs it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirtyRegion {
+    rect: Option<([f32; 2], [f32; 2])>,
+}
+
+impl DirtyRegion {
+    pub fn add(&mut self, bounds: ([f32; 2], [f32; 2])) {
+        self.rect = Some(
+            self.rect
+                .map_or(bounds, |rect| geometry::union(rect, bounds)),
+        );
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.rect.is_none()
+    }
+
+    /// Returns the dirty area as a pixel rectangle `(x, y, width, height)`
+    /// with the origin at the bottom-left, as scissor boxes expect, and
+    /// resets the region. The rectangle is clipped to the window and grown by
+    /// a pixel to cover antialiased edges.
+    pub fn take_scissor(
+        &mut self,
+        camera: &Camera,
+        size: (u32, u32),
+    ) -> Option<(u32, u32, u32, u32)> {
+        let (min, max) = self.rect.take()?;
+        let corners = [
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [min[0], max[1]],
+            [max[0], max[1]],
+        ];
+        // Pixel rows grow downwards, so flip to get a bottom-left origin.
+        let pixels: Vec<[f32; 2]> = corners
+            .iter()
+            .map(|&c| {
+                let p = camera.world_to_screen(WorldPos(c), size).0;
+                [p[0], size.1 as f32 - p[1]]
+            })
+            .collect();
+        let (lo, hi) = geometry::bounds(&pixels)?;
+        let x0 = (lo[0].floor() - 1.0).max(0.0) as u32;
+        let y0 = (lo[1].floor() - 1.0).max(0.0) as u32;
+        let x1 = ((hi[0].ceil() + 1.0).max(0.0) as u32).min(size.0);
+        let y1 = ((hi[1].ceil() + 1.0).max(0.0) as u32).min(size.1);
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+        Some((x0, y0, x1 - x0, y1 - y0))
+    }
+}
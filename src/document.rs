@@ -0,0 +1,548 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::View;
+use crate::geometry;
+use crate::layer::Layer;
+use crate::settings::Settings;
+use crate::stroke::{self, Stroke};
+
+/// Version written in the `version` field of saved documents.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// First bytes of a binary document, followed by a version byte.
+pub const BINARY_MAGIC: &[u8; 4] = b"SKCB";
+
+/// Smallest fraction of the view the page may shrink to when zooming out.
+pub const MIN_PAGE_VIEW_FRACTION: f32 = 0.1;
+
+/// The fixed-size frame of a document on the infinite canvas, and the image
+/// frame used for export. The default page covers the world rectangle from
+/// (-1, -1) to (1, 1), what a window of its size shows at unit zoom; other
+/// sizes keep that pixel scale, so they are centered on the origin and
+/// extend in proportion to their width and height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page {
+    pub width: u32,
+    pub height: u32,
+    /// Print resolution, written to exports so they have the right physical
+    /// size.
+    #[serde(default = "default_dpi")]
+    pub dpi: u32,
+}
+
+/// DPI of pages that do not set one, matching CSS pixels.
+pub const DEFAULT_DPI: u32 = 96;
+
+fn default_dpi() -> u32 {
+    DEFAULT_DPI
+}
+
+impl Page {
+    pub fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let [x, y] = self.half_extent();
+        ([-x, -y], [x, y])
+    }
+
+    /// Half the world width and height of the page.
+    fn half_extent(&self) -> [f32; 2] {
+        let reference = Page::default();
+        [
+            self.width as f32 / reference.width as f32,
+            self.height as f32 / reference.height as f32,
+        ]
+    }
+
+    /// Physical size in inches at the page's DPI.
+    pub fn size_inches(&self) -> (f32, f32) {
+        let dpi = self.dpi.max(1) as f32;
+        (self.width as f32 / dpi, self.height as f32 / dpi)
+    }
+
+    /// Lowest zoom at which the page still spans `MIN_PAGE_VIEW_FRACTION` of
+    /// the view along its longer side. Larger pages may shrink to a lower
+    /// zoom.
+    pub fn min_zoom(&self) -> f32 {
+        let [x, y] = self.half_extent();
+        let extent = x.max(y);
+        if extent > 0.0 {
+            MIN_PAGE_VIEW_FRACTION / extent
+        } else {
+            MIN_PAGE_VIEW_FRACTION
+        }
+    }
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            width: 960,
+            height: 540,
+            dpi: DEFAULT_DPI,
+        }
+    }
+}
+
+/// A sketch made of layers, saved as JSON or in the compact binary format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub layers: Vec<Layer>,
+    pub page: Page,
+    /// Working environment restored when the document is reopened, over
+    /// the global config.
+    pub settings: Settings,
+    /// Pan, zoom and rotation the document was saved with, to apply with
+    /// `Camera::restore` on load.
+    pub view: View,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            layers: vec![Layer::new("Layer 1")],
+            page: Page::default(),
+            settings: Settings::default(),
+            view: View::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Encode(rmp_serde::encode::Error),
+    Decode(rmp_serde::decode::Error),
+    UnsupportedVersion(u32),
+    /// A stroke ID or creation index too large for new strokes to follow.
+    IdOverflow,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "i/o error: {}", err),
+            Error::Json(err) => write!(f, "invalid sketch file: {}", err),
+            Error::Encode(err) => write!(f, "cannot encode sketch: {}", err),
+            Error::Decode(err) => write!(f, "invalid binary sketch file: {}", err),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported sketch file version {}", version)
+            }
+            Error::IdOverflow => write!(f, "invalid sketch file: stroke ID out of range"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Error::Encode(err)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Error::Decode(err)
+    }
+}
+
+/// On-disk layout of the current format.
+#[derive(Serialize, Deserialize)]
+struct DocumentV2 {
+    version: u32,
+    layers: Vec<Layer>,
+    #[serde(default)]
+    page: Page,
+    #[serde(default, skip_serializing_if = "Settings::is_empty")]
+    settings: Settings,
+    /// Missing from files saved before views were kept, which open
+    /// centered at unit zoom.
+    #[serde(default)]
+    view: View,
+}
+
+/// Flat layout of files saved before layers existed.
+#[derive(Deserialize)]
+struct DocumentV1 {
+    strokes: Vec<Stroke>,
+}
+
+/// Just the `version` field of a JSON document, read first to pick the
+/// layout to parse the rest with. Files saved before layers existed have
+/// none.
+#[derive(Deserialize)]
+struct VersionField {
+    #[serde(default)]
+    version: Option<u32>,
+}
+
+impl Document {
+    /// Sets the canvas background, adding a background layer at the bottom
+    /// if there is none yet.
+    pub fn set_background(&mut self, color: [u8; 3]) {
+        match self.layers.first_mut() {
+            Some(layer) if layer.is_background() => layer.fill = Some(color),
+            _ => self.layers.insert(0, Layer::background(color)),
+        }
+    }
+
+    /// Color an export starts from: the visible background layer's fill,
+    /// unless a transparent export was requested.
+    pub fn background(&self, transparent: bool) -> Option<[u8; 3]> {
+        if transparent {
+            return None;
+        }
+        self.layers
+            .first()
+            .filter(|layer| layer.visible)
+            .and_then(|layer| layer.fill)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        // Parsing against the layout the file claims keeps the error of
+        // that layout, with its line and column, instead of one saying it
+        // matched no layout at all.
+        let VersionField { version } = serde_json::from_str(json)?;
+        let doc = match version {
+            None | Some(1) => {
+                let doc: DocumentV1 = serde_json::from_str(json)?;
+                let mut layer = Layer::new("Layer 1");
+                layer.strokes = doc.strokes;
+                Self {
+                    layers: vec![layer],
+                    ..Self::default()
+                }
+            }
+            Some(version) if version > CURRENT_VERSION => {
+                return Err(Error::UnsupportedVersion(version));
+            }
+            Some(_) => {
+                let doc: DocumentV2 = serde_json::from_str(json)?;
+                Self {
+                    layers: doc.layers,
+                    page: doc.page,
+                    settings: doc.settings,
+                    view: doc.view,
+                }
+            }
+        };
+        doc.reserve_ids()?;
+        Ok(doc)
+    }
+
+    /// Keeps new strokes from reusing the IDs of the loaded ones.
+    fn reserve_ids(&self) -> Result<(), Error> {
+        if stroke::reserve_ids(self.layers.iter().flat_map(|l| &l.strokes)) {
+            Ok(())
+        } else {
+            Err(Error::IdOverflow)
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        self.to_json_with_precision(None)
+    }
+
+    /// Like `to_json`, rounding stroke coordinates to `decimals` places when
+    /// given. The document itself keeps full precision. Four decimals is well
+    /// under a pixel on any page size; on freehand-heavy files it saves about
+    /// a quarter of the size, as coordinates otherwise print with eight or
+    /// nine significant digits.
+    pub fn to_json_with_precision(&self, decimals: Option<u32>) -> Result<String, Error> {
+        let mut layers = self.layers.clone();
+        if let Some(decimals) = decimals {
+            for stroke in layers.iter_mut().flat_map(|l| l.strokes.iter_mut()) {
+                geometry::round_points(&mut stroke.points, decimals);
+            }
+        }
+        let doc = DocumentV2 {
+            version: CURRENT_VERSION,
+            layers,
+            page: self.page,
+            settings: self.settings.clone(),
+            view: self.view,
+        };
+        Ok(serde_json::to_string(&doc)?)
+    }
+
+    /// Compact binary form: `BINARY_MAGIC`, a version byte, then the current
+    /// layout as MessagePack. Coordinates take 5 bytes each instead of the
+    /// 10 to 12 characters they print as in JSON.
+    pub fn to_binary(&self) -> Result<Vec<u8>, Error> {
+        let doc = DocumentV2 {
+            version: CURRENT_VERSION,
+            layers: self.layers.clone(),
+            page: self.page,
+            settings: self.settings.clone(),
+            view: self.view,
+        };
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(CURRENT_VERSION as u8);
+        // Named fields, so fields skipped when empty still decode.
+        rmp_serde::encode::write_named(&mut bytes, &doc)?;
+        Ok(bytes)
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, Error> {
+        let header = BINARY_MAGIC.len() + 1;
+        if bytes.len() < header || !Self::is_binary(bytes) {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a binary sketch file",
+            )));
+        }
+        let version = u32::from(bytes[BINARY_MAGIC.len()]);
+        if version > CURRENT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let doc: DocumentV2 = rmp_serde::from_slice(&bytes[header..])?;
+        let doc = Self {
+            layers: doc.layers,
+            page: doc.page,
+            settings: doc.settings,
+            view: doc.view,
+        };
+        doc.reserve_ids()?;
+        Ok(doc)
+    }
+
+    pub fn is_binary(bytes: &[u8]) -> bool {
+        bytes.starts_with(BINARY_MAGIC)
+    }
+
+    /// Loads a document in either format, telling them apart by the magic
+    /// number.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        if Self::is_binary(&bytes) {
+            return Self::from_binary(&bytes);
+        }
+        let json = String::from_utf8(bytes)
+            .map_err(|err| Error::Io(io::Error::new(io::ErrorKind::InvalidData, err)))?;
+        Self::from_json(&json)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.save_with_precision(path, None)
+    }
+
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_binary()?)?;
+        Ok(())
+    }
+
+    /// Same as `load`, which accepts both formats.
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::load(path)
+    }
+
+    pub fn save_with_precision<P: AsRef<Path>>(
+        &self,
+        path: P,
+        decimals: Option<u32>,
+    ) -> Result<(), Error> {
+        fs::write(path, self.to_json_with_precision(decimals)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::BlendMode;
+    use crate::coords::WorldPos;
+    use std::time::Duration;
+
+    fn layered() -> Document {
+        let mut ink = Layer::new("Ink");
+        ink.opacity = 0.5;
+        ink.blend = BlendMode::Multiply;
+        for i in 0..3 {
+            let mut stroke = Stroke::new([10 * i, 20, 30], 0.02);
+            stroke.push([i as f32 * 0.1, 0.0], Duration::from_millis(0));
+            stroke.push([i as f32 * 0.1, 0.5], Duration::from_millis(16));
+            ink.strokes.push(stroke);
+        }
+        let mut sketch = Layer::new("Sketch");
+        sketch.visible = false;
+        sketch.locked = true;
+        let mut doc = Document {
+            layers: vec![sketch, ink],
+            ..Document::default()
+        };
+        doc.set_background([250, 250, 240]);
+        doc
+    }
+
+    #[test]
+    fn json_round_trip_keeps_layers() {
+        let doc = layered();
+        let loaded = Document::from_json(&doc.to_json().unwrap()).unwrap();
+        assert_eq!(loaded, doc);
+        let names: Vec<&str> = loaded.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(&names[1..], ["Sketch", "Ink"]);
+    }
+
+    #[test]
+    fn binary_round_trip_keeps_layers() {
+        let doc = layered();
+        let bytes = doc.to_binary().unwrap();
+        assert!(Document::is_binary(&bytes));
+        assert_eq!(Document::from_binary(&bytes).unwrap(), doc);
+    }
+
+    #[test]
+    fn flat_files_load_into_one_layer() {
+        let json = r#"{"strokes":[
+            {"points":[[0.0,0.0],[1.0,1.0]],"color":[1,2,3],"width":0.01},
+            {"points":[[0.5,0.5]],"color":[4,5,6],"width":0.02}
+        ]}"#;
+        let doc = Document::from_json(json).unwrap();
+        assert_eq!(doc.layers.len(), 1);
+        let layer = &doc.layers[0];
+        assert_eq!(layer.name, "Layer 1");
+        assert!(layer.visible);
+        assert_eq!(layer.strokes.len(), 2);
+        assert_eq!(layer.strokes[1].color, [4, 5, 6]);
+        assert_ne!(layer.strokes[0].id, layer.strokes[1].id);
+
+        // Saving writes the layered format, which loads back the same.
+        let saved = doc.to_json().unwrap();
+        assert!(saved.contains("\"version\":2"));
+        assert_eq!(Document::from_json(&saved).unwrap(), doc);
+    }
+
+    #[test]
+    fn pages_extend_with_their_size() {
+        let page = Page::default();
+        assert_eq!(page.bounds(), ([-1.0, -1.0], [1.0, 1.0]));
+        assert_eq!(page.min_zoom(), MIN_PAGE_VIEW_FRACTION);
+
+        let portrait = Page {
+            width: 540,
+            height: 1080,
+            ..Page::default()
+        };
+        assert_eq!(portrait.bounds(), ([-0.5625, -2.0], [0.5625, 2.0]));
+        // At the lowest zoom the long side still spans a tenth of the view.
+        let camera = Camera {
+            zoom: portrait.min_zoom(),
+            ..Camera::default()
+        };
+        let (min, max) = portrait.bounds();
+        let top = camera.world_to_ndc(WorldPos([0.0, max[1]])).0[1];
+        let bottom = camera.world_to_ndc(WorldPos([0.0, min[1]])).0[1];
+        assert!(((top - bottom) / 2.0 - MIN_PAGE_VIEW_FRACTION).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_bad_field() {
+        let json = "{\"version\":2,\n\"layers\":[{\"name\":3}]}";
+        let message = Document::from_json(json).unwrap_err().to_string();
+        assert!(message.contains("invalid type"), "{}", message);
+        assert!(message.contains("line 2"), "{}", message);
+
+        let legacy = Document::from_json(r#"{"strokes":7}"#).unwrap_err();
+        assert!(legacy.to_string().contains("invalid type"), "{}", legacy);
+        assert!(Document::from_json("{\"version\":").is_err());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let json = r#"{"version":99,"layers":[]}"#;
+        assert!(matches!(
+            Document::from_json(json),
+            Err(Error::UnsupportedVersion(99))
+        ));
+        let mut bytes = Document::default().to_binary().unwrap();
+        bytes[BINARY_MAGIC.len()] = 99;
+        assert!(matches!(
+            Document::from_binary(&bytes),
+            Err(Error::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn out_of_range_ids_are_rejected() {
+        for field in &["id", "created"] {
+            let json = format!(
+                r#"{{"strokes":[{{"{}":18446744073709551615,"points":[],"color":[0,0,0],"width":1.0}}]}}"#,
+                field
+            );
+            assert!(matches!(Document::from_json(&json), Err(Error::IdOverflow)));
+        }
+
+        let mut doc = layered();
+        doc.layers[2].strokes[0].id = u64::MAX;
+        let bytes = doc.to_binary().unwrap();
+        assert!(matches!(
+            Document::from_binary(&bytes),
+            Err(Error::IdOverflow)
+        ));
+    }
+
+    #[test]
+    fn the_view_round_trips() {
+        let mut camera = Camera {
+            aspect: 2.0,
+            ..Camera::default()
+        };
+        camera.zoom_at(3.0, WorldPos([0.4, -0.2]));
+        camera.rotate(0.5);
+        let doc = Document {
+            view: camera.view(),
+            ..Document::default()
+        };
+        let loaded = Document::from_json(&doc.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.view, doc.view);
+        let loaded = Document::from_binary(&doc.to_binary().unwrap()).unwrap();
+        assert_eq!(loaded.view, doc.view);
+
+        let mut reopened = Camera::default();
+        reopened.restore(loaded.view);
+        assert_eq!(reopened.pan, camera.pan);
+        assert_eq!(reopened.zoom, camera.zoom);
+        assert_eq!(reopened.rotation, camera.rotation);
+        assert_eq!(reopened.aspect, 1.0);
+    }
+
+    #[test]
+    fn files_without_a_view_open_centered() {
+        let doc = Document::from_json(r#"{"version":2,"layers":[]}"#).unwrap();
+        assert_eq!(doc.view, View::default());
+        let mut camera = Camera {
+            pan: [5.0, 5.0],
+            zoom: 4.0,
+            ..Camera::default()
+        };
+        camera.restore(doc.view);
+        assert_eq!((camera.pan, camera.zoom), ([0.0, 0.0], 1.0));
+
+        let partial: View = serde_json::from_str(r#"{"zoom":2.0}"#).unwrap();
+        assert_eq!(partial.center, [0.0, 0.0]);
+        camera.restore(View {
+            zoom: 0.0,
+            ..partial
+        });
+        assert_eq!(camera.zoom, 1.0);
+    }
+}
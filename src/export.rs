@@ -0,0 +1,777 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::color::srgb_to_linear;
+use crate::document::{self, Document, Page};
+use crate::layer::Layer;
+use crate::render;
+use crate::stroke::Stroke;
+
+/// 4x4 Bayer threshold matrix.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Applies ordered dithering to tightly packed RGBA8 `pixels`, `width` pixels
+/// per row, nudging color channels by up to one step so that smooth
+/// gradients do not show visible bands. Alpha is left untouched.
+pub fn dither_rgba(pixels: &mut [u8], width: u32) {
+    let width = width.max(1) as usize;
+    for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let threshold = (f32::from(BAYER_4X4[y % 4][x % 4]) + 0.5) / 16.0;
+        let offset = threshold * 2.0 - 1.0;
+        for channel in &mut pixel[..3] {
+            *channel = (f32::from(*channel) + offset).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Scales an export of `requested` (width, height) pixels down to fit within
+/// `max` pixels on each side, the GPU's largest renderbuffer, keeping its
+/// aspect ratio. Each side stays at least one pixel. Rendering past the
+/// limit fails on some drivers or takes the whole process down, so this is
+/// checked before allocating the export framebuffer.
+pub fn clamp_export_size(requested: (u32, u32), max: u32) -> (u32, u32) {
+    let (width, height) = requested;
+    let max = max.max(1);
+    if width <= max && height <= max {
+        return requested;
+    }
+    let scale = f64::from(max) / f64::from(width.max(height));
+    let fit = |side: u32| ((f64::from(side) * scale).floor() as u32).clamp(1, max);
+    let clamped = (fit(width), fit(height));
+    log::warn!(
+        "export size {}x{} exceeds the GPU limit of {} pixels, exporting at {}x{}",
+        width,
+        height,
+        max,
+        clamped.0,
+        clamped.1
+    );
+    clamped
+}
+
+/// Largest rectangle of `aspect` (width over height) centered in an image
+/// of `size` pixels, as (x, y, width, height) from the top left. Content is
+/// rendered into it with the bars around it left to the background, so an
+/// export keeps the document's shape whatever the window's.
+pub fn letterbox_rect(size: (u32, u32), aspect: f32) -> (u32, u32, u32, u32) {
+    let (width, height) = size;
+    if aspect <= 0.0 || !aspect.is_finite() || width == 0 || height == 0 {
+        return (0, 0, width, height);
+    }
+    let (w, h) = if width as f32 / height as f32 > aspect {
+        // Wider than the target: bars left and right.
+        (
+            ((height as f32 * aspect).round() as u32).clamp(1, width),
+            height,
+        )
+    } else {
+        (
+            width,
+            ((width as f32 / aspect).round() as u32).clamp(1, height),
+        )
+    };
+    ((width - w) / 2, (height - h) / 2, w, h)
+}
+
+/// Bars keeping exports at a fixed aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    /// Target width over height, or `None` to fill the whole image.
+    pub aspect: Option<f32>,
+    pub color: [u8; 4],
+}
+
+impl Letterbox {
+    /// Aspect ratio of `page`.
+    pub fn for_page(page: &Page) -> Self {
+        Self {
+            aspect: Some(page.width.max(1) as f32 / page.height.max(1) as f32),
+            ..Self::default()
+        }
+    }
+
+    /// Viewport to render into within an image of `size`.
+    pub fn viewport(&self, size: (u32, u32)) -> (u32, u32, u32, u32) {
+        match self.aspect {
+            Some(aspect) => letterbox_rect(size, aspect),
+            None => (0, 0, size.0, size.1),
+        }
+    }
+
+    /// Paints the bars outside the viewport of tightly packed RGBA8
+    /// `pixels`, `size` pixels large, in the bar color.
+    pub fn fill_bars(&self, pixels: &mut [u8], size: (u32, u32)) {
+        let (x, y, w, h) = self.viewport(size);
+        let width = size.0 as usize;
+        for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+            let (px, py) = ((i % width.max(1)) as u32, (i / width.max(1)) as u32);
+            if px < x || px >= x + w || py < y || py >= y + h {
+                pixel.copy_from_slice(&self.color);
+            }
+        }
+    }
+}
+
+impl Default for Letterbox {
+    fn default() -> Self {
+        Self {
+            aspect: None,
+            color: [128, 128, 128, 255],
+        }
+    }
+}
+
+/// CRC-32 as used by PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Complete PNG `pHYs` chunk (length, type, data and CRC) declaring `dpi`,
+/// to be written right after `IHDR`.
+pub fn png_phys_chunk(dpi: u32) -> Vec<u8> {
+    // PNG stores pixels per meter.
+    let ppm = (f64::from(dpi) / 0.0254).round() as u32;
+    let mut data = ppm.to_be_bytes().to_vec();
+    data.extend_from_slice(&ppm.to_be_bytes());
+    // Unit specifier: meters.
+    data.push(1);
+    png_chunk(b"pHYs", &data)
+}
+
+/// Adler-32 checksum closing a zlib stream.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// Length, type, data and CRC of a PNG chunk.
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&body);
+    chunk.extend_from_slice(&crc32(&body).to_be_bytes());
+    chunk
+}
+
+/// Encoding of the color channels of an exported image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma encoded, as the canvas is displayed; what image viewers and
+    /// the web expect.
+    #[default]
+    Srgb,
+    /// Linear light, for compositing and rendering pipelines that expect
+    /// linear input. Eight bits are coarse in the darks without the sRGB
+    /// curve, so subtle dark gradients may band.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Converts readback `pixels`, RGBA8 in sRGB as stored in the
+    /// framebuffer after the linear blending of `color`, into this space.
+    /// Alpha is left untouched.
+    pub fn convert_readback(self, pixels: &mut [u8]) {
+        if self == ColorSpace::Srgb {
+            return;
+        }
+        // Only 256 possible inputs, so convert through a lookup table.
+        let mut table = [0u8; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = (srgb_to_linear(i as f32 / 255.0) * 255.0).round() as u8;
+        }
+        for pixel in pixels.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = table[*channel as usize];
+            }
+        }
+    }
+
+    /// PNG chunk telling readers how to interpret the channels: `sRGB`, or
+    /// `gAMA` with a gamma of 1 for linear data.
+    fn png_chunk(self) -> Vec<u8> {
+        match self {
+            // Perceptual rendering intent.
+            ColorSpace::Srgb => png_chunk(b"sRGB", &[0]),
+            ColorSpace::Linear => png_chunk(b"gAMA", &100_000u32.to_be_bytes()),
+        }
+    }
+}
+
+/// Encodes tightly packed RGBA8 `pixels`, read back in sRGB, as a PNG in
+/// `space` declaring `dpi`. With `dither` set, `dither_rgba` is applied
+/// after the conversion to `space`, against banding in smooth gradients.
+/// The image data is stored uncompressed, which keeps the encoder trivial
+/// at the cost of file size. Fails with `InvalidInput` unless `pixels` holds
+/// exactly `width` by `height` pixels.
+pub fn encode_png(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    dpi: u32,
+    space: ColorSpace,
+    dither: bool,
+) -> io::Result<Vec<u8>> {
+    let row = width as usize * 4;
+    if row.checked_mul(height as usize) != Some(pixels.len()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes of pixels for a {}x{} image",
+                pixels.len(),
+                width,
+                height
+            ),
+        ));
+    }
+    let mut converted = pixels.to_vec();
+    space.convert_readback(&mut converted);
+    if dither {
+        dither_rgba(&mut converted, width);
+    }
+    // Each scanline is prefixed by filter type 0 (none).
+    let mut raw = Vec::with_capacity((row + 1) * height as usize);
+    for line in converted.chunks(row.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = width.to_be_bytes().to_vec();
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filter and interlace.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend(png_chunk(b"IHDR", &header));
+    png.extend(space.png_chunk());
+    png.extend(png_phys_chunk(dpi));
+    png.extend(png_chunk(b"IDAT", &zlib));
+    png.extend(png_chunk(b"IEND", &[]));
+    Ok(png)
+}
+
+/// File name of layer `index` in a layer export: the index keeps the files
+/// in stacking order, the name makes them recognizable.
+pub fn layer_file_name(index: usize, name: &str) -> String {
+    let lowered = name.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let slug = if words.is_empty() {
+        "layer".to_string()
+    } else {
+        words.join("-")
+    };
+    format!("{:02}-{}.png", index, slug)
+}
+
+/// Writes every layer to its own PNG in `dir`, for compositing in other
+/// tools. `render` draws a single layer over a transparent background and
+/// returns its width, height and RGBA8 pixels. Hidden layers are skipped
+/// unless `include_hidden` is set. Returns the paths written.
+pub fn export_layers<F>(
+    layers: &[Layer],
+    dir: &Path,
+    include_hidden: bool,
+    dpi: u32,
+    space: ColorSpace,
+    dither: bool,
+    mut render: F,
+) -> io::Result<Vec<PathBuf>>
+where
+    F: FnMut(&Layer) -> (u32, u32, Vec<u8>),
+{
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (index, layer) in layers.iter().enumerate() {
+        if !layer.visible && !include_hidden {
+            continue;
+        }
+        let (width, height, pixels) = render(layer);
+        let path = dir.join(layer_file_name(index, &layer.name));
+        fs::write(
+            &path,
+            encode_png(width, height, &pixels, dpi, space, dither)?,
+        )?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Grid of sketch thumbnails on a single image, for reviewing many sketches
+/// at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactSheet {
+    pub columns: u32,
+    /// Size of each cell in pixels; thumbnails are centered in it.
+    pub cell: (u32, u32),
+    /// Gap around and between cells, in pixels.
+    pub padding: u32,
+    /// Space kept below each cell for its label.
+    pub label_height: u32,
+    pub background: [u8; 4],
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            cell: (256, 256),
+            padding: 16,
+            label_height: 20,
+            background: [255, 255, 255, 255],
+        }
+    }
+}
+
+impl ContactSheet {
+    /// Size of a sheet holding `count` sketches.
+    pub fn size(&self, count: usize) -> (u32, u32) {
+        let columns = self.columns.max(1);
+        let rows = (count as u32).div_ceil(columns).max(1);
+        let (cw, ch) = self.cell;
+        (
+            self.padding + columns * (cw + self.padding),
+            self.padding + rows * (ch + self.label_height + self.padding),
+        )
+    }
+
+    /// Top-left corner of cell `index`, and of the label strip below it.
+    pub fn cell_origin(&self, index: usize) -> ((u32, u32), (u32, u32)) {
+        let columns = self.columns.max(1) as usize;
+        let (col, row) = ((index % columns) as u32, (index / columns) as u32);
+        let (cw, ch) = self.cell;
+        let x = self.padding + col * (cw + self.padding);
+        let y = self.padding + row * (ch + self.label_height + self.padding);
+        ((x, y), (x, y + ch))
+    }
+
+    /// Composes RGBA8 `thumbnails` (width, height, pixels), in order, into a
+    /// sheet, returning its width, height and pixels. Thumbnails larger than
+    /// a cell are cropped to it.
+    pub fn compose(&self, thumbnails: &[(u32, u32, Vec<u8>)]) -> (u32, u32, Vec<u8>) {
+        let (width, height) = self.size(thumbnails.len());
+        let mut sheet: Vec<u8> = self
+            .background
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .collect();
+        for (index, (tw, th, pixels)) in thumbnails.iter().enumerate() {
+            let ((cx, cy), _) = self.cell_origin(index);
+            let (w, h) = ((*tw).min(self.cell.0), (*th).min(self.cell.1));
+            let (ox, oy) = (cx + (self.cell.0 - w) / 2, cy + (self.cell.1 - h) / 2);
+            for y in 0..h {
+                let src = (y * tw * 4) as usize;
+                let dst = (((oy + y) * width + ox) * 4) as usize;
+                let row = (w * 4) as usize;
+                if let Some(line) = pixels.get(src..src + row) {
+                    sheet[dst..dst + row].copy_from_slice(line);
+                }
+            }
+        }
+        (width, height, sheet)
+    }
+}
+
+/// Loads every sketch in `inputs`, renders each with `thumbnail`, which
+/// returns an RGBA8 image fitting `sheet.cell`, and writes the grid to
+/// `output` as an sRGB PNG.
+pub fn export_contact_sheet<F>(
+    inputs: &[PathBuf],
+    output: &Path,
+    sheet: &ContactSheet,
+    mut thumbnail: F,
+) -> Result<(), document::Error>
+where
+    F: FnMut(&Document, (u32, u32)) -> (u32, u32, Vec<u8>),
+{
+    let mut thumbnails = Vec::with_capacity(inputs.len());
+    for path in inputs {
+        let doc = Document::load(path)?;
+        thumbnails.push(thumbnail(&doc, sheet.cell));
+    }
+    let (width, height, pixels) = sheet.compose(&thumbnails);
+    fs::write(
+        output,
+        encode_png(
+            width,
+            height,
+            &pixels,
+            document::DEFAULT_DPI,
+            ColorSpace::Srgb,
+            false,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Renders the sketch at `input` without a window and writes it to `output`
+/// as an sRGB PNG, `size` pixels large or at the page size, for batch
+/// conversion from the command line. With `transparent` set the background
+/// is left out, for compositing the strokes elsewhere.
+pub fn render_file(
+    input: &Path,
+    output: &Path,
+    size: Option<(u32, u32)>,
+    transparent: bool,
+) -> Result<(), document::Error> {
+    let doc = Document::load(input)?;
+    let (width, height) = size.unwrap_or((doc.page.width, doc.page.height));
+    let size = (width.max(1), height.max(1));
+    let pixels = render::render_document(&doc, size, transparent);
+    fs::write(
+        output,
+        encode_png(
+            size.0,
+            size.1,
+            &pixels,
+            doc.page.dpi,
+            ColorSpace::Srgb,
+            false,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Side of the thumbnails saved alongside sketches, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+/// Where the thumbnail of the sketch at `path` goes: `name.json` gets
+/// `name.thumb.png` next to it.
+pub fn thumbnail_path(path: &Path) -> PathBuf {
+    path.with_extension("thumb.png")
+}
+
+/// Saves `doc` to `path` like `Document::save`, along with a thumbnail of
+/// its visible layers at `thumbnail_path(path)` for file browsers.
+pub fn save_with_thumbnail(doc: &Document, path: &Path) -> Result<(), document::Error> {
+    doc.save(path)?;
+    let strokes: Vec<Stroke> = doc
+        .layers
+        .iter()
+        .filter(|layer| layer.visible)
+        .flat_map(|layer| layer.strokes.iter().cloned())
+        .collect();
+    let pixels = render::render_thumbnail(&strokes, THUMBNAIL_SIZE);
+    fs::write(
+        thumbnail_path(path),
+        encode_png(
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+            &pixels,
+            document::DEFAULT_DPI,
+            ColorSpace::Srgb,
+            false,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Version of the web JSON schema, bumped independently of the sketch file
+/// format and only when existing readers would break.
+pub const WEB_JSON_VERSION: u32 = 1;
+
+/// Root of the web JSON export. The schema, meant for drawing on an HTML
+/// canvas without knowing the sketch format:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "width": 960,
+///   "height": 540,
+///   "paths": [
+///     { "points": [[x, y], ...], "color": "#rrggbb", "width": 2.5 }
+///   ]
+/// }
+/// ```
+///
+/// Coordinates and widths are canvas pixels, with the origin at the top
+/// left and y pointing down. Paths are listed bottom to top and are meant
+/// to be stroked with round caps and joins. `tests/fixtures/web_sketch.json`
+/// is a complete example, checked against the exporter.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebSketch {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub paths: Vec<WebPath>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebPath {
+    pub points: Vec<[f32; 2]>,
+    pub color: String,
+    pub width: f32,
+}
+
+impl WebSketch {
+    /// `strokes` mapped from world space onto a `width` by `height` canvas.
+    /// Strokes with fewer than two points are left out.
+    pub fn new(strokes: &[Stroke], width: u32, height: u32) -> Self {
+        let (w, h) = (width as f32, height as f32);
+        let paths = strokes
+            .iter()
+            .filter(|s| s.points.len() >= 2)
+            .map(|s| {
+                let [r, g, b] = s.color;
+                WebPath {
+                    points: s
+                        .points
+                        .iter()
+                        .map(|p| [(p[0] + 1.0) / 2.0 * w, (1.0 - p[1]) / 2.0 * h])
+                        .collect(),
+                    color: format!("#{:02x}{:02x}{:02x}", r, g, b),
+                    // World space spans two units across the canvas width.
+                    width: s.width * w / 2.0,
+                }
+            })
+            .collect();
+        Self {
+            version: WEB_JSON_VERSION,
+            width,
+            height,
+            paths,
+        }
+    }
+}
+
+/// Writes `strokes` to `path` as web JSON (see `WebSketch`) for a `width` by
+/// `height` canvas.
+pub fn export_web_json(
+    path: &Path,
+    strokes: &[Stroke],
+    width: u32,
+    height: u32,
+) -> Result<(), document::Error> {
+    let json = serde_json::to_string(&WebSketch::new(strokes, width, height))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Size attributes of the root `<svg>` element for `page`: physical width
+/// and height in inches, with a pixel `viewBox` so content coordinates stay
+/// in pixels.
+pub fn svg_size_attributes(page: &Page) -> String {
+    let (width, height) = page.size_inches();
+    format!(
+        "width=\"{:.4}in\" height=\"{:.4}in\" viewBox=\"0 0 {} {}\"",
+        width, height, page.width, page.height
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The documented web JSON schema, as a web viewer would receive it.
+    const WEB_SKETCH: &str = include_str!("../tests/fixtures/web_sketch.json");
+
+    fn stroke(points: &[[f32; 2]], color: [u8; 3], width: f32) -> Stroke {
+        let mut stroke = Stroke::new(color, width);
+        stroke.points = points.to_vec();
+        stroke
+    }
+
+    fn fixture_strokes() -> Vec<Stroke> {
+        vec![
+            stroke(&[[-1.0, 1.0], [0.0, 0.0], [1.0, -1.0]], [255, 128, 0], 0.25),
+            // A lone dot has no path to draw.
+            stroke(&[[0.3, 0.3]], [1, 2, 3], 0.5),
+            stroke(&[[0.5, 0.5], [-0.5, -0.5]], [0, 0, 0], 0.0625),
+        ]
+    }
+
+    #[test]
+    fn web_json_matches_the_fixture() {
+        let sketch = WebSketch::new(&fixture_strokes(), 200, 100);
+        let actual = serde_json::to_value(&sketch).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(WEB_SKETCH).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn export_web_json_writes_the_fixture() {
+        let path = std::env::temp_dir().join(format!("sketched-web-{}.json", std::process::id()));
+        export_web_json(&path, &fixture_strokes(), 200, 100).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let actual: serde_json::Value = serde_json::from_str(&written).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(WEB_SKETCH).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    /// Scanlines of a PNG written by `encode_png`, filter bytes removed.
+    fn png_pixels(png: &[u8], width: u32) -> Vec<u8> {
+        let mut at = 8;
+        let zlib = loop {
+            let len = u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]) as usize;
+            if &png[at + 4..at + 8] == b"IDAT" {
+                break &png[at + 8..at + 8 + len];
+            }
+            at += len + 12;
+        };
+        let mut raw = Vec::new();
+        let mut block = &zlib[2..zlib.len() - 4];
+        while !block.is_empty() {
+            let len = usize::from(u16::from_le_bytes([block[1], block[2]]));
+            raw.extend_from_slice(&block[5..5 + len]);
+            block = &block[5 + len..];
+        }
+        raw.chunks(width as usize * 4 + 1)
+            .flat_map(|line| line[1..].to_vec())
+            .collect()
+    }
+
+    fn flat(width: u32, height: u32, value: u8) -> Vec<u8> {
+        [value, value, value, 200]
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .collect()
+    }
+
+    #[test]
+    fn dithering_nudges_colors_by_at_most_one_step() {
+        let original = flat(8, 8, 100);
+        let mut pixels = original.clone();
+        dither_rgba(&mut pixels, 8);
+        assert_ne!(pixels, original);
+        for (dithered, before) in pixels.chunks(4).zip(original.chunks(4)) {
+            assert_eq!(dithered[3], before[3]);
+            assert!(dithered[..3].iter().all(|&c| (99..=101).contains(&c)));
+        }
+        let mut white = flat(4, 4, 255);
+        dither_rgba(&mut white, 4);
+        assert!(white.chunks(4).all(|p| p[..3].iter().all(|&c| c >= 254)));
+    }
+
+    #[test]
+    fn png_export_rejects_pixels_of_the_wrong_size() {
+        let pixels = flat(4, 4, 100);
+        for &(width, height) in &[(4, 5), (3, 4), (u32::MAX, u32::MAX)] {
+            let err = encode_png(width, height, &pixels, 96, ColorSpace::Srgb, false).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(encode_png(4, 4, &pixels[..60], 96, ColorSpace::Srgb, false).is_err());
+        assert!(encode_png(0, 0, &[], 96, ColorSpace::Srgb, false).is_ok());
+    }
+
+    #[test]
+    fn png_export_dithers_only_when_asked() {
+        let pixels = flat(8, 4, 100);
+        let plain = encode_png(8, 4, &pixels, 96, ColorSpace::Srgb, false).unwrap();
+        assert_eq!(png_pixels(&plain, 8), pixels);
+
+        let dithered = encode_png(8, 4, &pixels, 96, ColorSpace::Srgb, true).unwrap();
+        let mut expected = pixels.clone();
+        dither_rgba(&mut expected, 8);
+        assert_eq!(png_pixels(&dithered, 8), expected);
+        assert_ne!(expected, pixels);
+    }
+
+    #[test]
+    fn png_export_dithers_after_the_color_conversion() {
+        let pixels = flat(4, 4, 180);
+        let png = encode_png(4, 4, &pixels, 96, ColorSpace::Linear, true).unwrap();
+        let mut expected = pixels.clone();
+        ColorSpace::Linear.convert_readback(&mut expected);
+        dither_rgba(&mut expected, 4);
+        assert_eq!(png_pixels(&png, 4), expected);
+    }
+
+    #[test]
+    fn render_file_writes_the_page_as_png() {
+        let dir = std::env::temp_dir().join(format!("sketched-render-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.json"), dir.join("out.png"));
+        let mut doc = Document::default();
+        doc.set_background([255, 255, 255]);
+        doc.save(&input).unwrap();
+        render_file(&input, &output, Some((6, 4)), false).unwrap();
+        let png = fs::read(&output).unwrap();
+        assert!(render_file(&dir.join("missing.json"), &output, None, false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(&png[16..24], &[0, 0, 0, 6, 0, 0, 0, 4]);
+        assert_eq!(
+            png_pixels(&png, 6),
+            render::clear((6, 4), Some([255, 255, 255]))
+        );
+    }
+
+    #[test]
+    fn transparent_exports_keep_alpha_where_nothing_is_drawn() {
+        let dir = std::env::temp_dir().join(format!("sketched-alpha-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.json"), dir.join("out.png"));
+        let mut doc = Document::default();
+        doc.set_background([255, 255, 255]);
+        let mut ink = Stroke::new([255, 0, 0], 0.5);
+        ink.points = vec![[-1.0, 0.0], [1.0, 0.0]];
+        doc.layers[1].strokes.push(ink);
+        doc.save(&input).unwrap();
+        render_file(&input, &output, Some((8, 8)), true).unwrap();
+        let png = fs::read(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let pixels = png_pixels(&png, 8);
+        let at = |x: usize, y: usize| &pixels[(y * 8 + x) * 4..(y * 8 + x) * 4 + 4];
+        assert_eq!(at(0, 0), &[0, 0, 0, 0]);
+        assert_eq!(at(4, 4), &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn sketches_are_saved_with_a_thumbnail() {
+        let dir = std::env::temp_dir().join(format!("sketched-thumb-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sketch.json");
+        let mut doc = Document::default();
+        doc.layers[0].strokes = fixture_strokes();
+        save_with_thumbnail(&doc, &path).unwrap();
+        let loaded = Document::load(&path).unwrap();
+        let png = fs::read(thumbnail_path(&path)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(thumbnail_path(&path), dir.join("sketch.thumb.png"));
+        assert_eq!(loaded.layers[0].strokes.len(), 3);
+        assert_eq!(
+            png_pixels(&png, THUMBNAIL_SIZE),
+            render::render_thumbnail(&doc.layers[0].strokes, THUMBNAIL_SIZE)
+        );
+    }
+}
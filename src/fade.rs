@@ -0,0 +1,79 @@
+//! Disappearing ink: strokes fade out point by point and are removed once
+//! their lifetime is over.
+//!
+//! Point timestamps are relative to the start of their stroke, so the mode
+//! records when each stroke started on the session clock.
+
+use std::collections::HashMap;
+
+use crate::stroke::Stroke;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FadingInk {
+    /// How long each point stays on the canvas, in seconds.
+    pub lifetime: f32,
+    /// Length of the opacity ramp at the end of the lifetime, in seconds.
+    pub fade: f32,
+    /// Session time at which each stroke, by ID, started.
+    born: HashMap<u64, f32>,
+}
+
+impl FadingInk {
+    pub fn new(lifetime: f32, fade: f32) -> Self {
+        Self {
+            lifetime,
+            fade,
+            born: HashMap::new(),
+        }
+    }
+
+    /// Records that `stroke` started at session time `now`. Strokes never
+    /// tracked are treated as starting when first asked about.
+    pub fn track(&mut self, stroke: &Stroke, now: f32) {
+        self.born.entry(stroke.id).or_insert(now);
+    }
+
+    /// Opacity of each point of `stroke` at session time `now`.
+    pub fn point_alphas(&mut self, stroke: &Stroke, now: f32) -> Vec<u8> {
+        let born = *self.born.entry(stroke.id).or_insert(now);
+        (0..stroke.points.len())
+            .map(|i| {
+                let offset = stroke.timestamps.get(i).copied().unwrap_or(0.0);
+                self.alpha(now - born - offset)
+            })
+            .collect()
+    }
+
+    /// Opacity of a point `age` seconds old: opaque until the fade starts,
+    /// then ramping down to zero at the end of its lifetime.
+    fn alpha(&self, age: f32) -> u8 {
+        let fade = self
+            .fade
+            .clamp(f32::EPSILON, self.lifetime.max(f32::EPSILON));
+        let remaining = self.lifetime - age;
+        ((remaining / fade).clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+
+    /// Removes the strokes whose last point has outlived the lifetime at
+    /// `now`, returning how many were removed.
+    pub fn prune(&mut self, strokes: &mut Vec<Stroke>, now: f32) -> usize {
+        let before = strokes.len();
+        let lifetime = self.lifetime;
+        let born = &self.born;
+        strokes.retain(|s| {
+            let last = s.timestamps.last().copied().unwrap_or(0.0);
+            born.get(&s.id)
+                .is_none_or(|&start| now - start - last <= lifetime)
+        });
+        let alive: Vec<u64> = strokes.iter().map(|s| s.id).collect();
+        self.born.retain(|id, _| alive.contains(id));
+        before - strokes.len()
+    }
+}
+
+impl Default for FadingInk {
+    /// Ten seconds per point, fading over the last three.
+    fn default() -> Self {
+        Self::new(10.0, 3.0)
+    }
+}
@@ -0,0 +1,50 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::stroke::Stroke;
+
+/// Strokes streamed as newline-delimited JSON, one stroke record per line,
+/// e.g. from a generative script piped into stdin. Lines are read on a
+/// separate thread so the main loop only has to poll for new strokes.
+pub struct StrokeFeed {
+    receiver: Receiver<Stroke>,
+}
+
+impl StrokeFeed {
+    pub fn spawn<R: BufRead + Send + 'static>(reader: R) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for (number, line) in reader.lines().enumerate() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => {
+                        log::warn!("stopped reading strokes: {}", err);
+                        break;
+                    }
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Stroke>(&line) {
+                    Ok(stroke) => {
+                        if sender.send(stroke).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => log::warn!("skipping stroke on line {}: {}", number + 1, err),
+                }
+            }
+        });
+        Self { receiver }
+    }
+
+    pub fn stdin() -> Self {
+        Self::spawn(std::io::BufReader::new(std::io::stdin()))
+    }
+
+    /// Strokes received since the last call, without blocking.
+    pub fn poll(&self) -> Vec<Stroke> {
+        self.receiver.try_iter().collect()
+    }
+}
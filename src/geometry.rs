@@ -0,0 +1,482 @@
+/// Evaluates the quadratic Bézier defined by `p0`, control point `p1` and `p2`
+/// into a polyline of `samples` segments, including both end points.
+pub fn quad_bezier(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], samples: usize) -> Vec<[f32; 2]> {
+    let samples = samples.max(1);
+    (0..=samples)
+        .map(|i| {
+            let t = i as f32 / samples as f32;
+            let u = 1.0 - t;
+            let a = u * u;
+            let b = 2.0 * u * t;
+            let c = t * t;
+            [
+                a * p0[0] + b * p1[0] + c * p2[0],
+                a * p0[1] + b * p1[1] + c * p2[1],
+            ]
+        })
+        .collect()
+}
+
+/// Evaluates the cubic Bézier defined by `p0`, control points `p1`, `p2` and
+/// `p3` into a polyline of `samples` segments, including both end points.
+pub fn cubic_bezier(
+    p0: [f32; 2],
+    p1: [f32; 2],
+    p2: [f32; 2],
+    p3: [f32; 2],
+    samples: usize,
+) -> Vec<[f32; 2]> {
+    let samples = samples.max(1);
+    (0..=samples)
+        .map(|i| {
+            let t = i as f32 / samples as f32;
+            let u = 1.0 - t;
+            let a = u * u * u;
+            let b = 3.0 * u * u * t;
+            let c = 3.0 * u * t * t;
+            let d = t * t * t;
+            [
+                a * p0[0] + b * p1[0] + c * p2[0] + d * p3[0],
+                a * p0[1] + b * p1[1] + c * p2[1] + d * p3[1],
+            ]
+        })
+        .collect()
+}
+
+/// Drops points that would land less than one pixel away from the previously
+/// kept point once `points` (in NDC) are zoomed by `zoom` and mapped onto a
+/// `win` sized window. The first and last points are always kept.
+pub fn decimate_for_view(points: &[[f32; 2]], zoom: f32, win: (u32, u32)) -> Vec<[f32; 2]> {
+    let scale = [zoom * win.0 as f32 / 2.0, zoom * win.1 as f32 / 2.0];
+    let mut kept: Vec<[f32; 2]> = Vec::with_capacity(points.len());
+    for (i, &point) in points.iter().enumerate() {
+        let is_last = i + 1 == points.len();
+        match kept.last() {
+            Some(&prev) if !is_last => {
+                let dx = (point[0] - prev[0]) * scale[0];
+                let dy = (point[1] - prev[1]) * scale[1];
+                if dx * dx + dy * dy >= 1.0 {
+                    kept.push(point);
+                }
+            }
+            _ => kept.push(point),
+        }
+    }
+    kept
+}
+
+/// Generates `segments` points around the ellipse centered on `center` with
+/// the given `radii`, without repeating the first point.
+pub fn ellipse_points(center: [f32; 2], radii: [f32; 2], segments: usize) -> Vec<[f32; 2]> {
+    let segments = segments.max(3);
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            [
+                center[0] + radii[0] * angle.cos(),
+                center[1] + radii[1] * angle.sin(),
+            ]
+        })
+        .collect()
+}
+
+/// Length of the segments approximating an ellipse outline, in pixels.
+const ELLIPSE_SEGMENT_LENGTH: f32 = 4.0;
+const ELLIPSE_MIN_SEGMENTS: usize = 12;
+const ELLIPSE_MAX_SEGMENTS: usize = 256;
+
+/// Picks a segment count for an ellipse whose largest on-screen radius is
+/// `radius_px`, so the outline is made of roughly equal length segments.
+pub fn ellipse_segments(radius_px: f32) -> usize {
+    let circumference = std::f32::consts::TAU * radius_px.max(0.0);
+    let segments = (circumference / ELLIPSE_SEGMENT_LENGTH).ceil() as usize;
+    segments.clamp(ELLIPSE_MIN_SEGMENTS, ELLIPSE_MAX_SEGMENTS)
+}
+
+/// Maximum recursion depth of `adaptive_smooth` per segment, i.e. at most
+/// 2^depth samples between two input points.
+const ADAPTIVE_MAX_DEPTH: u32 = 8;
+
+/// Smooths `points` into a Catmull-Rom spline passing through every input
+/// point. Each segment is recursively subdivided until the curve midpoint is
+/// within `tolerance` of its chord, so straight sections get few samples and
+/// tight curves get many.
+pub fn adaptive_smooth(points: &[[f32; 2]], tolerance: f32) -> Vec<[f32; 2]> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+    for i in 0..points.len() - 1 {
+        adaptive_smooth_segment(points, i, tolerance, &mut smoothed);
+    }
+    smoothed
+}
+
+/// Appends the samples of the `adaptive_smooth` curve between `points[i]`
+/// (excluded) and `points[i + 1]` (included) to `out`. The segment only
+/// depends on the points from `i - 1` to `i + 2`.
+pub fn adaptive_smooth_segment(
+    points: &[[f32; 2]],
+    i: usize,
+    tolerance: f32,
+    out: &mut Vec<[f32; 2]>,
+) {
+    let last = points.len() - 1;
+    let p0 = points[i.saturating_sub(1)];
+    let p1 = points[i];
+    let p2 = points[i + 1];
+    let p3 = points[(i + 2).min(last)];
+    let segment = |t: f32| catmull_rom_point(p0, p1, p2, p3, t);
+    let tolerance = tolerance.max(f32::EPSILON);
+    subdivide(&segment, (0.0, p1), (1.0, p2), tolerance, 0, out);
+}
+
+fn subdivide<F: Fn(f32) -> [f32; 2]>(
+    curve: &F,
+    start: (f32, [f32; 2]),
+    end: (f32, [f32; 2]),
+    tolerance: f32,
+    depth: u32,
+    out: &mut Vec<[f32; 2]>,
+) {
+    let t = (start.0 + end.0) / 2.0;
+    let mid = curve(t);
+    if depth < ADAPTIVE_MAX_DEPTH && segment_distance(mid, start.1, end.1) > tolerance {
+        subdivide(curve, start, (t, mid), tolerance, depth + 1, out);
+        subdivide(curve, (t, mid), end, tolerance, depth + 1, out);
+    } else {
+        out.push(end.1);
+    }
+}
+
+/// Turning angle, in degrees, above which `detect_corners` treats a point as
+/// an intentional corner.
+pub const DEFAULT_CORNER_ANGLE: f32 = 60.0;
+
+/// Indices of the inner points of `points` where the direction turns by more
+/// than `angle_threshold` degrees between the segment arriving and the one
+/// leaving. Repeated points are skipped when measuring the turn.
+pub fn detect_corners(points: &[[f32; 2]], angle_threshold: f32) -> Vec<usize> {
+    let direction = |a: [f32; 2], b: [f32; 2]| {
+        let d = [b[0] - a[0], b[1] - a[1]];
+        let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+        (len > f32::EPSILON).then(|| [d[0] / len, d[1] / len])
+    };
+    let threshold = angle_threshold.to_radians().cos();
+    (1..points.len().saturating_sub(1))
+        .filter(|&i| {
+            let before = (0..i).rev().find_map(|j| direction(points[j], points[i]));
+            let after = (i + 1..points.len()).find_map(|j| direction(points[i], points[j]));
+            match (before, after) {
+                (Some(a), Some(b)) => a[0] * b[0] + a[1] * b[1] < threshold,
+                _ => false,
+            }
+        })
+        .collect()
+}
+
+/// Upper bound on `relax` passes; past this, moving averages visibly shrink
+/// strokes toward their chord.
+pub const MAX_RELAX_PASSES: u32 = 16;
+
+/// Runs `passes` rounds of a 1-2-1 moving average over the inner points of
+/// `points`, each round smoothing out a little more jitter. The end points
+/// stay in place. `passes` is clamped to `MAX_RELAX_PASSES`.
+pub fn relax(points: &[[f32; 2]], passes: u32) -> Vec<[f32; 2]> {
+    let mut current = points.to_vec();
+    if points.len() < 3 {
+        return current;
+    }
+    let mut next = current.clone();
+    for _ in 0..passes.min(MAX_RELAX_PASSES) {
+        for i in 1..current.len() - 1 {
+            let (a, b, c) = (current[i - 1], current[i], current[i + 1]);
+            next[i] = [
+                (a[0] + 2.0 * b[0] + c[0]) / 4.0,
+                (a[1] + 2.0 * b[1] + c[1]) / 4.0,
+            ];
+        }
+        std::mem::swap(&mut current, &mut next);
+    }
+    current
+}
+
+/// Evaluates the uniform Catmull-Rom segment between `p1` and `p2`.
+pub fn catmull_rom_point(
+    p0: [f32; 2],
+    p1: [f32; 2],
+    p2: [f32; 2],
+    p3: [f32; 2],
+    t: f32,
+) -> [f32; 2] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let mut out = [0.0; 2];
+    for (i, o) in out.iter_mut().enumerate() {
+        *o = 0.5
+            * (2.0 * p1[i]
+                + (p2[i] - p0[i]) * t
+                + (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t2
+                + (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t3);
+    }
+    out
+}
+
+pub fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// `p` rotated counterclockwise by `angle` radians around `center`.
+pub fn rotate_point(p: [f32; 2], center: [f32; 2], angle: f32) -> [f32; 2] {
+    let (sin, cos) = angle.sin_cos();
+    let d = [p[0] - center[0], p[1] - center[1]];
+    [
+        center[0] + d[0] * cos - d[1] * sin,
+        center[1] + d[0] * sin + d[1] * cos,
+    ]
+}
+
+/// Index of the node of `nodes` nearest to `pos` within `radius`, e.g. the
+/// one under the cursor.
+pub fn node_at(nodes: &[[f32; 2]], pos: [f32; 2], radius: f32) -> Option<usize> {
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| (i, distance(n, pos)))
+        .filter(|&(_, d)| d <= radius)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+}
+
+/// Distance from `p` to the segment between `a` and `b`.
+pub fn segment_distance(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let len2 = ab[0] * ab[0] + ab[1] * ab[1];
+    if len2 <= f32::EPSILON {
+        return distance(p, a);
+    }
+    let t = (((p[0] - a[0]) * ab[0] + (p[1] - a[1]) * ab[1]) / len2).clamp(0.0, 1.0);
+    distance(p, [a[0] + ab[0] * t, a[1] + ab[1] * t])
+}
+
+/// Indices of the points kept by Douglas-Peucker simplification of `points`
+/// with tolerance `epsilon`, in increasing order. The end points are always
+/// kept.
+pub fn simplify_indices(points: &[[f32; 2]], epsilon: f32) -> Vec<usize> {
+    if points.len() < 3 {
+        return (0..points.len()).collect();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let mut farthest = (0.0, first);
+        for i in first + 1..last {
+            let d = segment_distance(points[i], points[first], points[last]);
+            if d > farthest.0 {
+                farthest = (d, i);
+            }
+        }
+        if farthest.0 > epsilon {
+            keep[farthest.1] = true;
+            spans.push((first, farthest.1));
+            spans.push((farthest.1, last));
+        }
+    }
+
+    keep.iter()
+        .enumerate()
+        .filter_map(|(i, &k)| if k { Some(i) } else { None })
+        .collect()
+}
+
+/// Douglas-Peucker simplification of `points` with tolerance `epsilon`.
+pub fn simplify(points: &[[f32; 2]], epsilon: f32) -> Vec<[f32; 2]> {
+    simplify_indices(points, epsilon)
+        .into_iter()
+        .map(|i| points[i])
+        .collect()
+}
+
+/// Axis-aligned bounding box of `points` as `(min, max)` corners, or `None`
+/// when there are no points.
+pub fn bounds(points: &[[f32; 2]]) -> Option<([f32; 2], [f32; 2])> {
+    let first = *points.first()?;
+    Some(points.iter().fold((first, first), |(min, max), p| {
+        (
+            [min[0].min(p[0]), min[1].min(p[1])],
+            [max[0].max(p[0]), max[1].max(p[1])],
+        )
+    }))
+}
+
+/// Smallest box containing both `a` and `b`.
+pub fn union(a: ([f32; 2], [f32; 2]), b: ([f32; 2], [f32; 2])) -> ([f32; 2], [f32; 2]) {
+    (
+        [a.0[0].min(b.0[0]), a.0[1].min(b.0[1])],
+        [a.1[0].max(b.1[0]), a.1[1].max(b.1[1])],
+    )
+}
+
+/// Resamples the polyline `points` at fixed arc length intervals of
+/// `spacing`, independently of how densely the input was captured. The first
+/// point is always included, the last one only if it falls on the spacing.
+pub fn resample_by_arclength(points: &[[f32; 2]], spacing: f32) -> Vec<[f32; 2]> {
+    let first = match points.first() {
+        Some(&first) => first,
+        None => return Vec::new(),
+    };
+    if spacing <= 0.0 {
+        return vec![first];
+    }
+
+    let mut resampled = vec![first];
+    // Distance still to travel before the next sample.
+    let mut remaining = spacing;
+    for pair in points.windows(2) {
+        let (mut a, b) = (pair[0], pair[1]);
+        let mut length = distance(a, b);
+        while length >= remaining {
+            let t = remaining / length;
+            a = [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t];
+            resampled.push(a);
+            length -= remaining;
+            remaining = spacing;
+        }
+        remaining -= length;
+    }
+    resampled
+}
+
+/// Ray casting test of whether `p` is inside `poly`, which is implicitly
+/// closed. Self-intersecting polygons follow the even-odd rule.
+pub fn point_in_polygon(poly: &[[f32; 2]], p: [f32; 2]) -> bool {
+    let mut inside = false;
+    let mut j = match poly.len() {
+        0 => return false,
+        n => n - 1,
+    };
+    for i in 0..poly.len() {
+        let (a, b) = (poly[i], poly[j]);
+        if (a[1] > p[1]) != (b[1] > p[1]) {
+            let x = a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+            if p[0] < x {
+                inside = !inside;
+            }
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Closed outline of the rectangle at `origin` with the given `size`.
+pub fn rectangle_points(origin: [f32; 2], size: [f32; 2]) -> Vec<[f32; 2]> {
+    let [x, y] = origin;
+    let [w, h] = size;
+    vec![[x, y], [x + w, y], [x + w, y + h], [x, y + h], [x, y]]
+}
+
+/// Rounds every coordinate to `decimals` decimal places.
+pub fn round_points(points: &mut [[f32; 2]], decimals: u32) {
+    let scale = 10f32.powi(decimals as i32);
+    for point in points {
+        point[0] = (point[0] * scale).round() / scale;
+        point[1] = (point[1] * scale).round() / scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ellipse_segments_grow_with_radius_within_limits() {
+        assert_eq!(ellipse_segments(0.0), ELLIPSE_MIN_SEGMENTS);
+        assert_eq!(ellipse_segments(-5.0), ELLIPSE_MIN_SEGMENTS);
+        assert_eq!(ellipse_segments(1e6), ELLIPSE_MAX_SEGMENTS);
+        // 2π * 100 / 4 = 157.08, rounded up.
+        assert_eq!(ellipse_segments(100.0), 158);
+        assert!(ellipse_segments(50.0) < ellipse_segments(100.0));
+    }
+
+    #[test]
+    fn ellipse_segments_keep_segment_length_near_target() {
+        for &radius in &[20.0, 60.0, 150.0] {
+            let segments = ellipse_segments(radius);
+            let length = std::f32::consts::TAU * radius / segments as f32;
+            assert!(length <= ELLIPSE_SEGMENT_LENGTH, "radius {}", radius);
+            assert!(length > ELLIPSE_SEGMENT_LENGTH * 0.9, "radius {}", radius);
+        }
+    }
+
+    /// Samples along a circle of `n` points, as a drawn stroke would be.
+    fn circle(n: usize, radius: f32) -> Vec<[f32; 2]> {
+        (0..n)
+            .map(|i| {
+                let a = i as f32 / n as f32 * std::f32::consts::PI;
+                [radius * a.cos(), radius * a.sin()]
+            })
+            .collect()
+    }
+
+    /// Largest distance from the dense spline through `points` to the
+    /// polyline `approx`.
+    fn max_deviation(points: &[[f32; 2]], approx: &[[f32; 2]]) -> f32 {
+        let last = points.len() - 1;
+        let mut worst = 0.0f32;
+        for i in 0..last {
+            let (p0, p1, p2, p3) = (
+                points[i.saturating_sub(1)],
+                points[i],
+                points[i + 1],
+                points[(i + 2).min(last)],
+            );
+            for k in 0..=64 {
+                let c = catmull_rom_point(p0, p1, p2, p3, k as f32 / 64.0);
+                let d = approx
+                    .windows(2)
+                    .map(|s| segment_distance(c, s[0], s[1]))
+                    .fold(f32::INFINITY, f32::min);
+                worst = worst.max(d);
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn adaptive_smooth_stays_within_tolerance_of_the_spline() {
+        let points = circle(12, 1.0);
+        for &tolerance in &[0.01, 0.001, 0.0001] {
+            let smoothed = adaptive_smooth(&points, tolerance);
+            // The midpoint test bounds the error of each flat enough piece
+            // to about the tolerance; allow for the curve bulging slightly
+            // past the tested midpoint.
+            let deviation = max_deviation(&points, &smoothed);
+            assert!(
+                deviation <= tolerance * 1.5,
+                "tolerance {} deviation {}",
+                tolerance,
+                deviation
+            );
+        }
+    }
+
+    #[test]
+    fn adaptive_smooth_passes_through_inputs_and_spends_samples_on_curves() {
+        let points = circle(12, 1.0);
+        let coarse = adaptive_smooth(&points, 0.01);
+        let fine = adaptive_smooth(&points, 0.0001);
+        for p in &points {
+            assert!(fine.contains(p));
+        }
+        assert!(fine.len() > coarse.len());
+
+        let straight: Vec<[f32; 2]> = (0..10).map(|i| [i as f32, 0.0]).collect();
+        assert_eq!(adaptive_smooth(&straight, 0.001), straight);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Rounds `point` to the nearest intersection of a grid with the given
+/// `spacing` whose lines pass through `origin`.
+pub fn snap_to_grid(point: [f32; 2], spacing: f32, origin: [f32; 2]) -> [f32; 2] {
+    if spacing <= 0.0 {
+        return point;
+    }
+    let snap = |v: f32, o: f32| ((v - o) / spacing).round() * spacing + o;
+    [snap(point[0], origin[0]), snap(point[1], origin[1])]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Grid {
+    pub spacing: f32,
+    pub snapping: bool,
+    /// A grid intersection, so the grid can be aligned to a reference
+    /// other than the world origin.
+    pub origin: [f32; 2],
+}
+
+impl Grid {
+    /// Snaps `point` to the grid if snapping is enabled.
+    pub fn snap(&self, point: [f32; 2]) -> [f32; 2] {
+        if self.snapping {
+            snap_to_grid(point, self.spacing, self.origin)
+        } else {
+            point
+        }
+    }
+
+    /// Snaps both end points of a line or rectangle drag, so shapes built
+    /// from them are aligned on grid intersections.
+    pub fn snap_shape(&self, start: [f32; 2], end: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        (self.snap(start), self.snap(end))
+    }
+
+    /// HUD text for the point `point` snaps to, while snapping is on.
+    pub fn snap_readout(&self, point: [f32; 2]) -> Option<String> {
+        if !self.snapping {
+            return None;
+        }
+        let [x, y] = self.snap(point);
+        Some(format!("Snap: ({:.3}, {:.3})", x, y))
+    }
+
+    /// World positions of the vertical and horizontal lines of a grid level
+    /// `spacing` apart that fall within `view_rect`, honoring the origin.
+    pub fn lines(&self, spacing: f32, view_rect: ([f32; 2], [f32; 2])) -> (Vec<f32>, Vec<f32>) {
+        let (min, max) = view_rect;
+        let along = |lo: f32, hi: f32, o: f32| {
+            if spacing <= 0.0 || hi < lo {
+                return Vec::new();
+            }
+            let first = ((lo - o) / spacing).ceil() as i64;
+            let last = ((hi - o) / spacing).floor() as i64;
+            (first..=last).map(|k| o + k as f32 * spacing).collect()
+        };
+        (
+            along(min[0], max[0], self.origin[0]),
+            along(min[1], max[1], self.origin[1]),
+        )
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            spacing: 0.1,
+            snapping: false,
+            origin: [0.0, 0.0],
+        }
+    }
+}
+
+/// Screen spacing, in pixels, below which grid lines are hidden.
+const GRID_FADE_START_PX: f32 = 6.0;
+/// Screen spacing, in pixels, from which grid lines are fully opaque.
+const GRID_FADE_END_PX: f32 = 24.0;
+/// Ratio between the spacings of successive grid levels.
+const GRID_LEVEL_RATIO: f32 = 10.0;
+
+/// Opacity of grid lines `spacing_px` pixels apart on screen: dense grids
+/// fade out instead of turning into a solid mass of lines.
+pub fn grid_opacity(spacing_px: f32) -> f32 {
+    let t = ((spacing_px - GRID_FADE_START_PX) / (GRID_FADE_END_PX - GRID_FADE_START_PX))
+        .clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// The grid levels to draw for a world `spacing`, as `(spacing, opacity)`
+/// pairs: the finest level still visible at `zoom` in a window `width`
+/// pixels wide, fading in or out, and the next coarser one.
+pub fn grid_levels(spacing: f32, zoom: f32, width: u32) -> Vec<(f32, f32)> {
+    if spacing <= 0.0 || zoom <= 0.0 || width == 0 {
+        return Vec::new();
+    }
+    let px_per_unit = zoom * width as f32 / 2.0;
+    let mut level = spacing;
+    while grid_opacity(level * px_per_unit) <= 0.0 {
+        level *= GRID_LEVEL_RATIO;
+    }
+    let coarse = level * GRID_LEVEL_RATIO;
+    vec![
+        (level, grid_opacity(level * px_per_unit)),
+        (coarse, grid_opacity(coarse * px_per_unit)),
+    ]
+}
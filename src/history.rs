@@ -0,0 +1,773 @@
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use crate::layer::{self, Layer};
+use crate::stroke::{
+    self, duplicate_indices, estimate_memory, indices_of, join_strokes, point_count, replace_color,
+    Stroke,
+};
+
+/// IDs of the strokes at `indices` that are not locked. Commands record
+/// IDs, so a stroke locked after a move still undoes with it.
+fn unlocked_ids(strokes: &[Stroke], indices: &[usize]) -> Vec<u64> {
+    indices
+        .iter()
+        .filter_map(|&i| strokes.get(i))
+        .filter(|s| !s.locked)
+        .map(|s| s.id)
+        .collect()
+}
+
+/// Moves the strokes at `indices` by `delta`, locked or not: commands only
+/// record unlocked strokes, and must still undo once they are locked.
+fn translate_strokes(strokes: &mut [Stroke], indices: &[usize], delta: [f32; 2]) {
+    for &index in indices {
+        if let Some(stroke) = strokes.get_mut(index) {
+            stroke.translate(delta);
+        }
+    }
+}
+
+/// Rotates the strokes at `indices` by `angle` radians around `center`, as
+/// `translate_strokes` moves them.
+fn rotate_strokes(strokes: &mut [Stroke], indices: &[usize], center: [f32; 2], angle: f32) {
+    for &index in indices {
+        if let Some(stroke) = strokes.get_mut(index) {
+            stroke.rotate(center, angle);
+        }
+    }
+}
+
+fn stroke_ids(strokes: &[Stroke]) -> Vec<u64> {
+    strokes.iter().map(|s| s.id).collect()
+}
+
+/// Sorts `strokes` into the order of `ids`. Strokes missing from `ids` go on
+/// top, in their current order.
+fn order_by_ids(strokes: &mut [Stroke], ids: &[u64]) {
+    let rank: std::collections::HashMap<u64, usize> =
+        ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    strokes.sort_by_key(|s| rank.get(&s.id).copied().unwrap_or(usize::MAX));
+}
+
+/// An undoable edit of the stroke list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Appends a stroke on top of the others.
+    Add(Stroke),
+    /// Removes the unlocked strokes at `indices`, kept in increasing order. Once
+    /// applied, `removed` holds the matching strokes so undoing puts them
+    /// back at the same place in the draw order.
+    Delete {
+        indices: Vec<usize>,
+        removed: Vec<Stroke>,
+    },
+    /// Moves the strokes with the given IDs, which stay valid if other
+    /// commands reorder the list in between.
+    Translate { ids: Vec<u64>, delta: [f32; 2] },
+    /// Rotates the strokes with the given IDs by `angle` radians around
+    /// `center`.
+    Rotate {
+        ids: Vec<u64>,
+        center: [f32; 2],
+        angle: f32,
+    },
+    /// Changes the draw order from the stroke IDs in `before` to those in
+    /// `after`.
+    Reorder { before: Vec<u64>, after: Vec<u64> },
+    /// Replaces the whole stroke list, for document-wide operations.
+    Replace {
+        before: Vec<Stroke>,
+        after: Vec<Stroke>,
+    },
+}
+
+impl Command {
+    /// A command deleting the strokes at `indices`.
+    pub fn delete(indices: &[usize]) -> Self {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        Command::Delete {
+            indices,
+            removed: Vec::new(),
+        }
+    }
+
+    /// A command moving the unlocked strokes currently at `indices` by
+    /// `delta`.
+    pub fn translate(strokes: &[Stroke], indices: &[usize], delta: [f32; 2]) -> Self {
+        Command::Translate {
+            ids: unlocked_ids(strokes, indices),
+            delta,
+        }
+    }
+
+    /// A command rotating the unlocked strokes currently at `indices`.
+    pub fn rotate(strokes: &[Stroke], indices: &[usize], center: [f32; 2], angle: f32) -> Self {
+        Command::Rotate {
+            ids: unlocked_ids(strokes, indices),
+            center,
+            angle,
+        }
+    }
+
+    /// A command raising the unlocked strokes at `indices` to the top of the
+    /// draw order, as `stroke::bring_to_front` does.
+    pub fn bring_to_front(strokes: &[Stroke], indices: &[usize]) -> Self {
+        Self::reorder(strokes, |s| stroke::bring_to_front(s, indices))
+    }
+
+    /// A command lowering the unlocked strokes at `indices` to the bottom of
+    /// the draw order, as `stroke::send_to_back` does.
+    pub fn send_to_back(strokes: &[Stroke], indices: &[usize]) -> Self {
+        Self::reorder(strokes, |s| stroke::send_to_back(s, indices))
+    }
+
+    fn reorder(strokes: &[Stroke], reorder: impl FnOnce(&mut Vec<Stroke>)) -> Self {
+        let mut reordered = strokes.to_vec();
+        reorder(&mut reordered);
+        Command::Reorder {
+            before: stroke_ids(strokes),
+            after: stroke_ids(&reordered),
+        }
+    }
+
+    /// Approximate memory the command keeps alive on the undo stack.
+    fn memory(&self) -> usize {
+        size_of::<Command>()
+            + match self {
+                Command::Add(stroke) => estimate_memory(std::slice::from_ref(stroke)),
+                Command::Delete { indices, removed } => {
+                    indices.capacity() * size_of::<usize>() + estimate_memory(removed)
+                }
+                Command::Translate { ids, .. } | Command::Rotate { ids, .. } => {
+                    ids.capacity() * size_of::<u64>()
+                }
+                Command::Reorder { before, after } => {
+                    (before.capacity() + after.capacity()) * size_of::<u64>()
+                }
+                Command::Replace { before, after } => {
+                    estimate_memory(before) + estimate_memory(after)
+                }
+            }
+    }
+
+    /// Folds `next` into this command if both move or rotate the same
+    /// strokes, so that the pair undoes as one step.
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (
+                Command::Translate { ids, delta },
+                Command::Translate {
+                    ids: next_ids,
+                    delta: next_delta,
+                },
+            ) if ids == next_ids => {
+                delta[0] += next_delta[0];
+                delta[1] += next_delta[1];
+                true
+            }
+            (
+                Command::Rotate { ids, center, angle },
+                Command::Rotate {
+                    ids: next_ids,
+                    center: next_center,
+                    angle: next_angle,
+                },
+            ) if ids == next_ids && center == next_center => {
+                *angle += next_angle;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(&mut self, strokes: &mut Vec<Stroke>) {
+        match self {
+            Command::Add(stroke) => strokes.push(stroke.clone()),
+            Command::Delete { indices, removed } => {
+                indices.retain(|&i| strokes.get(i).is_some_and(|s| !s.locked));
+                // Remove from the back so the remaining indices stay valid.
+                *removed = indices.iter().rev().map(|&i| strokes.remove(i)).collect();
+                removed.reverse();
+            }
+            Command::Translate { ids, delta } => {
+                let indices = indices_of(strokes, ids);
+                translate_strokes(strokes, &indices, *delta)
+            }
+            Command::Rotate { ids, center, angle } => {
+                let indices = indices_of(strokes, ids);
+                rotate_strokes(strokes, &indices, *center, *angle)
+            }
+            Command::Reorder { after, .. } => order_by_ids(strokes, after),
+            Command::Replace { after, .. } => *strokes = after.clone(),
+        }
+    }
+
+    fn revert(&self, strokes: &mut Vec<Stroke>) {
+        match self {
+            // Later commands may have reordered the list, so the stroke is
+            // found by ID rather than assumed to be on top.
+            Command::Add(added) => {
+                if let Some(i) = strokes.iter().rposition(|s| s.id == added.id) {
+                    strokes.remove(i);
+                }
+            }
+            Command::Delete { indices, removed } => {
+                for (&i, stroke) in indices.iter().zip(removed) {
+                    strokes.insert(i, stroke.clone());
+                }
+            }
+            Command::Translate { ids, delta } => {
+                let indices = indices_of(strokes, ids);
+                translate_strokes(strokes, &indices, [-delta[0], -delta[1]])
+            }
+            Command::Rotate { ids, center, angle } => {
+                let indices = indices_of(strokes, ids);
+                rotate_strokes(strokes, &indices, *center, -angle)
+            }
+            Command::Reorder { before, .. } => order_by_ids(strokes, before),
+            Command::Replace { before, .. } => *strokes = before.clone(),
+        }
+    }
+}
+
+/// Bounds on how much undo history is kept and how finely it is split.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UndoLimits {
+    /// Memory the undo stack may hold before its oldest steps are dropped;
+    /// `None` keeps everything. The latest step is always kept.
+    pub max_bytes: Option<usize>,
+    /// Consecutive nudges or rotations of the same strokes applied within
+    /// this time of each other undo as one step.
+    pub coalesce_window: Duration,
+}
+
+impl Default for UndoLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(64 << 20),
+            coalesce_window: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Undo/redo stacks of the commands applied to a stroke list.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub limits: UndoLimits,
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    /// Memory held by `undo`, as estimated by `Command::memory`.
+    undo_bytes: usize,
+    /// When the top of the undo stack was last applied or extended, while
+    /// it may still be coalesced with.
+    last_applied: Option<Instant>,
+    /// Length of the undo stack when the document was last saved, or `None`
+    /// if that state can no longer be reached.
+    saved_at: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::with_limits(UndoLimits::default())
+    }
+
+    pub fn with_limits(limits: UndoLimits) -> Self {
+        Self {
+            limits,
+            saved_at: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Applies `command` to `strokes` and records it, dropping the redo stack.
+    pub fn apply(&mut self, strokes: &mut Vec<Stroke>, mut command: Command) {
+        command.apply(strokes);
+        self.push_undo(command);
+        self.last_applied = None;
+        if self.saved_at.is_some_and(|saved| saved >= self.undo.len()) {
+            self.saved_at = None;
+        }
+        self.redo.clear();
+        self.trim();
+    }
+
+    /// Like `apply`, but merges the command into the previous step if both
+    /// move or rotate the same strokes and `now` is within the coalescing
+    /// window of it, so a run of arrow-key nudges undoes at once. A saved
+    /// state is never merged away.
+    pub fn apply_coalesced(
+        &mut self,
+        strokes: &mut Vec<Stroke>,
+        mut command: Command,
+        now: Instant,
+    ) {
+        let recent = self
+            .last_applied
+            .is_some_and(|last| now.saturating_duration_since(last) <= self.limits.coalesce_window);
+        let at_save = self.saved_at == Some(self.undo.len());
+        if recent && !at_save {
+            if let Some(top) = self.undo.last_mut() {
+                command.apply(strokes);
+                if top.merge(&command) {
+                    self.last_applied = Some(now);
+                    if self.saved_at.is_some_and(|saved| saved > self.undo.len()) {
+                        self.saved_at = None;
+                    }
+                    self.redo.clear();
+                    return;
+                }
+                command.revert(strokes);
+            }
+        }
+        self.apply(strokes, command);
+        self.last_applied = Some(now);
+    }
+
+    fn push_undo(&mut self, command: Command) {
+        self.undo_bytes += command.memory();
+        self.undo.push(command);
+    }
+
+    fn pop_undo(&mut self) -> Option<Command> {
+        let command = self.undo.pop()?;
+        self.undo_bytes = self.undo_bytes.saturating_sub(command.memory());
+        Some(command)
+    }
+
+    /// Drops the oldest undo steps past `limits.max_bytes`.
+    fn trim(&mut self) {
+        let max = match self.limits.max_bytes {
+            Some(max) => max,
+            None => return,
+        };
+        let mut dropped = 0;
+        while self.undo_bytes > max && self.undo.len() - dropped > 1 {
+            self.undo_bytes = self.undo_bytes.saturating_sub(self.undo[dropped].memory());
+            dropped += 1;
+        }
+        if dropped > 0 {
+            self.undo.drain(..dropped);
+            self.saved_at = self.saved_at.and_then(|saved| saved.checked_sub(dropped));
+        }
+    }
+
+    /// Memory held by the undo stack, as counted against
+    /// `limits.max_bytes`.
+    pub fn undo_memory(&self) -> usize {
+        self.undo_bytes
+    }
+
+    /// Replaces all strokes with `after` as a single undoable step.
+    pub fn replace_all(&mut self, strokes: &mut Vec<Stroke>, after: Vec<Stroke>) {
+        let before = strokes.clone();
+        self.apply(strokes, Command::Replace { before, after });
+    }
+
+    /// Raises the unlocked strokes at `indices` to the top of the draw order
+    /// as a single undoable step. Returns whether the order changed.
+    pub fn bring_to_front(&mut self, strokes: &mut Vec<Stroke>, indices: &[usize]) -> bool {
+        self.apply_reorder(strokes, Command::bring_to_front(strokes, indices))
+    }
+
+    /// Lowers the unlocked strokes at `indices` to the bottom of the draw
+    /// order as a single undoable step. Returns whether the order changed.
+    pub fn send_to_back(&mut self, strokes: &mut Vec<Stroke>, indices: &[usize]) -> bool {
+        self.apply_reorder(strokes, Command::send_to_back(strokes, indices))
+    }
+
+    fn apply_reorder(&mut self, strokes: &mut Vec<Stroke>, command: Command) -> bool {
+        let changed = matches!(&command, Command::Reorder { before, after } if before != after);
+        if changed {
+            self.apply(strokes, command);
+        }
+        changed
+    }
+
+    /// Runs Douglas-Peucker over every unlocked stroke as a single undoable
+    /// step, returning the total point counts before and after.
+    pub fn simplify_all(&mut self, strokes: &mut Vec<Stroke>, epsilon: f32) -> (usize, usize) {
+        let before = point_count(strokes);
+        let mut simplified = strokes.clone();
+        for stroke in simplified.iter_mut().filter(|s| !s.locked) {
+            stroke.simplify(epsilon);
+        }
+        let after = point_count(&simplified);
+        self.replace_all(strokes, simplified);
+        (before, after)
+    }
+
+    /// Deletes strokes duplicating an earlier one within `tol` as a single
+    /// undoable step, returning how many were removed.
+    pub fn remove_duplicates(&mut self, strokes: &mut Vec<Stroke>, tol: f32) -> usize {
+        let duplicates = duplicate_indices(strokes, tol);
+        if !duplicates.is_empty() {
+            self.apply(strokes, Command::delete(&duplicates));
+        }
+        duplicates.len()
+    }
+
+    /// Joins strokes whose ends meet within `threshold` as a single undoable
+    /// step, returning how many strokes the list shrank by.
+    pub fn join_strokes(&mut self, strokes: &mut Vec<Stroke>, threshold: f32) -> usize {
+        let joined = join_strokes(strokes, threshold);
+        let removed = strokes.len() - joined.len();
+        if removed > 0 {
+            self.replace_all(strokes, joined);
+        }
+        removed
+    }
+
+    /// Replaces `from` with `to` (see `stroke::replace_color`) in the
+    /// strokes at `selection`, or in all strokes, as a single undoable step.
+    /// Returns how many strokes changed.
+    pub fn replace_color(
+        &mut self,
+        strokes: &mut Vec<Stroke>,
+        selection: Option<&[usize]>,
+        from: [u8; 3],
+        to: [u8; 3],
+        tolerance: u8,
+    ) -> usize {
+        let mut recolored = strokes.clone();
+        let changed = match selection {
+            Some(indices) => {
+                let mut indices = indices.to_vec();
+                indices.sort_unstable();
+                indices.dedup();
+                let mut changed = 0;
+                for &i in &indices {
+                    if let Some(stroke) = recolored.get_mut(i) {
+                        changed += replace_color(std::slice::from_mut(stroke), from, to, tolerance);
+                    }
+                }
+                changed
+            }
+            None => replace_color(&mut recolored, from, to, tolerance),
+        };
+        if changed > 0 {
+            self.replace_all(strokes, recolored);
+        }
+        changed
+    }
+
+    pub fn undo(&mut self, strokes: &mut Vec<Stroke>) -> bool {
+        self.last_applied = None;
+        match self.pop_undo() {
+            Some(command) => {
+                command.revert(strokes);
+                self.redo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, strokes: &mut Vec<Stroke>) -> bool {
+        self.last_applied = None;
+        match self.redo.pop() {
+            Some(mut command) => {
+                command.apply(strokes);
+                self.push_undo(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo.len());
+    }
+
+    /// Whether the strokes differ from their last saved state.
+    pub fn is_modified(&self) -> bool {
+        self.saved_at != Some(self.undo.len())
+    }
+}
+
+/// Undo/redo of changes to the layer structure, such as flattening. These
+/// are rare and touch every layer, so whole snapshots are kept instead of
+/// commands.
+#[derive(Debug, Clone, Default)]
+pub struct LayerHistory {
+    undo: Vec<Vec<Layer>>,
+    redo: Vec<Vec<Layer>>,
+}
+
+impl LayerHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `layers` as they are before a structural change.
+    pub fn record(&mut self, layers: &[Layer]) {
+        self.undo.push(layers.to_vec());
+        self.redo.clear();
+    }
+
+    /// Merges all layers into one as an undoable step.
+    pub fn flatten(&mut self, layers: &mut Vec<Layer>) {
+        self.record(layers);
+        layer::flatten(layers);
+    }
+
+    pub fn undo(&mut self, layers: &mut Vec<Layer>) -> bool {
+        match self.undo.pop() {
+            Some(before) => {
+                self.redo.push(std::mem::replace(layers, before));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, layers: &mut Vec<Layer>) -> bool {
+        match self.redo.pop() {
+            Some(after) => {
+                self.undo.push(std::mem::replace(layers, after));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A two-point stroke starting at `x`, told apart by its position.
+    fn stroke(x: f32) -> Stroke {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        stroke.push([x, 0.0], Duration::from_millis(0));
+        stroke.push([x, 1.0], Duration::from_millis(10));
+        stroke
+    }
+
+    fn ids(strokes: &[Stroke]) -> Vec<u64> {
+        strokes.iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn undoing_a_delete_restores_the_draw_order() {
+        let mut strokes: Vec<Stroke> = (0..5).map(|i| stroke(i as f32)).collect();
+        let original = ids(&strokes);
+        let mut history = History::new();
+
+        // Out of order and repeated on purpose.
+        history.apply(&mut strokes, Command::delete(&[3, 1, 3]));
+        assert_eq!(ids(&strokes), [original[0], original[2], original[4]]);
+
+        assert!(history.undo(&mut strokes));
+        assert_eq!(ids(&strokes), original);
+
+        assert!(history.redo(&mut strokes));
+        assert_eq!(ids(&strokes), [original[0], original[2], original[4]]);
+    }
+
+    #[test]
+    fn interleaved_edits_undo_back_to_the_original_order() {
+        let mut strokes: Vec<Stroke> = (0..3).map(|i| stroke(i as f32)).collect();
+        let original = strokes.clone();
+        let mut history = History::new();
+
+        history.apply(&mut strokes, Command::delete(&[0]));
+        history.apply(&mut strokes, Command::Add(stroke(9.0)));
+        let moved = Command::translate(&strokes, &[0], [0.5, 0.0]);
+        history.apply(&mut strokes, moved);
+        history.apply(&mut strokes, Command::delete(&[1, 2]));
+
+        while history.undo(&mut strokes) {}
+        assert_eq!(strokes, original);
+        assert!(!history.is_modified());
+    }
+
+    #[test]
+    fn out_of_range_delete_indices_are_ignored() {
+        let mut strokes = vec![stroke(0.0), stroke(1.0)];
+        let original = ids(&strokes);
+        let mut history = History::new();
+        history.apply(&mut strokes, Command::delete(&[1, 7]));
+        assert_eq!(ids(&strokes), [original[0]]);
+        history.undo(&mut strokes);
+        assert_eq!(ids(&strokes), original);
+    }
+
+    fn locked(x: f32) -> Stroke {
+        Stroke {
+            locked: true,
+            ..stroke(x)
+        }
+    }
+
+    #[test]
+    fn delete_skips_locked_strokes() {
+        let mut strokes = vec![stroke(0.0), locked(1.0), stroke(2.0)];
+        let original = ids(&strokes);
+        let mut history = History::new();
+        history.apply(&mut strokes, Command::delete(&[0, 1, 2]));
+        assert_eq!(ids(&strokes), [original[1]]);
+        history.undo(&mut strokes);
+        assert_eq!(ids(&strokes), original);
+    }
+
+    #[test]
+    fn locked_strokes_are_not_extended_or_closed() {
+        let mut strokes = vec![locked(0.0)];
+        stroke::continue_last_stroke(&mut strokes, [5.0, 5.0], [1, 2, 3], 0.1);
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(strokes[0].points, locked(0.0).points);
+        assert_eq!(strokes[1].points, vec![[5.0, 5.0]]);
+        assert_eq!(strokes[1].color, [1, 2, 3]);
+
+        let mut ring = Stroke::new([0, 0, 0], 0.01);
+        for (i, &p) in [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.01]]
+            .iter()
+            .enumerate()
+        {
+            ring.push(p, Duration::from_millis(i as u64));
+        }
+        ring.locked = true;
+        assert!(!stroke::maybe_close(&mut ring, 0.1));
+        assert_eq!(ring.points[3], [0.0, 0.01]);
+        ring.locked = false;
+        assert!(stroke::maybe_close(&mut ring, 0.1));
+    }
+
+    #[test]
+    fn translate_and_rotate_skip_locked_strokes() {
+        let mut strokes = vec![stroke(0.0), locked(1.0)];
+        let mut history = History::new();
+        let translate = Command::translate(&strokes, &[0, 1], [1.0, 0.0]);
+        history.apply(&mut strokes, translate);
+        assert_eq!(strokes[0].points[0], [1.0, 0.0]);
+        assert_eq!(strokes[1].points[0], [1.0, 0.0]);
+
+        let center = [0.0, 0.0];
+        let rotate = Command::rotate(&strokes, &[0, 1], center, std::f32::consts::PI);
+        history.apply(&mut strokes, rotate);
+        assert!((strokes[0].points[0][0] + 1.0).abs() < 1e-5);
+        assert_eq!(strokes[1].points, locked(1.0).points);
+
+        // Locking after a move does not keep the move from undoing.
+        strokes[0].locked = true;
+        history.undo(&mut strokes);
+        history.undo(&mut strokes);
+        assert_eq!(strokes[0].points, stroke(0.0).points);
+    }
+
+    #[test]
+    fn simplify_all_skips_locked_strokes() {
+        let dense = |locked| {
+            let mut stroke = Stroke::new([0, 0, 0], 0.01);
+            for i in 0..10 {
+                stroke.push([i as f32, 0.0], Duration::from_millis(i));
+            }
+            stroke.locked = locked;
+            stroke
+        };
+        let mut strokes = vec![dense(false), dense(true)];
+        let mut history = History::new();
+        assert_eq!(history.simplify_all(&mut strokes, 0.1), (20, 12));
+        assert_eq!(strokes[0].points.len(), 2);
+        assert_eq!(strokes[1].points.len(), 10);
+    }
+
+    #[test]
+    fn remove_duplicates_skips_locked_strokes() {
+        let mut strokes = vec![stroke(0.0), locked(0.0), stroke(0.0)];
+        let kept = ids(&strokes)[..2].to_vec();
+        let mut history = History::new();
+        assert_eq!(history.remove_duplicates(&mut strokes, 1e-3), 1);
+        assert_eq!(ids(&strokes), kept);
+    }
+
+    #[test]
+    fn reordering_undoes_and_redoes() {
+        let mut strokes: Vec<Stroke> = (0..4).map(|i| stroke(i as f32)).collect();
+        let original = ids(&strokes);
+        let mut history = History::new();
+
+        assert!(history.bring_to_front(&mut strokes, &[0, 2]));
+        assert_eq!(
+            ids(&strokes),
+            [original[1], original[3], original[0], original[2]]
+        );
+        assert!(history.send_to_back(&mut strokes, &[3]));
+        assert_eq!(
+            ids(&strokes),
+            [original[2], original[1], original[3], original[0]]
+        );
+        // Already at the back: nothing to record.
+        assert!(!history.send_to_back(&mut strokes, &[0]));
+
+        assert!(history.undo(&mut strokes));
+        assert!(history.undo(&mut strokes));
+        assert_eq!(ids(&strokes), original);
+        assert!(!history.is_modified());
+        assert!(history.redo(&mut strokes));
+        assert_eq!(
+            ids(&strokes),
+            [original[1], original[3], original[0], original[2]]
+        );
+    }
+
+    #[test]
+    fn reordering_keeps_locked_strokes_in_place() {
+        let mut strokes = vec![stroke(0.0), locked(1.0), stroke(2.0)];
+        let original = ids(&strokes);
+        let mut history = History::new();
+        assert!(!history.bring_to_front(&mut strokes, &[1]));
+        assert_eq!(ids(&strokes), original);
+    }
+
+    #[test]
+    fn undoing_an_add_removes_that_stroke_after_a_reorder() {
+        let mut strokes = vec![stroke(0.0), stroke(1.0)];
+        let original = ids(&strokes);
+        let mut history = History::new();
+        let added = stroke(2.0);
+        let added_id = added.id;
+        history.apply(&mut strokes, Command::Add(added));
+        history.send_to_back(&mut strokes, &[2]);
+        assert_eq!(ids(&strokes)[0], added_id);
+
+        // Undo the reorder, then the add.
+        history.undo(&mut strokes);
+        history.undo(&mut strokes);
+        assert_eq!(ids(&strokes), original);
+
+        // Reverting the add itself, with the stroke no longer on top.
+        let mut add = Command::Add(stroke(3.0));
+        add.apply(&mut strokes);
+        stroke::send_to_back(&mut strokes, &[2]);
+        add.revert(&mut strokes);
+        assert_eq!(ids(&strokes), original);
+    }
+
+    #[test]
+    fn replacing_colors_counts_repeated_selections_once() {
+        let mut strokes: Vec<Stroke> = (0..3).map(|i| stroke(i as f32)).collect();
+        strokes[2].color = [0, 0, 255];
+        let original = strokes.clone();
+        let mut history = History::new();
+
+        let changed =
+            history.replace_color(&mut strokes, Some(&[1, 0, 1, 7]), [0, 0, 0], [4, 4, 4], 8);
+        assert_eq!(changed, 2);
+        assert_eq!(strokes[0].color, [4, 4, 4]);
+        assert_eq!(strokes[1].color, [4, 4, 4]);
+        assert_eq!(strokes[2].color, [0, 0, 255]);
+
+        history.undo(&mut strokes);
+        assert_eq!(strokes, original);
+        assert_eq!(
+            history.replace_color(&mut strokes, None, [0, 0, 255], [255, 0, 0], 0),
+            1
+        );
+    }
+}
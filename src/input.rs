@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coords::ScreenPos;
+use crate::geometry::distance;
+
+/// Distance in pixels the cursor may move between press and release for the
+/// gesture to still count as a click rather than a drag.
+pub const CLICK_DRAG_THRESHOLD: f32 = 4.0;
+
+/// Whether a press at `press` released at `release` (window pixels) is a
+/// click rather than a drag.
+pub fn is_click(press: ScreenPos, release: ScreenPos) -> bool {
+    let dx = release.x() - press.x();
+    let dy = release.y() - press.y();
+    dx * dx + dy * dy <= CLICK_DRAG_THRESHOLD * CLICK_DRAG_THRESHOLD
+}
+
+/// How close in time and space two presses must be to form a double-click.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoubleClick {
+    pub max_interval: Duration,
+    /// In window pixels.
+    pub max_distance: f32,
+}
+
+impl DoubleClick {
+    pub fn matches(
+        &self,
+        prev_time: Instant,
+        prev_pos: ScreenPos,
+        now: Instant,
+        pos: ScreenPos,
+    ) -> bool {
+        now.saturating_duration_since(prev_time) <= self.max_interval
+            && distance(prev_pos.0, pos.0) <= self.max_distance
+    }
+}
+
+impl Default for DoubleClick {
+    fn default() -> Self {
+        Self {
+            max_interval: Duration::from_millis(400),
+            max_distance: 6.0,
+        }
+    }
+}
+
+/// Whether a press at `pos` at `now` completes a double-click with the
+/// previous press, using the default `DoubleClick` limits.
+pub fn is_double_click(
+    prev_press_time: Instant,
+    prev_pos: ScreenPos,
+    now: Instant,
+    pos: ScreenPos,
+) -> bool {
+    DoubleClick::default().matches(prev_press_time, prev_pos, now, pos)
+}
+
+/// Hides the cursor after `timeout` without pointer activity while viewing,
+/// and shows it again on the next movement. Keys do not count, so that
+/// shortcuts used while viewing leave the cursor hidden.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdleCursor {
+    /// `None` never hides the cursor.
+    pub timeout: Option<Duration>,
+    last_activity: Instant,
+    hidden: bool,
+}
+
+impl IdleCursor {
+    pub fn new(timeout: Option<Duration>, now: Instant) -> Self {
+        Self {
+            timeout,
+            last_activity: now,
+            hidden: false,
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Folds in a frame's `events`; `in_use` is set while a tool is drawing
+    /// or dragging, which keeps the cursor visible even if it holds still.
+    /// Returns the new visibility when it changes, for the backend to apply.
+    pub fn update(&mut self, events: &[InputEvent], in_use: bool, now: Instant) -> Option<bool> {
+        let active = in_use
+            || events.iter().any(|event| {
+                matches!(
+                    event,
+                    InputEvent::CursorMoved(_)
+                        | InputEvent::Button { .. }
+                        | InputEvent::Scroll(_)
+                        | InputEvent::Touch { .. }
+                )
+            });
+        if active {
+            self.last_activity = now;
+        }
+        let hide = !active
+            && self.timeout.is_some_and(|timeout| {
+                now.saturating_duration_since(self.last_activity) >= timeout
+            });
+        if hide == self.hidden {
+            return None;
+        }
+        self.hidden = hide;
+        Some(!hide)
+    }
+}
+
+impl Default for IdleCursor {
+    fn default() -> Self {
+        Self::new(Some(Duration::from_secs(3)), Instant::now())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+}
+
+/// Raw input, as translated from the windowing backend's events. Keys are
+/// identified by the backend's key code.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    CursorMoved(ScreenPos),
+    Button {
+        button: MouseButton,
+        pressed: bool,
+    },
+    Scroll([f32; 2]),
+    Key {
+        code: u32,
+        pressed: bool,
+    },
+    Modifiers(Modifiers),
+    /// A finger, identified by `id` for as long as it stays down, at
+    /// `position` in window pixels. Only sent on platforms exposing touch.
+    Touch {
+        id: u64,
+        position: ScreenPos,
+        phase: TouchPhase,
+    },
+}
+
+/// How many points the freehand tool captures per frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureMode {
+    /// One point per frame, at the cursor position after all events.
+    #[default]
+    PerFrame,
+    /// A point for every cursor move received while drawing, keeping the
+    /// sub-frame motion the OS reported. Helps most at low frame rates.
+    PerEvent,
+}
+
+/// What the fingers on screen did during the last frame, so that the core
+/// handles gestures rather than raw touch points.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Gesture {
+    #[default]
+    None,
+    /// A single finger down at this position, used for drawing.
+    Touch(ScreenPos),
+    /// Two fingers moved: `scale` is the ratio of their distances to the
+    /// previous frame and `pan` how far their midpoint moved, in pixels.
+    PinchPan {
+        center: ScreenPos,
+        scale: f32,
+        pan: [f32; 2],
+    },
+}
+
+/// Per-frame snapshot of the input state, built from the raw events received
+/// since the previous frame.
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    pub cursor: ScreenPos,
+    pub modifiers: Modifiers,
+    /// Accumulated scroll since the last frame.
+    pub scroll: [f32; 2],
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    keys_down: HashSet<u32>,
+    keys_pressed: HashSet<u32>,
+    touches: BTreeMap<u64, ScreenPos>,
+    gesture: Gesture,
+    /// Cursor positions received this frame while the left button was held,
+    /// including the press position.
+    drag_trail: Vec<ScreenPos>,
+}
+
+impl InputState {
+    /// Starts a new frame and folds `events` into the snapshot.
+    pub fn update<I: IntoIterator<Item = InputEvent>>(&mut self, events: I) {
+        self.scroll = [0.0, 0.0];
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.keys_pressed.clear();
+        let previous_touches = self.touches.clone();
+        self.drag_trail.clear();
+
+        for event in events {
+            match event {
+                InputEvent::CursorMoved(pos) => {
+                    self.cursor = pos;
+                    if self.is_down(MouseButton::Left) {
+                        self.push_trail(pos);
+                    }
+                }
+                InputEvent::Button { button, pressed } => {
+                    if pressed && button == MouseButton::Left {
+                        self.push_trail(self.cursor);
+                    }
+                    if pressed {
+                        self.buttons_down.insert(button);
+                        self.buttons_pressed.insert(button);
+                    } else {
+                        self.buttons_down.remove(&button);
+                        self.buttons_released.insert(button);
+                    }
+                }
+                InputEvent::Scroll(delta) => {
+                    self.scroll[0] += delta[0];
+                    self.scroll[1] += delta[1];
+                }
+                InputEvent::Key { code, pressed } => {
+                    if pressed {
+                        if self.keys_down.insert(code) {
+                            self.keys_pressed.insert(code);
+                        }
+                    } else {
+                        self.keys_down.remove(&code);
+                    }
+                }
+                InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+                InputEvent::Touch {
+                    id,
+                    position,
+                    phase,
+                } => match phase {
+                    TouchPhase::Started | TouchPhase::Moved => {
+                        self.touches.insert(id, position);
+                    }
+                    TouchPhase::Ended => {
+                        self.touches.remove(&id);
+                    }
+                },
+            }
+        }
+        self.gesture = self.detect_gesture(&previous_touches);
+    }
+
+    /// Appends to the drag trail, skipping repeats of the last position.
+    fn push_trail(&mut self, pos: ScreenPos) {
+        if self.drag_trail.last() != Some(&pos) {
+            self.drag_trail.push(pos);
+        }
+    }
+
+    /// Points to add to the stroke being drawn with the left button this
+    /// frame, in window pixels.
+    pub fn captured_points(&self, mode: CaptureMode) -> Vec<ScreenPos> {
+        match mode {
+            CaptureMode::PerEvent => self.drag_trail.clone(),
+            CaptureMode::PerFrame if self.is_down(MouseButton::Left) => vec![self.cursor],
+            CaptureMode::PerFrame => Vec::new(),
+        }
+    }
+
+    fn detect_gesture(&self, previous: &BTreeMap<u64, ScreenPos>) -> Gesture {
+        let mut current = self.touches.iter();
+        match (current.next(), current.next()) {
+            (Some((_, &p)), None) => Gesture::Touch(p),
+            (Some((&id_a, &a)), Some((&id_b, &b))) => {
+                let (prev_a, prev_b) = match (previous.get(&id_a), previous.get(&id_b)) {
+                    (Some(&pa), Some(&pb)) => (pa.0, pb.0),
+                    // A finger just landed: nothing to compare with yet.
+                    _ => return Gesture::None,
+                };
+                let (a, b) = (a.0, b.0);
+                let center = midpoint(a, b);
+                let prev_center = midpoint(prev_a, prev_b);
+                let prev_distance = distance(prev_a, prev_b);
+                let scale = if prev_distance > f32::EPSILON {
+                    distance(a, b) / prev_distance
+                } else {
+                    1.0
+                };
+                Gesture::PinchPan {
+                    center: ScreenPos(center),
+                    scale,
+                    pan: [center[0] - prev_center[0], center[1] - prev_center[1]],
+                }
+            }
+            _ => Gesture::None,
+        }
+    }
+
+    /// Gesture performed during the last frame; always `Gesture::None` where
+    /// touch is unavailable.
+    pub fn gesture(&self) -> Gesture {
+        self.gesture
+    }
+
+    pub fn is_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    /// Whether `button` went down during the last frame.
+    pub fn was_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    /// Whether `button` was released during the last frame.
+    pub fn was_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn is_key_down(&self, code: u32) -> bool {
+        self.keys_down.contains(&code)
+    }
+
+    /// Whether the key went down during the last frame. Key repeats do not
+    /// count as new presses.
+    pub fn was_key_pressed(&self, code: u32) -> bool {
+        self.keys_pressed.contains(&code)
+    }
+}
+
+/// Extrapolates the cursor one step ahead along its last motion, `factor`
+/// being the fraction of the last step to add.
+pub fn predict(prev: [f32; 2], cur: [f32; 2], factor: f32) -> [f32; 2] {
+    [
+        cur[0] + (cur[0] - prev[0]) * factor,
+        cur[1] + (cur[1] - prev[1]) * factor,
+    ]
+}
+
+/// Latency compensation for the live stroke: a predicted tip drawn ahead of
+/// the real cursor, replaced by the real position on the next frame. The
+/// prediction is display-only and never added to the stroke.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prediction {
+    pub enabled: bool,
+    pub factor: f32,
+}
+
+impl Prediction {
+    /// Tip to draw after the stroke's last real point `cur`, if any.
+    pub fn tip(&self, prev: [f32; 2], cur: [f32; 2]) -> Option<[f32; 2]> {
+        if self.enabled && self.factor > 0.0 && prev != cur {
+            Some(predict(prev, cur, self.factor))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Prediction {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            factor: 0.5,
+        }
+    }
+}
+
+fn midpoint(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0]
+}
+
+/// Text typed to place a shape precisely, as `x, y, width, height`. Commas,
+/// spaces, `x` and `×` all separate values, so `10,20 30x40` works too.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoordinateEntry {
+    pub buffer: String,
+}
+
+impl CoordinateEntry {
+    /// Accepts the characters that can appear in a coordinate; anything else
+    /// is ignored. Returns whether the character was taken.
+    pub fn push_char(&mut self, c: char) -> bool {
+        let accepted = c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | ',' | ' ' | 'x' | '×');
+        if accepted {
+            self.buffer.push(c);
+        }
+        accepted
+    }
+
+    pub fn backspace(&mut self) {
+        self.buffer.pop();
+    }
+
+    /// Parses the buffer as a rectangle origin and size. The buffer is
+    /// cleared on success and kept for correction otherwise.
+    pub fn submit(&mut self) -> Option<([f32; 2], [f32; 2])> {
+        let values: Vec<f32> = self
+            .buffer
+            .split([',', ' ', 'x', '×'])
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match values[..] {
+            [x, y, w, h] => {
+                self.buffer.clear();
+                Some(([x, y], [w, h]))
+            }
+            _ => None,
+        }
+    }
+}
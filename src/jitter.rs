@@ -0,0 +1,74 @@
+//! Brush scatter and color jitter for a sketchy, textured look.
+//!
+//! The captured points are stored untouched along with a seed, and the
+//! jitter is applied when meshing. Each point's offset depends only on the
+//! seed and the point's index, so a stroke looks the same after undo, reload
+//! or while being replayed.
+
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+/// SplitMix64 finalizer, giving a well-mixed hash of `x`.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Deterministic value in `[0, 1)` for point `index` and `channel`.
+fn unit(seed: u64, index: usize, channel: u64) -> f32 {
+    let hash = mix(seed ^ mix((index as u64) << 3 | channel));
+    // The top 24 bits fill an f32 mantissa exactly.
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Jitter {
+    /// Radius, in world units, within which each point is moved.
+    pub scatter: f32,
+    /// Largest change of each color channel, as a fraction of its range.
+    pub color: f32,
+    pub seed: u64,
+}
+
+impl Jitter {
+    pub fn is_off(&self) -> bool {
+        self.scatter <= 0.0 && self.color <= 0.0
+    }
+
+    /// The same settings with another seed, given to each new stroke.
+    pub fn seeded(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Moves each of `points` to a uniformly distributed spot within the
+    /// scatter radius.
+    pub fn scatter_points(&self, points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let angle = unit(self.seed, i, 0) * TAU;
+                let radius = self.scatter * unit(self.seed, i, 1).sqrt();
+                [p[0] + radius * angle.cos(), p[1] + radius * angle.sin()]
+            })
+            .collect()
+    }
+
+    /// Color of point `index` after jitter.
+    pub fn color_at(&self, index: usize, color: [u8; 4]) -> [u8; 4] {
+        if self.color <= 0.0 {
+            return color;
+        }
+        let mut out = color;
+        for (channel, value) in out[..3].iter_mut().enumerate() {
+            let offset = (unit(self.seed, index, 2 + channel as u64) * 2.0 - 1.0) * self.color;
+            *value = (f32::from(*value) + offset * 255.0)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+        out
+    }
+}
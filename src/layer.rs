@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::BlendMode;
+use crate::stroke::{erase_strokes, is_false, Stroke};
+
+/// A named group of strokes composited over the layers below it. Layers are
+/// rendered in list order, bottom first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub blend: BlendMode,
+    pub strokes: Vec<Stroke>,
+    /// Solid color covering the whole canvas beneath this layer's strokes.
+    /// Set on the background layer only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<[u8; 3]>,
+    /// Protects every stroke of the layer from selection and erasing.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            opacity: 1.0,
+            blend: BlendMode::Normal,
+            strokes: Vec::new(),
+            fill: None,
+            locked: false,
+        }
+    }
+
+    /// A layer filled with `color`, meant to sit at the bottom of the stack
+    /// in place of the clear color.
+    pub fn background(color: [u8; 3]) -> Self {
+        Self {
+            fill: Some(color),
+            ..Self::new("Background")
+        }
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.fill.is_some()
+    }
+
+    /// The strokes to run an edit on, or `None` while the layer is locked.
+    /// Edits such as `History::simplify_all` take a stroke list and only
+    /// skip locked strokes, so they must reach a layer through this.
+    pub fn editable_strokes(&mut self) -> Option<&mut Vec<Stroke>> {
+        if self.locked {
+            None
+        } else {
+            Some(&mut self.strokes)
+        }
+    }
+}
+
+/// Merges all layers into one, see `flatten_selected`.
+pub fn flatten(layers: &mut Vec<Layer>) {
+    let all: Vec<usize> = (0..layers.len()).collect();
+    flatten_selected(layers, &all);
+}
+
+/// Merges the layers at `indices` into the lowest of them, concatenating
+/// their strokes bottom first so the drawing order is preserved. The merged
+/// layer keeps the name, opacity, blend mode and fill of the lowest layer;
+/// hidden layers are dropped, as they do not contribute to the image.
+pub fn flatten_selected(layers: &mut Vec<Layer>, indices: &[usize]) {
+    let mut indices: Vec<usize> = indices
+        .iter()
+        .copied()
+        .filter(|&i| i < layers.len())
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    let (&target, rest) = match indices.split_first() {
+        Some(split) if !split.1.is_empty() => split,
+        _ => return,
+    };
+    let mut merged = Vec::new();
+    for &i in rest.iter().rev() {
+        let layer = layers.remove(i);
+        if layer.visible {
+            merged.push(layer.strokes);
+        }
+    }
+    let target = &mut layers[target];
+    if !target.visible {
+        target.strokes.clear();
+        target.visible = true;
+    }
+    for strokes in merged.into_iter().rev() {
+        target.strokes.extend(strokes);
+    }
+}
+
+/// Which layers an eraser affects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseScope {
+    /// Only the layer at this index, as layered editors do by default.
+    Layer(usize),
+    /// Every layer, e.g. while a modifier is held.
+    All,
+}
+
+/// Removes the strokes touched by the eraser circle on the unlocked layers
+/// in `scope`, returning how many were removed.
+pub fn erase(layers: &mut [Layer], scope: EraseScope, center: [f32; 2], radius: f32) -> usize {
+    let mut erase_layer = |layer: &mut Layer| {
+        if layer.locked {
+            0
+        } else {
+            erase_strokes(&mut layer.strokes, center, radius)
+        }
+    };
+    match scope {
+        EraseScope::Layer(index) => layers.get_mut(index).map_or(0, &mut erase_layer),
+        EraseScope::All => layers.iter_mut().map(erase_layer).sum(),
+    }
+}
+
+/// Rendered output of one layer, kept between frames so only layers whose
+/// strokes changed are rendered again. `texture` is whatever the renderer
+/// composites: a mesh today, an offscreen texture once there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerCache<T> {
+    pub texture: Option<T>,
+    pub dirty: bool,
+}
+
+impl<T> Default for LayerCache<T> {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            dirty: true,
+        }
+    }
+}
+
+/// One `LayerCache` per layer of a document, in layer order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerCaches<T> {
+    caches: Vec<LayerCache<T>>,
+}
+
+impl<T> Default for LayerCaches<T> {
+    fn default() -> Self {
+        Self { caches: Vec::new() }
+    }
+}
+
+impl<T> LayerCaches<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks layer `index` for re-rendering after its strokes changed.
+    pub fn mark_dirty(&mut self, index: usize) {
+        if let Some(cache) = self.caches.get_mut(index) {
+            cache.dirty = true;
+        }
+    }
+
+    /// Marks every layer, after layers were added, removed or reordered.
+    pub fn mark_all_dirty(&mut self) {
+        for cache in &mut self.caches {
+            cache.dirty = true;
+        }
+    }
+
+    /// Re-renders the dirty layers of `layers` with `render` and returns how
+    /// many were rendered. New layers start dirty.
+    pub fn update<F: FnMut(&Layer) -> T>(&mut self, layers: &[Layer], mut render: F) -> usize {
+        self.caches.resize_with(layers.len(), LayerCache::default);
+        let mut rendered = 0;
+        for (cache, layer) in self.caches.iter_mut().zip(layers) {
+            if cache.dirty {
+                cache.texture = Some(render(layer));
+                cache.dirty = false;
+                rendered += 1;
+            }
+        }
+        rendered
+    }
+
+    /// The cached output of the visible layers, bottom first, to composite
+    /// with each layer's opacity and blend mode.
+    pub fn visible<'a>(&'a self, layers: &'a [Layer]) -> impl Iterator<Item = (&'a Layer, &'a T)> {
+        layers
+            .iter()
+            .zip(&self.caches)
+            .filter(|(layer, _)| layer.visible)
+            .filter_map(|(layer, cache)| cache.texture.as_ref().map(|t| (layer, t)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_layers_have_no_editable_strokes() {
+        let mut layer = Layer::new("Ink");
+        layer.strokes.push(Stroke::new([0, 0, 0], 0.01));
+        assert_eq!(layer.editable_strokes().map(|s| s.len()), Some(1));
+        layer.locked = true;
+        assert!(layer.editable_strokes().is_none());
+    }
+}
@@ -0,0 +1,34 @@
+pub mod align;
+pub mod backend;
+pub mod camera;
+pub mod clock;
+pub mod color;
+pub mod coords;
+pub mod dirty;
+pub mod document;
+pub mod export;
+pub mod fade;
+pub mod feed;
+pub mod geometry;
+pub mod grid;
+pub mod history;
+pub mod input;
+pub mod jitter;
+pub mod layer;
+pub mod mesh;
+pub mod path;
+pub mod pressure;
+pub mod quit;
+pub mod recent;
+pub mod record;
+pub mod reference;
+pub mod render;
+pub mod replay;
+pub mod settings;
+pub mod smoothing;
+pub mod stats;
+pub mod stroke;
+pub mod svg;
+pub mod tool;
+pub mod width;
+pub mod worker;
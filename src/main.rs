@@ -1,3 +1,116 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use sketched::export;
+
+const USAGE: &str =
+    "usage: sketched --render-only <input> <output.png> [--size <width>x<height>] [--transparent]";
+
+/// Arguments of `--render-only`: render a sketch to a PNG and exit.
+#[derive(Debug, PartialEq)]
+struct RenderOnly {
+    input: PathBuf,
+    output: PathBuf,
+    size: Option<(u32, u32)>,
+    /// Leave the background out, for compositing elsewhere.
+    transparent: bool,
+}
+
+/// Parses `WIDTHxHEIGHT`, both sides at least one pixel.
+fn parse_size(text: &str) -> Option<(u32, u32)> {
+    let (width, height) = text.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    if size.0 > 0 && size.1 > 0 {
+        Some(size)
+    } else {
+        None
+    }
+}
+
+impl RenderOnly {
+    /// Reads the arguments following `--render-only`.
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        let mut size = None;
+        let mut transparent = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--transparent" {
+                transparent = true;
+            } else if arg == "--size" {
+                let value = args.next().ok_or("--size needs a value")?;
+                size = Some(parse_size(value).ok_or(format!("invalid size {:?}", value))?);
+            } else {
+                paths.push(PathBuf::from(arg));
+            }
+        }
+        match paths.as_slice() {
+            [input, output] => Ok(Self {
+                input: input.clone(),
+                output: output.clone(),
+                size,
+                transparent,
+            }),
+            _ => Err("expected an input and an output path".to_string()),
+        }
+    }
+}
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("--render-only") {
+        println!("Hello, world!");
+        return;
+    }
+    let options = match RenderOnly::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = export::render_file(
+        &options.input,
+        &options.output,
+        options.size,
+        options.transparent,
+    ) {
+        eprintln!("{}: {}", options.input.display(), err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn render_only_takes_two_paths_and_an_optional_size() {
+        assert_eq!(
+            RenderOnly::parse(&args(&["in.json", "out.png", "--size", "320x200"])),
+            Ok(RenderOnly {
+                input: "in.json".into(),
+                output: "out.png".into(),
+                size: Some((320, 200)),
+                transparent: false,
+            })
+        );
+        assert_eq!(
+            RenderOnly::parse(&args(&["in.json", "out.png"])).map(|o| o.size),
+            Ok(None)
+        );
+        assert_eq!(
+            RenderOnly::parse(&args(&["--transparent", "in.json", "out.png"]))
+                .map(|o| o.transparent),
+            Ok(true)
+        );
+        assert!(RenderOnly::parse(&args(&["in.json"])).is_err());
+        assert!(RenderOnly::parse(&args(&["in.json", "out.png", "--size", "0x10"])).is_err());
+        assert!(RenderOnly::parse(&args(&["in.json", "out.png", "--size"])).is_err());
+    }
 }
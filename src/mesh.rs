@@ -0,0 +1,1164 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::camera::stroke_visible;
+use crate::color;
+use crate::geometry;
+use crate::path::PATH_SAMPLES;
+use crate::smoothing::Interpolation;
+use crate::stroke::{
+    Arrowheads, JoinStyle, Outline, Stroke, StrokeStyle, ARROW_SCALE, MITER_LIMIT,
+};
+use crate::width;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub color: [u8; 4],
+}
+
+/// Indexed triangle list. Indices are `u32` so that a single mesh can hold
+/// the thousands of vertices of a long stroke or of a whole batched canvas.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Appends `other`, offsetting its indices past the current vertices.
+    pub fn append(&mut self, other: &Mesh) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend(other.indices.iter().map(|i| i + base));
+    }
+
+    /// Adds the quad `a b c d`, given in order around its outline.
+    fn push_quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+}
+
+fn normal(a: [f32; 2], b: [f32; 2]) -> Option<[f32; 2]> {
+    let d = [b[0] - a[0], b[1] - a[1]];
+    let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+    if len <= f32::EPSILON {
+        None
+    } else {
+        Some([-d[1] / len, d[0] / len])
+    }
+}
+
+/// Offset direction at each of `points`: the segment normal at the ends and
+/// the average of the adjacent segment normals at inner points. Points
+/// without a usable segment reuse the previous direction.
+fn point_normals(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let segment_normals: Vec<Option<[f32; 2]>> =
+        points.windows(2).map(|s| normal(s[0], s[1])).collect();
+    let mut last_normal = segment_normals
+        .iter()
+        .flatten()
+        .next()
+        .copied()
+        .unwrap_or([0.0, 1.0]);
+    (0..points.len())
+        .map(|i| {
+            let before = if i > 0 { segment_normals[i - 1] } else { None };
+            let after = segment_normals.get(i).copied().flatten();
+            let n = match (before, after) {
+                (Some(a), Some(b)) => {
+                    let sum = [a[0] + b[0], a[1] + b[1]];
+                    let len = (sum[0] * sum[0] + sum[1] * sum[1]).sqrt();
+                    if len <= f32::EPSILON {
+                        a
+                    } else {
+                        [sum[0] / len, sum[1] / len]
+                    }
+                }
+                (Some(n), None) | (None, Some(n)) => n,
+                (None, None) => last_normal,
+            };
+            last_normal = n;
+            n
+        })
+        .collect()
+}
+
+/// Value at `i` of a per-point attribute, where shorter lists repeat their
+/// last value.
+fn attribute<T: Copy>(values: &[T], i: usize, fallback: T) -> T {
+    values
+        .get(i)
+        .or_else(|| values.last())
+        .copied()
+        .unwrap_or(fallback)
+}
+
+/// Expands the polyline `points` into a triangle strip of half-width
+/// `half_widths[i]` at each point. At inner points the offset follows the
+/// average of the adjacent segment normals.
+pub fn polyline_to_mesh(points: &[[f32; 2]], half_widths: &[f32], colors: &[[u8; 4]]) -> Mesh {
+    let strip = polyline_to_strip(points, half_widths, colors);
+    Mesh {
+        vertices: strip.vertices.iter().map(StrokeVertex::expand).collect(),
+        indices: strip.indices,
+    }
+}
+
+/// Largest angle a single triangle of a round join covers.
+const ROUND_JOIN_STEP: f32 = std::f32::consts::PI / 8.0;
+
+/// Like `polyline_to_mesh`, but with a rectangle per segment and the outer
+/// gap at each corner filled in `join` style, so corners keep the full width
+/// instead of pinching. Repeated points are skipped.
+pub fn joined_polyline_to_mesh(
+    points: &[[f32; 2]],
+    half_widths: &[f32],
+    colors: &[[u8; 4]],
+    join: JoinStyle,
+) -> Mesh {
+    // Points with their attributes, without zero-length segments.
+    let mut nodes: Vec<([f32; 2], f32, [u8; 4])> = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        if nodes.last().is_none_or(|&(q, _, _)| normal(q, p).is_some()) {
+            nodes.push((
+                p,
+                attribute(half_widths, i, 0.0),
+                attribute(colors, i, [0, 0, 0, 255]),
+            ));
+        }
+    }
+    let mut mesh = Mesh::default();
+    let normals: Vec<[f32; 2]> = nodes
+        .windows(2)
+        .filter_map(|w| normal(w[0].0, w[1].0))
+        .collect();
+    let offset = |p: [f32; 2], n: [f32; 2], d: f32| [p[0] + n[0] * d, p[1] + n[1] * d];
+    let push = |mesh: &mut Mesh, position: [f32; 2], color: [u8; 4]| {
+        mesh.vertices.push(Vertex { position, color });
+        mesh.vertices.len() as u32 - 1
+    };
+
+    for (i, &n) in normals.iter().enumerate() {
+        let ((a, wa, ca), (b, wb, cb)) = (nodes[i], nodes[i + 1]);
+        let v0 = push(&mut mesh, offset(a, n, wa), ca);
+        let v1 = push(&mut mesh, offset(a, n, -wa), ca);
+        let v2 = push(&mut mesh, offset(b, n, wb), cb);
+        let v3 = push(&mut mesh, offset(b, n, -wb), cb);
+        mesh.push_quad(v0, v2, v3, v1);
+    }
+
+    for (i, pair) in normals.windows(2).enumerate() {
+        let (n0, n1) = (pair[0], pair[1]);
+        let (p, w, color) = nodes[i + 1];
+        // Normals turn the same way as the segments they belong to.
+        let cross = n0[0] * n1[1] - n0[1] * n1[0];
+        if cross.abs() <= f32::EPSILON && n0[0] * n1[0] + n0[1] * n1[1] > 0.0 {
+            continue;
+        }
+        // The gap opens on the outside of the turn.
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let (from, to) = ([n0[0] * side, n0[1] * side], [n1[0] * side, n1[1] * side]);
+        let center = push(&mut mesh, p, color);
+        let start = push(&mut mesh, offset(p, from, w), color);
+        let end = offset(p, to, w);
+        match join {
+            JoinStyle::Bevel => {
+                let end = push(&mut mesh, end, color);
+                mesh.indices.extend_from_slice(&[center, start, end]);
+            }
+            JoinStyle::Miter => {
+                let sum = [from[0] + to[0], from[1] + to[1]];
+                let len = (sum[0] * sum[0] + sum[1] * sum[1]).sqrt();
+                // Cosine of half the turn; the miter is 1 / cos long.
+                let cos = if len > f32::EPSILON {
+                    (from[0] * sum[0] + from[1] * sum[1]) / len
+                } else {
+                    0.0
+                };
+                let end = push(&mut mesh, end, color);
+                if cos * MITER_LIMIT >= 1.0 {
+                    let tip = offset(p, [sum[0] / len, sum[1] / len], w / cos);
+                    let tip = push(&mut mesh, tip, color);
+                    mesh.push_quad(center, start, tip, end);
+                } else {
+                    mesh.indices.extend_from_slice(&[center, start, end]);
+                }
+            }
+            JoinStyle::Round => {
+                let a0 = from[1].atan2(from[0]);
+                let mut sweep = to[1].atan2(to[0]) - a0;
+                // Take the short way round, on the outer side.
+                if sweep > std::f32::consts::PI {
+                    sweep -= std::f32::consts::TAU;
+                } else if sweep < -std::f32::consts::PI {
+                    sweep += std::f32::consts::TAU;
+                }
+                let steps = (sweep.abs() / ROUND_JOIN_STEP).ceil().max(1.0) as u32;
+                let mut prev = start;
+                for k in 1..=steps {
+                    let angle = a0 + sweep * k as f32 / steps as f32;
+                    let next = push(&mut mesh, offset(p, [angle.cos(), angle.sin()], w), color);
+                    mesh.indices.extend_from_slice(&[center, prev, next]);
+                    prev = next;
+                }
+            }
+        }
+    }
+    mesh
+}
+
+/// Like `polyline_to_mesh` with soft edges: the color fades out over
+/// `feather` centered on each edge, so the stroke keeps its apparent width.
+/// Antialiases in geometry what a distance field would in a fragment shader.
+pub fn feathered_polyline_to_mesh(
+    points: &[[f32; 2]],
+    half_widths: &[f32],
+    feather: f32,
+    colors: &[[u8; 4]],
+) -> Mesh {
+    let mut mesh = Mesh::default();
+    if points.len() < 2 {
+        return mesh;
+    }
+    let feather = feather.max(0.0);
+    for (i, (&p, n)) in points.iter().zip(point_normals(points)).enumerate() {
+        let half_width = attribute(half_widths, i, 0.0);
+        let color = attribute(colors, i, [0, 0, 0, 255]);
+        let clear = [color[0], color[1], color[2], 0];
+        let solid = (half_width - feather / 2.0).max(0.0);
+        let soft = half_width + feather / 2.0;
+        for &(offset, color) in &[
+            (soft, clear),
+            (solid, color),
+            (-solid, color),
+            (-soft, clear),
+        ] {
+            mesh.vertices.push(Vertex {
+                position: [p[0] + n[0] * offset, p[1] + n[1] * offset],
+                color,
+            });
+        }
+        if i > 0 {
+            let (a, b) = (4 * (i as u32 - 1), 4 * i as u32);
+            for band in 0..3 {
+                mesh.push_quad(a + band, b + band, b + band + 1, a + band + 1);
+            }
+        }
+    }
+    mesh
+}
+
+/// Vertex of a stroke strip before width expansion: both vertices of a
+/// point share its centerline position and carry opposite normals, and the
+/// vertex stage offsets each by `half_width` along its normal. Keeping the
+/// width per vertex lets pressure and tapering vary smoothly along the
+/// stroke without re-meshing when only the widths change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeVertex {
+    pub position: [f32; 2],
+    /// Unit offset direction, already pointing to this vertex's side.
+    pub normal: [f32; 2],
+    pub half_width: f32,
+    pub color: [u8; 4],
+}
+
+impl StrokeVertex {
+    /// The expanded vertex, as the vertex shader computes it.
+    pub fn expand(&self) -> Vertex {
+        Vertex {
+            position: [
+                self.position[0] + self.normal[0] * self.half_width,
+                self.position[1] + self.normal[1] * self.half_width,
+            ],
+            color: self.color,
+        }
+    }
+}
+
+/// Indexed triangle strip of unexpanded `StrokeVertex`es.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrokeMesh {
+    pub vertices: Vec<StrokeVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Like `polyline_to_mesh`, leaving the width expansion to the vertex stage.
+pub fn polyline_to_strip(
+    points: &[[f32; 2]],
+    half_widths: &[f32],
+    colors: &[[u8; 4]],
+) -> StrokeMesh {
+    let mut strip = StrokeMesh::default();
+    if points.len() < 2 {
+        return strip;
+    }
+    for (i, (&p, n)) in points.iter().zip(point_normals(points)).enumerate() {
+        let half_width = attribute(half_widths, i, 0.0);
+        let color = attribute(colors, i, [0, 0, 0, 255]);
+        for &side in &[1.0, -1.0] {
+            strip.vertices.push(StrokeVertex {
+                position: p,
+                normal: [n[0] * side, n[1] * side],
+                half_width,
+                color,
+            });
+        }
+        if i > 0 {
+            let base = (2 * (i - 1)) as u32;
+            strip
+                .indices
+                .extend_from_slice(&[base, base + 2, base + 3, base, base + 3, base + 1]);
+        }
+    }
+    strip
+}
+
+/// Unexpanded strip of `stroke` with a width per point, e.g. from pressure
+/// or `width::taper_widths`. Missing widths use the stroke width.
+pub fn stroke_to_strip(stroke: &Stroke, widths: &[f32]) -> StrokeMesh {
+    let half_widths: Vec<f32> = (0..stroke.points.len())
+        .map(|i| widths.get(i).copied().unwrap_or(stroke.width) / 2.0)
+        .collect();
+    let colors: Vec<[u8; 4]> = (0..stroke.points.len())
+        .map(|i| {
+            let [r, g, b] = stroke.color_at(i);
+            [r, g, b, 255]
+        })
+        .collect();
+    polyline_to_strip(&stroke.points, &half_widths, &colors)
+}
+
+/// One axis-aligned square of side `size` centered on each of `points`.
+pub fn points_to_mesh(points: &[[f32; 2]], size: f32, colors: &[[u8; 4]]) -> Mesh {
+    let mut mesh = Mesh::default();
+    let h = size / 2.0;
+    for (i, p) in points.iter().enumerate() {
+        let color = colors
+            .get(i)
+            .or_else(|| colors.last())
+            .copied()
+            .unwrap_or([0, 0, 0, 255]);
+        let base = mesh.vertices.len() as u32;
+        for &(dx, dy) in &[(-h, -h), (h, -h), (h, h), (-h, h)] {
+            mesh.vertices.push(Vertex {
+                position: [p[0] + dx, p[1] + dy],
+                color,
+            });
+        }
+        mesh.push_quad(base, base + 1, base + 2, base + 3);
+    }
+    mesh
+}
+
+/// Triangle of length `size` with its point at `tip`, facing along the unit
+/// vector `dir`, half as wide at the base as it is long.
+pub fn arrowhead_mesh(
+    tip: [f32; 2],
+    dir: [f32; 2],
+    size: f32,
+    color: [u8; 4],
+) -> (Vec<Vertex>, Vec<u32>) {
+    let back = [tip[0] - dir[0] * size, tip[1] - dir[1] * size];
+    let side = [-dir[1] * size / 2.0, dir[0] * size / 2.0];
+    let vertices = [
+        tip,
+        [back[0] + side[0], back[1] + side[1]],
+        [back[0] - side[0], back[1] - side[1]],
+    ]
+    .iter()
+    .map(|&position| Vertex { position, color })
+    .collect();
+    (vertices, vec![0, 1, 2])
+}
+
+/// Direction at the last of `points`, measured from the last point at least
+/// `length` away so that jitter near the end does not swing the arrow. Short
+/// strokes fall back to their first point; `None` when all points coincide.
+fn arrow_direction(points: &[[f32; 2]], length: f32) -> Option<[f32; 2]> {
+    let tip = *points.last()?;
+    let base = points
+        .iter()
+        .rev()
+        .find(|&&p| geometry::distance(p, tip) >= length)
+        .or_else(|| points.first())?;
+    let len = geometry::distance(*base, tip);
+    if len <= f32::EPSILON {
+        None
+    } else {
+        Some([(tip[0] - base[0]) / len, (tip[1] - base[1]) / len])
+    }
+}
+
+/// Appends the arrowheads of `stroke` to `mesh`, which follows `points`
+/// colored with `colors`. A positive `grow` widens each head by that much
+/// on every side, for outlines.
+fn add_arrowheads(
+    mesh: &mut Mesh,
+    stroke: &Stroke,
+    points: &[[f32; 2]],
+    colors: &[[u8; 4]],
+    grow: f32,
+) {
+    if stroke.arrows == Arrowheads::None || points.len() < 2 {
+        return;
+    }
+    let size = stroke.width * ARROW_SCALE;
+    // Inradius of the head; scaling about the incenter offsets every edge
+    // by the same distance.
+    let inradius = size / (1.0 + 5f32.sqrt());
+    let scale = 1.0 + grow / inradius.max(f32::EPSILON);
+    let mut add = |points: &[[f32; 2]], color: [u8; 4]| {
+        if let Some(dir) = arrow_direction(points, size) {
+            let end = points[points.len() - 1];
+            // Pushed out by half its length so the head covers the line's
+            // flat end.
+            let tip = [end[0] + dir[0] * size / 2.0, end[1] + dir[1] * size / 2.0];
+            let reach = (size - inradius) * scale;
+            let center = [
+                tip[0] - dir[0] * (size - inradius),
+                tip[1] - dir[1] * (size - inradius),
+            ];
+            let tip = [center[0] + dir[0] * reach, center[1] + dir[1] * reach];
+            let (vertices, indices) = arrowhead_mesh(tip, dir, size * scale, color);
+            mesh.append(&Mesh { vertices, indices });
+        }
+    };
+    let last_color = colors.last().copied().unwrap_or([0, 0, 0, 255]);
+    add(points, last_color);
+    if stroke.arrows == Arrowheads::Both {
+        let reversed: Vec<[f32; 2]> = points.iter().rev().copied().collect();
+        add(&reversed, colors.first().copied().unwrap_or(last_color));
+    }
+}
+
+/// Triangle fan of the hue/saturation wheel read by `color::wheel_color_at`,
+/// with `segments` slices: white at the center, fully saturated hues on the
+/// rim. At full value saturation is linear in RGB, so the interpolated
+/// vertex colors match the picked color along every radius.
+pub fn color_wheel_mesh(center: [f32; 2], radius: f32, segments: usize) -> Mesh {
+    let segments = segments.max(3);
+    let mut mesh = Mesh::default();
+    mesh.vertices.push(Vertex {
+        position: center,
+        color: [255, 255, 255, 255],
+    });
+    for i in 0..=segments {
+        let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+        let [r, g, b] = color::hsv_to_rgb([angle.to_degrees(), 1.0, 1.0]);
+        mesh.vertices.push(Vertex {
+            position: [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ],
+            color: [r, g, b, 255],
+        });
+    }
+    for i in 1..=segments as u32 {
+        mesh.indices.extend_from_slice(&[0, i, i + 1]);
+    }
+    mesh
+}
+
+/// Filled circle of `radius` around `center`, antialiased by a rim of
+/// `feather` width fading to transparent, the mesh equivalent of a distance
+/// field edge.
+pub fn circle_marker_mesh(center: [f32; 2], radius: f32, feather: f32, color: [u8; 4]) -> Mesh {
+    const SEGMENTS: u32 = 24;
+    let mut mesh = Mesh::default();
+    mesh.vertices.push(Vertex {
+        position: center,
+        color,
+    });
+    let clear = [color[0], color[1], color[2], 0];
+    for i in 0..SEGMENTS {
+        let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        for &(r, color) in &[(radius, color), (radius + feather, clear)] {
+            mesh.vertices.push(Vertex {
+                position: [center[0] + r * cos, center[1] + r * sin],
+                color,
+            });
+        }
+    }
+    for i in 0..SEGMENTS {
+        let (inner, outer) = (1 + 2 * i, 2 + 2 * i);
+        let (next_inner, next_outer) = (1 + 2 * ((i + 1) % SEGMENTS), 2 + 2 * ((i + 1) % SEGMENTS));
+        mesh.indices.extend_from_slice(&[0, inner, next_inner]);
+        mesh.push_quad(inner, outer, next_outer, next_inner);
+    }
+    mesh
+}
+
+/// Circle outline of `thickness` around `center`, antialiased like
+/// `circle_marker_mesh` by fading both edges out over `feather`.
+pub fn ring_mesh(
+    center: [f32; 2],
+    radius: f32,
+    thickness: f32,
+    feather: f32,
+    segments: usize,
+    color: [u8; 4],
+) -> Mesh {
+    let segments = segments.max(3) as u32;
+    let clear = [color[0], color[1], color[2], 0];
+    let (inner, outer) = (
+        (radius - thickness / 2.0).max(0.0),
+        radius + thickness / 2.0,
+    );
+    let rings = [
+        ((inner - feather).max(0.0), clear),
+        (inner, color),
+        (outer, color),
+        (outer + feather, clear),
+    ];
+    let mut mesh = Mesh::default();
+    for i in 0..segments {
+        let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        for &(r, color) in &rings {
+            mesh.vertices.push(Vertex {
+                position: [center[0] + r * cos, center[1] + r * sin],
+                color,
+            });
+        }
+    }
+    for i in 0..segments {
+        let (a, b) = (4 * i, 4 * ((i + 1) % segments));
+        for ring in 0..3 {
+            mesh.push_quad(a + ring, a + ring + 1, b + ring + 1, b + ring);
+        }
+    }
+    mesh
+}
+
+/// Outline showing the area the eraser will clear, drawn at the cursor
+/// while the eraser is active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EraserPreview {
+    pub enabled: bool,
+    /// Line thickness and edge feather in screen pixels, so the outline
+    /// looks the same at every zoom.
+    pub thickness: f32,
+    pub feather: f32,
+    pub color: [u8; 4],
+}
+
+impl EraserPreview {
+    /// Outline of an eraser of `radius` at `cursor`, both in world units,
+    /// with `pixel` world units per screen pixel at the current zoom.
+    /// Empty when disabled.
+    pub fn mesh(&self, cursor: [f32; 2], radius: f32, pixel: f32) -> Mesh {
+        if !self.enabled {
+            return Mesh::default();
+        }
+        let segments = geometry::ellipse_segments(radius / pixel.max(f32::EPSILON));
+        ring_mesh(
+            cursor,
+            radius,
+            self.thickness * pixel,
+            self.feather * pixel,
+            segments,
+            self.color,
+        )
+    }
+}
+
+impl Default for EraserPreview {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thickness: 1.5,
+            feather: 1.0,
+            color: [40, 40, 40, 200],
+        }
+    }
+}
+
+/// Look of the editable nodes of a path or stroke.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeMarkers {
+    /// Radius in world units; also the hit-test radius.
+    pub radius: f32,
+    pub feather: f32,
+    pub color: [u8; 4],
+    /// Color of the node under the cursor.
+    pub hover_color: [u8; 4],
+}
+
+impl NodeMarkers {
+    /// Markers for all `nodes`, with `hovered` (from `geometry::node_at`)
+    /// highlighted and drawn slightly larger.
+    pub fn mesh(&self, nodes: &[[f32; 2]], hovered: Option<usize>) -> Mesh {
+        let mut mesh = Mesh::default();
+        for (i, &node) in nodes.iter().enumerate() {
+            let marker = if hovered == Some(i) {
+                circle_marker_mesh(node, self.radius * 1.25, self.feather, self.hover_color)
+            } else {
+                circle_marker_mesh(node, self.radius, self.feather, self.color)
+            };
+            mesh.append(&marker);
+        }
+        mesh
+    }
+}
+
+impl Default for NodeMarkers {
+    fn default() -> Self {
+        Self {
+            radius: 0.008,
+            feather: 0.002,
+            color: [40, 120, 255, 255],
+            hover_color: [255, 140, 0, 255],
+        }
+    }
+}
+
+/// Points `stroke` is drawn through: its own or those of its path, scattered
+/// by its jitter.
+fn styled_points(stroke: &Stroke) -> Cow<'_, [[f32; 2]]> {
+    let points = match &stroke.path {
+        Some(path) => Cow::Owned(path.to_polyline(PATH_SAMPLES)),
+        None => Cow::Borrowed(&stroke.points[..]),
+    };
+    if stroke.jitter.is_off() {
+        points
+    } else {
+        Cow::Owned(stroke.jitter.scatter_points(&points))
+    }
+}
+
+/// Half-width of a line `stroke` drawn through `points` at each of them,
+/// following its width profile, grown by `grow`. A single value when the
+/// width is constant. The profile is computed on the captured points, whose
+/// timestamps the speed taper needs, and resampled when `points` is a
+/// smoothed version of them.
+fn half_widths(stroke: &Stroke, points: &[[f32; 2]], grow: f32) -> Vec<f32> {
+    if stroke.profile.is_constant() {
+        return vec![stroke.width / 2.0 + grow];
+    }
+    let mut widths = stroke
+        .profile
+        .widths(&stroke.points, &stroke.timestamps, stroke.width);
+    if points.len() != stroke.points.len() {
+        widths = width::resample(&stroke.points, &widths, points);
+    }
+    widths.into_iter().map(|w| w / 2.0 + grow).collect()
+}
+
+/// Mesh of `stroke`'s shape through `points`, grown by `grow` on every
+/// side.
+fn shape_mesh(stroke: &Stroke, points: &[[f32; 2]], colors: &[[u8; 4]], grow: f32) -> Mesh {
+    let mut mesh = match stroke.style {
+        StrokeStyle::Line => {
+            let half_widths = half_widths(stroke, points, grow);
+            if stroke.feather_width() > 0.0 {
+                feathered_polyline_to_mesh(points, &half_widths, stroke.feather_width(), colors)
+            } else if let Some(join) = stroke.join_style {
+                joined_polyline_to_mesh(points, &half_widths, colors, join)
+            } else {
+                polyline_to_mesh(points, &half_widths, colors)
+            }
+        }
+        StrokeStyle::Points { size } => points_to_mesh(points, size + 2.0 * grow, colors),
+    };
+    add_arrowheads(&mut mesh, stroke, points, colors, grow);
+    mesh
+}
+
+/// Meshes `stroke` in its style with the given per-point colors, jittered
+/// when the stroke asks for it.
+fn mesh_in_style(stroke: &Stroke, colors: &[[u8; 4]]) -> Mesh {
+    let jitter = stroke.jitter;
+    let colors = if jitter.is_off() {
+        Cow::Borrowed(colors)
+    } else {
+        Cow::Owned(
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| jitter.color_at(i, c))
+                .collect(),
+        )
+    };
+    shape_mesh(stroke, &styled_points(stroke), &colors, 0.0)
+}
+
+/// Halo of `stroke`: its shape widened by `outline.width` on every side in
+/// the outline color, to be drawn right behind it.
+pub fn outline_mesh(stroke: &Stroke, outline: Outline) -> Mesh {
+    let [r, g, b] = outline.color;
+    shape_mesh(
+        stroke,
+        &styled_points(stroke),
+        &[[r, g, b, 255]],
+        outline.width.max(0.0),
+    )
+}
+
+/// Batches `strokes` in draw order, each right after its outline: its own,
+/// or `default` for strokes without one. Outlines therefore cover strokes
+/// drawn before, as ink over a halo does on paper.
+pub fn outlined_strokes_to_mesh(strokes: &[Stroke], default: Option<Outline>) -> Mesh {
+    let mut mesh = Mesh::default();
+    for stroke in strokes {
+        if let Some(outline) = stroke.outline.or(default) {
+            mesh.append(&outline_mesh(stroke, outline));
+        }
+        mesh.append(&stroke_to_mesh(stroke));
+    }
+    mesh
+}
+
+/// Builds the triangle mesh of `stroke`.
+pub fn stroke_to_mesh(stroke: &Stroke) -> Mesh {
+    let colors: Vec<[u8; 4]> = (0..stroke.points.len())
+        .map(|i| {
+            let [r, g, b] = stroke.color_at(i);
+            [r, g, b, 255]
+        })
+        .collect();
+    mesh_in_style(stroke, &colors)
+}
+
+/// Like `stroke_to_mesh` with a separate opacity per point, e.g. from
+/// `FadingInk::point_alphas`.
+pub fn stroke_to_mesh_faded(stroke: &Stroke, alphas: &[u8]) -> Mesh {
+    let colors: Vec<[u8; 4]> = (0..stroke.points.len())
+        .map(|i| {
+            let [r, g, b] = stroke.color_at(i);
+            [r, g, b, alphas.get(i).copied().unwrap_or(255)]
+        })
+        .collect();
+    mesh_in_style(stroke, &colors)
+}
+
+/// Tessellation settings. Finer settings cost more vertices and time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshQuality {
+    /// Tolerance of the adaptive spline strokes are smoothed with before
+    /// meshing, or `None` to mesh the captured points directly.
+    pub smoothing: Option<f32>,
+    /// Turning angle in degrees past which points are kept as sharp corners
+    /// while smoothing, or `None` to smooth through them.
+    pub corner_angle: Option<f32>,
+}
+
+/// Quality used while editing, which favors speed, and for export, which
+/// favors crisp output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProfile {
+    pub interactive: MeshQuality,
+    pub export: MeshQuality,
+}
+
+impl Default for RenderProfile {
+    fn default() -> Self {
+        Self {
+            interactive: MeshQuality {
+                smoothing: None,
+                corner_angle: None,
+            },
+            export: MeshQuality {
+                smoothing: Some(0.0005),
+                corner_angle: Some(geometry::DEFAULT_CORNER_ANGLE),
+            },
+        }
+    }
+}
+
+/// Builds the mesh of `stroke` at the given quality.
+pub fn stroke_to_mesh_with(stroke: &Stroke, quality: MeshQuality) -> Mesh {
+    let tolerance = match quality.smoothing {
+        // Dots sit on the captured points, jitter would be smoothed away
+        // and paths are curves already, so there is nothing to smooth.
+        Some(tolerance)
+            if stroke.points.len() >= 3
+                && stroke.style == StrokeStyle::Line
+                && stroke.jitter.is_off()
+                && stroke.path.is_none() =>
+        {
+            tolerance
+        }
+        _ => return stroke_to_mesh(stroke),
+    };
+    let points = match quality.corner_angle {
+        Some(angle) => {
+            Interpolation::Adaptive.apply_keeping_corners(&stroke.points, tolerance, angle, 0)
+        }
+        None => geometry::adaptive_smooth(&stroke.points, tolerance),
+    };
+    let colors = if stroke.has_point_colors() {
+        colors_along(stroke, &points)
+    } else {
+        let [r, g, b] = stroke.color;
+        vec![[r, g, b, 255]]
+    };
+    shape_mesh(stroke, &points, &colors, 0.0)
+}
+
+/// Colors of `stroke` at `samples` of a smoothed version of it, matched by
+/// the fraction of the length travelled.
+fn colors_along(stroke: &Stroke, samples: &[[f32; 2]]) -> Vec<[u8; 4]> {
+    let source = width::arc_lengths(&stroke.points);
+    let target = width::arc_lengths(samples);
+    let (source_total, target_total) = (
+        source.last().copied().unwrap_or(0.0).max(f32::EPSILON),
+        target.last().copied().unwrap_or(0.0).max(f32::EPSILON),
+    );
+    target
+        .iter()
+        .map(|&s| {
+            let along = s / target_total * source_total;
+            let i = source
+                .partition_point(|&l| l < along)
+                .clamp(1, source.len() - 1);
+            let span = (source[i] - source[i - 1]).max(f32::EPSILON);
+            let t = ((along - source[i - 1]) / span).clamp(0.0, 1.0);
+            let [r, g, b] = color::lerp_rgb(stroke.color_at(i - 1), stroke.color_at(i), t);
+            [r, g, b, 255]
+        })
+        .collect()
+}
+
+/// Batches the meshes of `strokes` at the given quality, in draw order.
+pub fn strokes_to_mesh_with(strokes: &[Stroke], quality: MeshQuality) -> Mesh {
+    let mut mesh = Mesh::default();
+    for stroke in strokes {
+        mesh.append(&stroke_to_mesh_with(stroke, quality));
+    }
+    mesh
+}
+
+/// How the in-progress stroke is told apart from committed ones until it is
+/// committed and re-meshed in its own color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewStyle {
+    pub enabled: bool,
+    /// Color the live stroke is shifted toward.
+    pub tint: [u8; 3],
+    /// How far toward `tint`, from 0 (unchanged) to 1.
+    pub amount: f32,
+    pub alpha: u8,
+}
+
+impl PreviewStyle {
+    pub fn color(&self, color: [u8; 3]) -> [u8; 4] {
+        if !self.enabled {
+            let [r, g, b] = color;
+            return [r, g, b, 255];
+        }
+        let [r, g, b] = color::lerp_rgb(color, self.tint, self.amount);
+        [r, g, b, self.alpha]
+    }
+}
+
+impl Default for PreviewStyle {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tint: [64, 128, 255],
+            amount: 0.3,
+            alpha: 200,
+        }
+    }
+}
+
+/// Mesh of the stroke being drawn, in the preview style.
+pub fn preview_mesh(stroke: &Stroke, style: &PreviewStyle) -> Mesh {
+    let colors: Vec<[u8; 4]> = (0..stroke.points.len())
+        .map(|i| style.color(stroke.color_at(i)))
+        .collect();
+    mesh_in_style(stroke, &colors)
+}
+
+/// Batches the meshes of all `strokes` into one, in draw order.
+pub fn strokes_to_mesh(strokes: &[Stroke]) -> Mesh {
+    let mut mesh = Mesh::default();
+    for stroke in strokes {
+        mesh.append(&stroke_to_mesh(stroke));
+    }
+    mesh
+}
+
+/// Vertex and index ranges of one stroke within a `MeshBatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshRange {
+    pub vertices: Range<usize>,
+    pub indices: Range<usize>,
+}
+
+/// Combined mesh of a stroke list that grows incrementally: appending a
+/// stroke only meshes that stroke and records where its geometry landed, so
+/// the GPU buffers can be extended instead of re-uploaded. Deleting or
+/// reordering strokes needs a full `rebuild`.
+#[derive(Debug, Clone, Default)]
+pub struct MeshBatch {
+    mesh: Mesh,
+    ranges: Vec<MeshRange>,
+    /// First vertex and index not uploaded yet.
+    uploaded: (usize, usize),
+}
+
+impl MeshBatch {
+    pub fn new(strokes: &[Stroke]) -> Self {
+        let mut batch = Self::default();
+        batch.rebuild(strokes);
+        batch
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// Where the geometry of the stroke at `index` lives.
+    pub fn range(&self, index: usize) -> Option<&MeshRange> {
+        self.ranges.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Appends the geometry of `stroke`, drawn on top of the others.
+    pub fn push(&mut self, stroke: &Stroke) {
+        let start = (self.mesh.vertices.len(), self.mesh.indices.len());
+        self.mesh.append(&stroke_to_mesh(stroke));
+        self.ranges.push(MeshRange {
+            vertices: start.0..self.mesh.vertices.len(),
+            indices: start.1..self.mesh.indices.len(),
+        });
+    }
+
+    /// Re-meshes every stroke from scratch, after deletions or reordering.
+    pub fn rebuild(&mut self, strokes: &[Stroke]) {
+        self.mesh = Mesh::default();
+        self.ranges.clear();
+        self.uploaded = (0, 0);
+        for stroke in strokes {
+            self.push(stroke);
+        }
+    }
+
+    /// Vertex and index ranges added since the previous call, which are all
+    /// that needs uploading if the GPU buffers were not reallocated. After a
+    /// `rebuild` this covers the whole mesh.
+    pub fn take_pending(&mut self) -> MeshRange {
+        let pending = MeshRange {
+            vertices: self.uploaded.0..self.mesh.vertices.len(),
+            indices: self.uploaded.1..self.mesh.indices.len(),
+        };
+        self.uploaded = (self.mesh.vertices.len(), self.mesh.indices.len());
+        pending
+    }
+}
+
+/// Like `strokes_to_mesh`, skipping strokes entirely outside `view_rect`.
+pub fn visible_strokes_to_mesh(strokes: &[Stroke], view_rect: ([f32; 2], [f32; 2])) -> Mesh {
+    let mut mesh = Mesh::default();
+    for stroke in strokes {
+        if stroke
+            .bounds()
+            .is_some_and(|b| stroke_visible(b, view_rect))
+        {
+            mesh.append(&stroke_to_mesh(stroke));
+        }
+    }
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jitter::Jitter;
+    use crate::width::WidthProfile;
+
+    fn line_stroke(profile: WidthProfile) -> Stroke {
+        let mut stroke = Stroke::new([0, 0, 0], 0.2);
+        stroke.points = (0..11).map(|i| [i as f32 * 0.1, 0.0]).collect();
+        stroke.profile = profile;
+        stroke
+    }
+
+    /// Distance between the two vertices meshed for point `i` of a plain
+    /// line stroke.
+    fn width_at(mesh: &Mesh, i: usize) -> f32 {
+        geometry::distance(
+            mesh.vertices[2 * i].position,
+            mesh.vertices[2 * i + 1].position,
+        )
+    }
+
+    #[test]
+    fn constant_strokes_mesh_at_their_width() {
+        let mesh = stroke_to_mesh(&line_stroke(WidthProfile::default()));
+        for i in 0..11 {
+            assert!((width_at(&mesh, i) - 0.2).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn tapered_strokes_mesh_with_their_profile() {
+        let mesh = stroke_to_mesh(&line_stroke(WidthProfile {
+            taper: 0.2,
+            ..WidthProfile::default()
+        }));
+        assert!(width_at(&mesh, 0) < 1e-6);
+        assert!((width_at(&mesh, 1) - 0.1).abs() < 1e-5);
+        assert!((width_at(&mesh, 5) - 0.2).abs() < 1e-6);
+        assert!(width_at(&mesh, 10) < 1e-6);
+    }
+
+    #[test]
+    fn outlines_follow_the_taper() {
+        let stroke = line_stroke(WidthProfile {
+            taper: 0.2,
+            ..WidthProfile::default()
+        });
+        let outline = Outline {
+            width: 0.05,
+            color: [255, 255, 255],
+        };
+        let mesh = outline_mesh(&stroke, outline);
+        assert!((width_at(&mesh, 0) - 0.1).abs() < 1e-5);
+        assert!((width_at(&mesh, 5) - 0.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn width_smoothing_reaches_the_mesh() {
+        let sharp = stroke_to_mesh(&line_stroke(WidthProfile {
+            taper: 0.2,
+            ..WidthProfile::default()
+        }));
+        let smoothed = stroke_to_mesh(&line_stroke(WidthProfile {
+            taper: 0.2,
+            smoothing: 2,
+            ..WidthProfile::default()
+        }));
+        assert!((width_at(&sharp, 2) - 0.2).abs() < 1e-6);
+        assert!(width_at(&smoothed, 2) < 0.19);
+        assert!(width_at(&smoothed, 0) > 0.0);
+    }
+
+    #[test]
+    fn fast_strokes_taper_in_the_mesh() {
+        let mut stroke = line_stroke(WidthProfile {
+            speed: crate::width::SpeedTaper {
+                enabled: true,
+                ..Default::default()
+            },
+            ..WidthProfile::default()
+        });
+        stroke.timestamps = (0..11).map(|i| i as f32 * 0.001).collect();
+        let mesh = stroke_to_mesh(&stroke);
+        assert!(width_at(&mesh, 0) < 1e-6);
+        assert!((width_at(&mesh, 5) - 0.2).abs() < 1e-6);
+
+        // Without timestamps there is no speed to taper by.
+        stroke.timestamps.clear();
+        assert!((width_at(&stroke_to_mesh(&stroke), 0) - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn smoothed_strokes_resample_their_profile() {
+        let mut stroke = line_stroke(WidthProfile {
+            taper: 0.3,
+            ..WidthProfile::default()
+        });
+        stroke.points = (0..11)
+            .map(|i| [i as f32 * 0.1, (i as f32 * 0.8).sin() * 0.2])
+            .collect();
+        let quality = MeshQuality {
+            smoothing: Some(0.0005),
+            corner_angle: None,
+        };
+        let mesh = stroke_to_mesh_with(&stroke, quality);
+        let points = mesh.vertices.len() / 2;
+        assert!(points > stroke.points.len());
+        assert!(width_at(&mesh, 0) < 1e-6);
+        assert!(width_at(&mesh, points - 1) < 1e-6);
+        let widest = (0..points).map(|i| width_at(&mesh, i)).fold(0.0, f32::max);
+        assert!((widest - 0.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn path_strokes_mesh_from_their_path() {
+        let mut path = crate::path::BezierPath::new([0.0, 0.0]);
+        path.push([0.0, 1.0], [1.0, 1.0], [1.0, 0.0]);
+        path.push([1.0, -1.0], [2.0, -1.0], [2.0, 0.0]);
+        let mut stroke = Stroke::from_path(path, [0, 0, 0], 0.1);
+        let expected = stroke_to_mesh(&stroke);
+        assert_eq!(expected.vertices.len(), 2 * (2 * PATH_SAMPLES + 1));
+
+        // The mesh comes from the path, not from the flattened points.
+        stroke.points.truncate(2);
+        assert_eq!(stroke_to_mesh(&stroke), expected);
+        let quality = RenderProfile::default().export;
+        assert_eq!(stroke_to_mesh_with(&stroke, quality), expected);
+    }
+
+    fn assert_within_bounds(stroke: &Stroke, mesh: &Mesh) {
+        let (min, max) = stroke.bounds().unwrap();
+        for v in &mesh.vertices {
+            let [x, y] = v.position;
+            assert!(
+                x >= min[0] - 1e-5
+                    && x <= max[0] + 1e-5
+                    && y >= min[1] - 1e-5
+                    && y <= max[1] + 1e-5,
+                "{:?} outside {:?}",
+                v.position,
+                (min, max)
+            );
+        }
+    }
+
+    #[test]
+    fn decorations_stay_within_the_bounds() {
+        let mut arrows = line_stroke(WidthProfile::default());
+        arrows.arrows = Arrowheads::Both;
+        let mut outlined = arrows.clone();
+        outlined.outline = Some(Outline {
+            width: 0.05,
+            color: [255, 255, 255],
+        });
+        let mut dots = line_stroke(WidthProfile::default());
+        dots.style = StrokeStyle::Points { size: 0.5 };
+        let mut feathered = line_stroke(WidthProfile::default());
+        feathered.feather = 0.5;
+        let mut scattered = arrows.clone();
+        scattered.jitter = Jitter {
+            scatter: 0.1,
+            color: 0.0,
+            seed: 7,
+        };
+        // Turning by about 150 degrees, just under the miter limit.
+        let mut mitered = line_stroke(WidthProfile::default());
+        mitered.points = vec![[0.0, 0.0], [1.0, 0.0], [0.0, 0.6]];
+        mitered.join_style = Some(JoinStyle::Miter);
+        let mut outlined_miter = mitered.clone();
+        outlined_miter.outline = outlined.outline;
+        for stroke in &[
+            arrows,
+            outlined,
+            dots,
+            feathered,
+            scattered,
+            mitered,
+            outlined_miter,
+        ] {
+            assert_within_bounds(stroke, &stroke_to_mesh(stroke));
+            if let Some(outline) = stroke.outline {
+                assert_within_bounds(stroke, &outline_mesh(stroke, outline));
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{cubic_bezier, rotate_point};
+
+/// Segments each Bézier segment of a stroke's path is flattened into.
+pub const PATH_SAMPLES: usize = 16;
+
+/// One cubic segment of a path, starting where the previous one ended.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BezierSegment {
+    pub ctrl1: [f32; 2],
+    pub ctrl2: [f32; 2],
+    pub end: [f32; 2],
+}
+
+/// A chain of cubic Bézier segments. The control points are kept so that the
+/// path stays editable after it has been created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BezierPath {
+    pub start: [f32; 2],
+    pub segments: Vec<BezierSegment>,
+}
+
+impl BezierPath {
+    pub fn new(start: [f32; 2]) -> Self {
+        Self {
+            start,
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, ctrl1: [f32; 2], ctrl2: [f32; 2], end: [f32; 2]) {
+        self.segments.push(BezierSegment { ctrl1, ctrl2, end });
+    }
+
+    /// Last anchor point of the path, where the next segment will start.
+    pub fn end(&self) -> [f32; 2] {
+        self.segments.last().map_or(self.start, |s| s.end)
+    }
+
+    /// Flattens the path, using `samples` segments per Bézier segment.
+    pub fn to_polyline(&self, samples: usize) -> Vec<[f32; 2]> {
+        let mut points = vec![self.start];
+        let mut from = self.start;
+        for segment in &self.segments {
+            let curve = cubic_bezier(from, segment.ctrl1, segment.ctrl2, segment.end, samples);
+            points.extend_from_slice(&curve[1..]);
+            from = segment.end;
+        }
+        points
+    }
+
+    /// Every editable node: the start, then each segment's first control,
+    /// second control and end. Indices match `node_mut`.
+    pub fn nodes(&self) -> Vec<[f32; 2]> {
+        let mut nodes = Vec::with_capacity(1 + self.segments.len() * 3);
+        nodes.push(self.start);
+        for segment in &self.segments {
+            nodes.extend_from_slice(&[segment.ctrl1, segment.ctrl2, segment.end]);
+        }
+        nodes
+    }
+
+    /// The node at `index` in `nodes` order, for dragging it.
+    pub fn node_mut(&mut self, index: usize) -> Option<&mut [f32; 2]> {
+        if index == 0 {
+            return Some(&mut self.start);
+        }
+        let segment = self.segments.get_mut((index - 1) / 3)?;
+        Some(match (index - 1) % 3 {
+            0 => &mut segment.ctrl1,
+            1 => &mut segment.ctrl2,
+            _ => &mut segment.end,
+        })
+    }
+
+    pub fn translate(&mut self, delta: [f32; 2]) {
+        self.map(|p| [p[0] + delta[0], p[1] + delta[1]]);
+    }
+
+    /// Rotates every node counterclockwise by `angle` radians around
+    /// `center`.
+    pub fn rotate(&mut self, center: [f32; 2], angle: f32) {
+        self.map(|p| rotate_point(p, center, angle));
+    }
+
+    fn map<F: Fn([f32; 2]) -> [f32; 2]>(&mut self, f: F) {
+        self.start = f(self.start);
+        for segment in &mut self.segments {
+            segment.ctrl1 = f(segment.ctrl1);
+            segment.ctrl2 = f(segment.ctrl2);
+            segment.end = f(segment.end);
+        }
+    }
+
+    /// Tangent lines from each anchor to its control handle, for display
+    /// while editing.
+    pub fn handles(&self) -> Vec<([f32; 2], [f32; 2])> {
+        let mut lines = Vec::with_capacity(self.segments.len() * 2);
+        let mut from = self.start;
+        for segment in &self.segments {
+            lines.push((from, segment.ctrl1));
+            lines.push((segment.end, segment.ctrl2));
+            from = segment.end;
+        }
+        lines
+    }
+}
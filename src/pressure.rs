@@ -0,0 +1,125 @@
+/// Response curve mapping raw tablet pressure to the pressure used for width
+/// and opacity. Both axes are in `[0, 1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PressureCurve {
+    /// Control points sorted by input pressure.
+    pub points: Vec<(f32, f32)>,
+}
+
+impl PressureCurve {
+    pub fn new(mut points: Vec<(f32, f32)>) -> Self {
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Self { points }
+    }
+
+    pub fn linear() -> Self {
+        Self::new(vec![(0.0, 0.0), (1.0, 1.0)])
+    }
+
+    /// Light touches already give a wide stroke.
+    pub fn soft() -> Self {
+        Self::new(vec![(0.0, 0.0), (0.3, 0.55), (1.0, 1.0)])
+    }
+
+    /// A firm press is needed before the stroke widens.
+    pub fn hard() -> Self {
+        Self::new(vec![(0.0, 0.0), (0.7, 0.45), (1.0, 1.0)])
+    }
+
+    /// Maps `p` through the curve by linear interpolation between control
+    /// points, clamping outside their range.
+    pub fn apply(&self, p: f32) -> f32 {
+        let p = p.clamp(0.0, 1.0);
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return p,
+        };
+        if p <= first.0 {
+            return first.1;
+        }
+        if p >= last.0 {
+            return last.1;
+        }
+        for pair in self.points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if p <= b.0 {
+                if b.0 - a.0 <= f32::EPSILON {
+                    return b.1;
+                }
+                let t = (p - a.0) / (b.0 - a.0);
+                return a.1 + (b.1 - a.1) * t;
+            }
+        }
+        last.1
+    }
+}
+
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self::linear()
+    }
+}
+
+/// What a pressure sample means for the stroke being captured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Contact {
+    /// Below the threshold with no stroke in progress; add nothing.
+    None,
+    /// Pressure just crossed the threshold: start a stroke at this point.
+    Started(f32),
+    /// Still above the threshold: add the point to the stroke.
+    Continued(f32),
+    /// Pressure dropped below the threshold: finish the stroke without
+    /// adding this point.
+    Ended,
+}
+
+/// Ignores pen contact lighter than `threshold`, so resting or brushing the
+/// pen against the tablet leaves no stray marks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PressureThreshold {
+    /// Raw pressure in `[0, 1]` a point needs to register.
+    pub threshold: f32,
+    down: bool,
+}
+
+impl PressureThreshold {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold: threshold.clamp(0.0, 0.99),
+            down: false,
+        }
+    }
+
+    /// Classifies a raw pressure sample. The pressure handed on is rescaled
+    /// so it starts from zero at the threshold, which keeps a stroke from
+    /// starting with a jump in width.
+    pub fn feed(&mut self, pressure: f32) -> Contact {
+        let threshold = self.threshold.clamp(0.0, 0.99);
+        if pressure <= threshold {
+            let was_down = std::mem::replace(&mut self.down, false);
+            return if was_down {
+                Contact::Ended
+            } else {
+                Contact::None
+            };
+        }
+        let effective = ((pressure - threshold) / (1.0 - threshold)).clamp(0.0, 1.0);
+        if std::mem::replace(&mut self.down, true) {
+            Contact::Continued(effective)
+        } else {
+            Contact::Started(effective)
+        }
+    }
+
+    /// Forgets the contact state, e.g. when the pen leaves proximity.
+    pub fn reset(&mut self) {
+        self.down = false;
+    }
+}
+
+impl Default for PressureThreshold {
+    fn default() -> Self {
+        Self::new(0.05)
+    }
+}
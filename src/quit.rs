@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::{InputState, Modifiers};
+
+/// Key codes of the desktop backend (winit's `VirtualKeyCode`) used by the
+/// default bindings.
+pub const KEY_Q: u32 = 26;
+pub const KEY_ESCAPE: u32 = 36;
+
+/// How long after a first quit request a second one confirms it.
+pub const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Guards quitting while there are unsaved changes: the first request only
+/// arms the guard, and a second one within the timeout confirms it.
+#[derive(Debug, Clone)]
+pub struct QuitGuard {
+    pub timeout: Duration,
+    armed_at: Option<Instant>,
+}
+
+impl QuitGuard {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            armed_at: None,
+        }
+    }
+
+    /// Handles a quit request made at `now`, returning whether the app should
+    /// exit. Unmodified documents quit right away.
+    pub fn request(&mut self, modified: bool, now: Instant) -> bool {
+        if !modified {
+            return true;
+        }
+        if self.is_pending(now) {
+            return true;
+        }
+        self.armed_at = Some(now);
+        false
+    }
+
+    /// Whether a confirmation is currently awaited, e.g. to show a prompt.
+    pub fn is_pending(&self, now: Instant) -> bool {
+        self.armed_at
+            .is_some_and(|armed_at| now.duration_since(armed_at) <= self.timeout)
+    }
+}
+
+impl Default for QuitGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONFIRM_TIMEOUT)
+    }
+}
+
+/// A key together with the exact modifiers that must be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyChord {
+    pub code: u32,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn was_pressed(&self, input: &InputState) -> bool {
+        input.was_key_pressed(self.code) && input.modifiers == self.modifiers
+    }
+}
+
+/// What a bare Escape press does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscapeAction {
+    /// Quits like the quit chord.
+    Quit,
+    /// Asks for a second press within the timeout, even with nothing
+    /// unsaved.
+    #[default]
+    Confirm,
+    /// Escape never quits.
+    Disabled,
+}
+
+/// Keys that quit the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuitKeys {
+    pub quit: KeyChord,
+    #[serde(default)]
+    pub escape: EscapeAction,
+}
+
+impl QuitKeys {
+    /// Checks this frame's `input` for a quit request and passes it to
+    /// `guard`, returning whether the app should exit.
+    pub fn handle(
+        &self,
+        input: &InputState,
+        guard: &mut QuitGuard,
+        modified: bool,
+        now: Instant,
+    ) -> bool {
+        if self.quit.was_pressed(input) {
+            return guard.request(modified, now);
+        }
+        let escape = KeyChord {
+            code: KEY_ESCAPE,
+            modifiers: Modifiers::default(),
+        };
+        if !escape.was_pressed(input) {
+            return false;
+        }
+        match self.escape {
+            EscapeAction::Quit => guard.request(modified, now),
+            EscapeAction::Confirm => guard.request(true, now),
+            EscapeAction::Disabled => false,
+        }
+    }
+}
+
+impl Default for QuitKeys {
+    /// Ctrl+Q, with Escape asking for confirmation.
+    fn default() -> Self {
+        Self {
+            quit: KeyChord {
+                code: KEY_Q,
+                modifiers: Modifiers {
+                    control: true,
+                    ..Modifiers::default()
+                },
+            },
+            escape: EscapeAction::Confirm,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MAX_RECENT: usize = 10;
+
+/// Most recently opened or saved sketches, newest first. Stored as one path
+/// per line in a small state file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentFiles {
+    pub paths: VecDeque<PathBuf>,
+    pub max: usize,
+}
+
+impl RecentFiles {
+    pub fn new(max: usize) -> Self {
+        Self {
+            paths: VecDeque::new(),
+            max,
+        }
+    }
+
+    /// Moves `path` to the front of the list, dropping the oldest entries
+    /// beyond the maximum.
+    pub fn push<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        self.paths.retain(|p| *p != path);
+        self.paths.push_front(path);
+        self.paths.truncate(self.max);
+    }
+
+    /// Recent files which still exist on disk.
+    pub fn existing(&self) -> Vec<&Path> {
+        self.paths
+            .iter()
+            .map(PathBuf::as_path)
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Loads the list from `file`. A missing file gives an empty list.
+    pub fn load<P: AsRef<Path>>(file: P, max: usize) -> io::Result<Self> {
+        let mut recent = Self::new(max);
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(recent),
+            Err(err) => return Err(err),
+        };
+        for line in content.lines().rev().filter(|l| !l.trim().is_empty()) {
+            recent.push(line);
+        }
+        Ok(recent)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
+        let mut content = String::new();
+        for path in &self.paths {
+            content.push_str(&path.to_string_lossy());
+            content.push('\n');
+        }
+        fs::write(file, content)
+    }
+}
+
+impl Default for RecentFiles {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RECENT)
+    }
+}
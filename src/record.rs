@@ -0,0 +1,185 @@
+//! Input recording and playback, for reproducing a session event for event.
+//!
+//! A recording is newline-delimited JSON with one line per frame, holding the
+//! events the backend delivered that frame. Playback feeds the frames back
+//! through a `Headless` backend, so it goes through the same
+//! `poll_events` / `InputState::update` path as live input.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backend, Headless, ScriptedFrame};
+use crate::input::InputEvent;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Frame {
+    /// Seconds since recording started.
+    time: f32,
+    size: (u32, u32),
+    events: Vec<InputEvent>,
+}
+
+/// Backend wrapper writing every polled frame of `inner` to a recording.
+pub struct Recorder<B> {
+    inner: B,
+    out: BufWriter<File>,
+    started: Instant,
+}
+
+/// Starts recording the input of `backend` to `path`.
+pub fn record_input<B: Backend>(path: impl AsRef<Path>, backend: B) -> io::Result<Recorder<B>> {
+    Ok(Recorder {
+        out: BufWriter::new(File::create(path)?),
+        started: backend.frame_time(),
+        inner: backend,
+    })
+}
+
+impl<B: Backend> Recorder<B> {
+    fn write_frame(&mut self, events: &[InputEvent]) -> io::Result<()> {
+        let frame = Frame {
+            time: self
+                .inner
+                .frame_time()
+                .saturating_duration_since(self.started)
+                .as_secs_f32(),
+            size: self.inner.framebuffer_size(),
+            events: events.to_vec(),
+        };
+        serde_json::to_writer(&mut self.out, &frame)?;
+        self.out.write_all(b"\n")?;
+        // Flushed every frame so the recording survives a crash.
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Backend> Backend for Recorder<B> {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        let events = self.inner.poll_events();
+        if let Err(err) = self.write_frame(&events) {
+            log::warn!("failed to record input: {}", err);
+        }
+        events
+    }
+
+    fn swap(&mut self) {
+        self.inner.swap()
+    }
+
+    fn framebuffer_size(&self) -> (u32, u32) {
+        self.inner.framebuffer_size()
+    }
+
+    fn should_close(&self) -> bool {
+        self.inner.should_close()
+    }
+
+    fn frame_time(&self) -> Instant {
+        self.inner.frame_time()
+    }
+}
+
+/// Loads the recording at `path` as a headless backend replaying it frame by
+/// frame, each at the window size and time it was recorded with, so that
+/// resizes, minimizing and time-dependent input play back as they happened.
+pub fn replay_input(path: impl AsRef<Path>) -> io::Result<Headless> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut frames: Vec<ScriptedFrame> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame: Frame = serde_json::from_str(&line)?;
+        frames.push(ScriptedFrame {
+            time: Duration::from_secs_f32(frame.time.max(0.0)),
+            size: frame.size,
+            events: frame.events,
+        });
+    }
+    let size = frames.first().map_or((0, 0), |f| f.size);
+    Ok(Headless::scripted(size, frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ScreenPos;
+    use crate::input::{DoubleClick, MouseButton};
+
+    fn press(pressed: bool) -> InputEvent {
+        InputEvent::Button {
+            button: MouseButton::Left,
+            pressed,
+        }
+    }
+
+    /// Size, time since the first poll and events of every frame.
+    fn drain<B: Backend>(backend: &mut B) -> Vec<((u32, u32), Duration, Vec<InputEvent>)> {
+        let started = backend.frame_time();
+        let mut frames = Vec::new();
+        while !backend.should_close() {
+            let events = backend.poll_events();
+            let time = backend.frame_time().saturating_duration_since(started);
+            frames.push((backend.framebuffer_size(), time, events));
+        }
+        frames
+    }
+
+    #[test]
+    fn replays_keep_sizes_and_timing() {
+        let frame = |ms, size, events| ScriptedFrame {
+            time: Duration::from_millis(ms),
+            size,
+            events,
+        };
+        let session = Headless::scripted(
+            (800, 600),
+            vec![
+                frame(0, (800, 600), vec![press(true)]),
+                frame(100, (800, 600), vec![press(false)]),
+                // Minimized, then restored larger.
+                frame(200, (0, 0), Vec::new()),
+                frame(350, (1024, 768), vec![press(true)]),
+            ],
+        );
+        let path =
+            std::env::temp_dir().join(format!("sketched-input-{}.jsonl", std::process::id()));
+        let mut recorder = record_input(&path, session).unwrap();
+        let recorded = drain(&mut recorder);
+        drop(recorder);
+        let mut replay = replay_input(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.framebuffer_size(), (800, 600));
+        let replayed = drain(&mut replay);
+
+        assert_eq!(replayed.len(), 4);
+        for (recorded, replayed) in recorded.iter().zip(&replayed) {
+            assert_eq!((recorded.0, &recorded.2), (replayed.0, &replayed.2));
+            let drift = recorded.1.max(replayed.1) - recorded.1.min(replayed.1);
+            assert!(drift < Duration::from_millis(1));
+        }
+        let sizes: Vec<(u32, u32)> = replayed.iter().map(|f| f.0).collect();
+        assert_eq!(sizes, [(800, 600), (800, 600), (0, 0), (1024, 768)]);
+
+        // The second press comes 350 ms after the first, as recorded, not
+        // as fast as the replay runs.
+        let start = replay.frame_time() - replayed[3].1;
+        let (first, second) = (start + replayed[0].1, start + replayed[3].1);
+        let pos = ScreenPos([10.0, 10.0]);
+        assert!(DoubleClick::default().matches(first, pos, second, pos));
+        let short = DoubleClick {
+            max_interval: Duration::from_millis(300),
+            ..DoubleClick::default()
+        };
+        assert!(!short.matches(first, pos, second, pos));
+    }
+}
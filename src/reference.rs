@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Opacity change per key press.
+pub const OPACITY_STEP: f32 = 0.1;
+
+/// Where the reference image sits relative to the drawing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Placement {
+    /// Under every layer, for tracing over it.
+    #[default]
+    Below,
+    /// Over every layer, to check the drawing against it.
+    Above,
+}
+
+/// Repeating the image across its bounds instead of fitting it once, for
+/// paper and canvas textures.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tiling {
+    pub enabled: bool,
+    /// Size of one tile relative to the image's pixel size; 0.5 repeats a
+    /// texture twice as often.
+    pub scale: f32,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Textured quad to draw: its world rectangle and the texture coordinates
+/// at its corners, from `uv_min` at `rect.0` to `uv_max` at `rect.1`.
+/// Coordinates past 0..1 rely on the texture's wrap mode being set to
+/// repeat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexturedQuad {
+    pub rect: ([f32; 2], [f32; 2]),
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// An image loaded for tracing, drawn as a textured quad at reduced opacity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceImage {
+    pub path: PathBuf,
+    /// Pixel size of the image, used to keep its aspect ratio.
+    pub size: (u32, u32),
+    pub visible: bool,
+    pub opacity: f32,
+    pub placement: Placement,
+    #[serde(default)]
+    pub tiling: Tiling,
+}
+
+impl ReferenceImage {
+    pub fn new<P: Into<PathBuf>>(path: P, size: (u32, u32)) -> Self {
+        Self {
+            path: path.into(),
+            size,
+            visible: true,
+            opacity: 0.5,
+            placement: Placement::Below,
+            tiling: Tiling::default(),
+        }
+    }
+
+    pub fn adjust_opacity(&mut self, delta: f32) {
+        self.opacity = (self.opacity + delta).clamp(0.0, 1.0);
+    }
+
+    pub fn toggle_visible(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn toggle_placement(&mut self) {
+        self.placement = match self.placement {
+            Placement::Below => Placement::Above,
+            Placement::Above => Placement::Below,
+        };
+    }
+
+    /// World rectangle of the quad: the largest rectangle with the image's
+    /// aspect ratio fitting centered in `bounds`, whose pixel size is
+    /// `bounds_px`.
+    pub fn fit(&self, bounds: ([f32; 2], [f32; 2]), bounds_px: (u32, u32)) -> ([f32; 2], [f32; 2]) {
+        let (min, max) = bounds;
+        let (w, h) = (self.size.0.max(1) as f32, self.size.1.max(1) as f32);
+        let (bw, bh) = (bounds_px.0.max(1) as f32, bounds_px.1.max(1) as f32);
+        let scale = (bw / w).min(bh / h);
+        // Fraction of the bounds covered along each axis.
+        let fx = w * scale / bw;
+        let fy = h * scale / bh;
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        let half = [(max[0] - min[0]) * fx / 2.0, (max[1] - min[1]) * fy / 2.0];
+        (
+            [center[0] - half[0], center[1] - half[1]],
+            [center[0] + half[0], center[1] + half[1]],
+        )
+    }
+
+    /// Quad drawing the image over `bounds`, whose pixel size is
+    /// `bounds_px`: fitted once with `fit`, or covering all of `bounds` with
+    /// tiles of `tiling.scale` times the image size, anchored at the top
+    /// left so the pattern stays put as the bounds grow.
+    pub fn quad(&self, bounds: ([f32; 2], [f32; 2]), bounds_px: (u32, u32)) -> TexturedQuad {
+        if !self.tiling.enabled {
+            return TexturedQuad {
+                rect: self.fit(bounds, bounds_px),
+                uv_min: [0.0, 1.0],
+                uv_max: [1.0, 0.0],
+            };
+        }
+        let scale = self.tiling.scale.max(0.01);
+        let tile = [
+            self.size.0.max(1) as f32 * scale,
+            self.size.1.max(1) as f32 * scale,
+        ];
+        let repeats = [bounds_px.0 as f32 / tile[0], bounds_px.1 as f32 / tile[1]];
+        // Texture rows go down while world y goes up, so v grows from the
+        // top edge of the bounds.
+        TexturedQuad {
+            rect: bounds,
+            uv_min: [0.0, repeats[1]],
+            uv_max: [repeats[0], 0.0],
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Software rendering of stroke meshes into RGBA8 images, for output made
+//! without a window: batch conversion, thumbnails and exports. Colors are
+//! blended in linear light as on the canvas, and images hold straight (not
+//! premultiplied) alpha over sRGB channels, as `export::encode_png` expects.
+
+use crate::camera::Camera;
+use crate::color::{from_linear, to_linear, BlendMode};
+use crate::coords::WorldPos;
+use crate::document::{Document, Page};
+use crate::layer::Layer;
+use crate::mesh::{outlined_strokes_to_mesh, Mesh};
+use crate::stroke::{self, Stroke};
+
+/// Space left around the content of a thumbnail, as a fraction of its size.
+pub const THUMBNAIL_MARGIN: f32 = 0.05;
+
+/// An image of `size` pixels filled with `color`, or fully transparent.
+pub fn clear(size: (u32, u32), color: Option<[u8; 3]>) -> Vec<u8> {
+    let pixel = match color {
+        Some([r, g, b]) => [r, g, b, 255],
+        None => [0; 4],
+    };
+    pixel
+        .iter()
+        .copied()
+        .cycle()
+        .take(size.0 as usize * size.1 as usize * 4)
+        .collect()
+}
+
+/// Composites `src` with coverage `alpha` onto the RGBA8 pixel `dst`. Blend
+/// modes need something to blend with, so over transparent pixels the
+/// source shows as is.
+fn blend_pixel(dst: &mut [u8], src: [u8; 3], alpha: f32, blend: BlendMode) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+    let below = f32::from(dst[3]) / 255.0;
+    let s = to_linear(src);
+    let d = to_linear([dst[0], dst[1], dst[2]]);
+    let blended = blend.composite(s, 1.0, d);
+    let out_alpha = alpha + below * (1.0 - alpha);
+    let mut out = [0.0; 3];
+    for i in 0..3 {
+        let mixed = s[i] + (blended[i] - s[i]) * below;
+        out[i] = (mixed * alpha + d[i] * below * (1.0 - alpha)) / out_alpha;
+    }
+    let [r, g, b] = from_linear(out);
+    dst.copy_from_slice(&[r, g, b, (out_alpha * 255.0).round() as u8]);
+}
+
+/// Twice the signed area of triangle `a`, `b`, `p`: positive when `p` lies
+/// to the left of `a`→`b` with y pointing down.
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether pixel centers exactly on the edge `a`→`b` are drawn. An edge
+/// shared by two triangles runs in opposite directions in each and only one
+/// direction qualifies, so its pixels are drawn once.
+fn owns_edge(a: [f32; 2], b: [f32; 2]) -> bool {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    dy < 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+fn fill_triangle(pixels: &mut [u8], size: (u32, u32), mut p: [[f32; 2]; 3], mut c: [[u8; 4]; 3]) {
+    let mut area = edge(p[0], p[1], p[2]);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    if area < 0.0 {
+        p.swap(0, 1);
+        c.swap(0, 1);
+        area = -area;
+    }
+    let (width, height) = (size.0 as f32, size.1 as f32);
+    let span = |axis: usize, limit: f32| {
+        let lo = p.iter().map(|q| q[axis]).fold(f32::INFINITY, f32::min);
+        let hi = p.iter().map(|q| q[axis]).fold(f32::NEG_INFINITY, f32::max);
+        (
+            lo.floor().clamp(0.0, limit) as usize,
+            hi.ceil().clamp(0.0, limit) as usize,
+        )
+    };
+    let ((x0, x1), (y0, y1)) = (span(0, width), span(1, height));
+    // Vertex i is weighted by the edge facing it.
+    let edges = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let center = [x as f32 + 0.5, y as f32 + 0.5];
+            let mut weights = [0.0; 3];
+            let mut inside = true;
+            for (weight, &(a, b)) in weights.iter_mut().zip(&edges) {
+                let w = edge(a, b, center);
+                inside &= w > 0.0 || (w == 0.0 && owns_edge(a, b));
+                *weight = w / area;
+            }
+            if !inside {
+                continue;
+            }
+            let mut color = [0.0; 4];
+            for (vertex, weight) in c.iter().zip(&weights) {
+                for (channel, &value) in color.iter_mut().zip(vertex) {
+                    *channel += f32::from(value) * weight;
+                }
+            }
+            let [r, g, b, a] = color;
+            let rgb = [r, g, b].map(|v| v.round().clamp(0.0, 255.0) as u8);
+            let at = (y * size.0 as usize + x) * 4;
+            blend_pixel(&mut pixels[at..at + 4], rgb, a / 255.0, BlendMode::Normal);
+        }
+    }
+}
+
+/// Fills the triangles of `mesh` into `pixels`, an RGBA8 image of `size`,
+/// painting each over what is already there. `to_pixel` maps world
+/// positions to pixels (origin top left, y down), e.g. through
+/// `Camera::world_to_screen`. Vertex colors are interpolated across each
+/// triangle, and a pixel is covered when its center is.
+pub fn fill_mesh<F>(pixels: &mut [u8], size: (u32, u32), mesh: &Mesh, to_pixel: F)
+where
+    F: Fn([f32; 2]) -> [f32; 2],
+{
+    if pixels.len() != size.0 as usize * size.1 as usize * 4 {
+        return;
+    }
+    let screen: Vec<[f32; 2]> = mesh.vertices.iter().map(|v| to_pixel(v.position)).collect();
+    for triangle in mesh.indices.chunks_exact(3) {
+        let vertex = |k: usize| {
+            mesh.vertices
+                .get(triangle[k] as usize)
+                .map(|v| (screen[triangle[k] as usize], v.color))
+        };
+        if let (Some(a), Some(b), Some(c)) = (vertex(0), vertex(1), vertex(2)) {
+            fill_triangle(pixels, size, [a.0, b.0, c.0], [a.1, b.1, c.1]);
+        }
+    }
+}
+
+/// Composites the RGBA8 image `src` over `dst`, both the same size, with
+/// `blend` and the alpha of `src` scaled by `opacity`.
+pub fn composite(dst: &mut [u8], src: &[u8], blend: BlendMode, opacity: f32) {
+    for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        let alpha = f32::from(s[3]) / 255.0 * opacity;
+        blend_pixel(d, [s[0], s[1], s[2]], alpha, blend);
+    }
+}
+
+/// Renders `layer` alone over a transparent background: its fill, if it is
+/// the background layer, then its strokes. Hidden or not, for per-layer
+/// exports.
+pub fn render_layer<F>(layer: &Layer, size: (u32, u32), to_pixel: F) -> Vec<u8>
+where
+    F: Fn([f32; 2]) -> [f32; 2],
+{
+    let mut pixels = clear(size, layer.fill);
+    let mesh = outlined_strokes_to_mesh(&layer.strokes, None);
+    fill_mesh(&mut pixels, size, &mesh, to_pixel);
+    pixels
+}
+
+/// Renders the visible `layers`, bottom first, each composited with its
+/// opacity and blend mode, over `background` or over transparency. Layer
+/// fills are left to `background`, see `Document::background`.
+pub fn render_layers<F>(
+    layers: &[Layer],
+    size: (u32, u32),
+    background: Option<[u8; 3]>,
+    to_pixel: F,
+) -> Vec<u8>
+where
+    F: Fn([f32; 2]) -> [f32; 2],
+{
+    let mut image = clear(size, background);
+    for layer in layers.iter().filter(|layer| layer.visible) {
+        let mut pixels = clear(size, None);
+        let mesh = outlined_strokes_to_mesh(&layer.strokes, None);
+        fill_mesh(&mut pixels, size, &mesh, &to_pixel);
+        composite(&mut image, &pixels, layer.blend, layer.opacity);
+    }
+    image
+}
+
+/// Maps world positions on `page` to the pixels of an image of `size`
+/// framing it exactly, as exports do.
+pub fn page_to_pixel(page: &Page, size: (u32, u32)) -> impl Fn([f32; 2]) -> [f32; 2] {
+    let (min, max) = page.bounds();
+    let scale = [
+        size.0 as f32 / (max[0] - min[0]),
+        size.1 as f32 / (max[1] - min[1]),
+    ];
+    move |p| [(p[0] - min[0]) * scale[0], (max[1] - p[1]) * scale[1]]
+}
+
+/// Renders the page of `doc` into an image of `size` pixels. Sizes of
+/// another aspect ratio than the page stretch it, see `Letterbox` to keep
+/// its shape. With `transparent` set the background is left out, so pixels
+/// without strokes have an alpha of 0.
+pub fn render_document(doc: &Document, size: (u32, u32), transparent: bool) -> Vec<u8> {
+    render_layers(
+        &doc.layers,
+        size,
+        doc.background(transparent),
+        page_to_pixel(&doc.page, size),
+    )
+}
+
+/// Square preview of `strokes`, `size` pixels a side, fit to their content
+/// with `Camera::fit` over a transparent background, for file listings.
+pub fn render_thumbnail(strokes: &[Stroke], size: u32) -> Vec<u8> {
+    let size = (size, size);
+    let mut pixels = clear(size, None);
+    if let Some(bounds) = stroke::bounds(strokes) {
+        let mut camera = Camera::fit(bounds, THUMBNAIL_MARGIN);
+        camera.set_viewport(size);
+        let mesh = outlined_strokes_to_mesh(strokes, None);
+        fill_mesh(&mut pixels, size, &mesh, |p| {
+            camera.world_to_screen(WorldPos(p), size).0
+        });
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex;
+
+    fn quad(min: [f32; 2], max: [f32; 2], color: [u8; 4]) -> Mesh {
+        let corners = [min, [max[0], min[1]], max, [min[0], max[1]]];
+        Mesh {
+            vertices: corners
+                .iter()
+                .map(|&position| Vertex { position, color })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let at = ((y * width + x) * 4) as usize;
+        [pixels[at], pixels[at + 1], pixels[at + 2], pixels[at + 3]]
+    }
+
+    #[test]
+    fn triangles_cover_the_pixels_whose_centers_they_contain() {
+        let mut pixels = clear((4, 4), None);
+        let mesh = quad([1.0, 1.0], [3.0, 3.0], [255, 0, 0, 255]);
+        fill_mesh(&mut pixels, (4, 4), &mesh, |p| p);
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                let expected = if inside { [255, 0, 0, 255] } else { [0; 4] };
+                assert_eq!(pixel(&pixels, 4, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        // The diagonal of the quad runs through pixel centers; painting
+        // them twice would make them darker than the rest.
+        let mut pixels = clear((4, 4), Some([255, 255, 255]));
+        let mesh = quad([0.0, 0.0], [4.0, 4.0], [0, 0, 0, 128]);
+        fill_mesh(&mut pixels, (4, 4), &mesh, |p| [p[0] + 0.5, p[1] + 0.5]);
+        let inner = pixel(&pixels, 4, 1, 2);
+        assert_eq!(pixel(&pixels, 4, 1, 1), inner);
+        assert_eq!(pixel(&pixels, 4, 2, 2), inner);
+    }
+
+    #[test]
+    fn layers_keep_the_background_transparent_where_nothing_is_drawn() {
+        let mut layer = Layer::new("Ink");
+        let mut stroke = crate::stroke::Stroke::new([0, 0, 255], 0.5);
+        stroke.points = vec![[-0.5, 0.0], [0.5, 0.0]];
+        layer.strokes.push(stroke);
+        let pixels = render_layers(&[layer], (8, 8), None, |p| {
+            [(p[0] + 1.0) * 4.0, (1.0 - p[1]) * 4.0]
+        });
+        assert_eq!(pixel(&pixels, 8, 0, 0)[3], 0);
+        assert_eq!(pixel(&pixels, 8, 4, 4), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn layer_opacity_and_blend_mode_apply_over_the_background() {
+        let mut layer = Layer::new("Shade");
+        layer.blend = BlendMode::Multiply;
+        layer.opacity = 0.5;
+        let mut under = clear((1, 1), Some([255, 255, 255]));
+        let mut black = clear((1, 1), None);
+        fill_mesh(
+            &mut black,
+            (1, 1),
+            &quad([0.0, 0.0], [1.0, 1.0], [0, 0, 0, 255]),
+            |p| p,
+        );
+        composite(&mut under, &black, layer.blend, layer.opacity);
+        let expected = from_linear([0.5; 3]);
+        assert_eq!(
+            pixel(&under, 1, 0, 0),
+            [expected[0], expected[1], expected[2], 255]
+        );
+    }
+
+    #[test]
+    fn paint_over_transparency_keeps_its_color_and_alpha() {
+        let mut pixels = clear((1, 1), None);
+        let mesh = quad([0.0, 0.0], [1.0, 1.0], [10, 200, 30, 128]);
+        fill_mesh(&mut pixels, (1, 1), &mesh, |p| p);
+        assert_eq!(pixel(&pixels, 1, 0, 0), [10, 200, 30, 128]);
+    }
+
+    #[test]
+    fn documents_render_their_page_with_the_background() {
+        let mut doc = Document::default();
+        doc.set_background([255, 255, 255]);
+        let size = (doc.page.width / 10, doc.page.height / 10);
+        let mut stroke = crate::stroke::Stroke::new([0, 0, 0], 0.2);
+        stroke.points = vec![[-1.0, 0.0], [1.0, 0.0]];
+        doc.layers[1].strokes.push(stroke);
+        let pixels = render_document(&doc, size, false);
+        assert_eq!(pixels.len(), (size.0 * size.1 * 4) as usize);
+        assert_eq!(pixel(&pixels, size.0, 0, 0), [255, 255, 255, 255]);
+        assert_eq!(
+            pixel(&pixels, size.0, size.0 / 2, size.1 / 2),
+            [0, 0, 0, 255]
+        );
+
+        let transparent = render_document(&doc, size, true);
+        assert_eq!(pixel(&transparent, size.0, 0, 0), [0; 4]);
+        assert_eq!(
+            pixel(&transparent, size.0, size.0 / 2, size.1 / 2),
+            [0, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn thumbnails_fit_the_content() {
+        let mut stroke = Stroke::new([0, 0, 0], 2.0);
+        stroke.points = vec![[10.0, 5.0], [30.0, 5.0]];
+        let pixels = render_thumbnail(&[stroke], 20);
+        assert_eq!(pixels.len(), 20 * 20 * 4);
+        // The stroke spans the width, less the margin, across the middle.
+        for x in 1..19 {
+            assert_eq!(pixel(&pixels, 20, x, 10)[3], 255, "x = {}", x);
+        }
+        assert_eq!(pixel(&pixels, 20, 10, 0)[3], 0);
+        assert_eq!(pixel(&pixels, 20, 10, 19)[3], 0);
+
+        assert!(render_thumbnail(&[], 4).iter().all(|&c| c == 0));
+    }
+}
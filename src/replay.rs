@@ -0,0 +1,108 @@
+//! Playback of a drawing stroke by stroke, as it was drawn.
+//!
+//! Strokes are laid out one after another on a single timeline in the order
+//! they were created, using their point timestamps; strokes without
+//! timestamps play at a fixed rate. What is shown is still drawn in render
+//! order.
+
+use crate::stroke::{creation_order, Stroke};
+
+/// Pause inserted between consecutive strokes, in seconds.
+pub const STROKE_GAP: f32 = 0.2;
+/// Playback time of each point of a stroke without timestamps, in seconds.
+pub const UNTIMED_POINT_DURATION: f32 = 1.0 / 120.0;
+/// Replay time moved per scroll unit while scrubbing, in seconds.
+pub const SCRUB_STEP: f32 = 0.25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// Current position on the timeline, in seconds.
+    pub time: f32,
+    pub playing: bool,
+    /// Start time of each stroke on the timeline, by list index.
+    starts: Vec<f32>,
+    duration: f32,
+}
+
+/// Time of each point of `stroke` relative to its first point.
+fn point_times(stroke: &Stroke) -> Vec<f32> {
+    if stroke.has_timestamps() {
+        let first = stroke.timestamps[0];
+        stroke.timestamps.iter().map(|t| t - first).collect()
+    } else {
+        (0..stroke.points.len())
+            .map(|i| i as f32 * UNTIMED_POINT_DURATION)
+            .collect()
+    }
+}
+
+impl Replay {
+    pub fn new(strokes: &[Stroke]) -> Self {
+        let mut starts = vec![0.0; strokes.len()];
+        let mut time = 0.0;
+        for i in creation_order(strokes) {
+            starts[i] = time;
+            time += point_times(&strokes[i]).last().copied().unwrap_or(0.0) + STROKE_GAP;
+        }
+        Self {
+            time: 0.0,
+            playing: true,
+            starts,
+            duration: time,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.duration
+    }
+
+    /// Plays forward by `dt` seconds, when playing.
+    pub fn advance(&mut self, dt: f32) {
+        if self.playing {
+            self.seek(self.time + dt);
+        }
+    }
+
+    /// Moves the replay position by `scroll` wheel units, forward for
+    /// positive values, pausing playback so the user can inspect the moment.
+    pub fn scrub(&mut self, scroll: f32) {
+        self.playing = false;
+        self.seek(self.time + scroll * SCRUB_STEP);
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration);
+    }
+
+    /// The strokes as they looked at the current time: strokes not started
+    /// yet are left out, the one being drawn is cut at the current point.
+    pub fn visible(&self, strokes: &[Stroke]) -> Vec<Stroke> {
+        let mut visible = Vec::new();
+        for (stroke, &start) in strokes.iter().zip(&self.starts) {
+            if start > self.time {
+                continue;
+            }
+            let elapsed = self.time - start;
+            let shown = point_times(stroke)
+                .iter()
+                .take_while(|&&t| t <= elapsed)
+                .count();
+            if shown == stroke.points.len() {
+                visible.push(stroke.clone());
+            } else {
+                let mut partial = stroke.clone();
+                partial.points.truncate(shown);
+                partial.timestamps.truncate(shown);
+                partial.point_colors.truncate(shown);
+                partial.path = None;
+                partial.invalidate_bbox();
+                visible.push(partial);
+            }
+        }
+        visible
+    }
+}
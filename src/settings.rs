@@ -0,0 +1,161 @@
+//! Working environment saved with a document: brush, per-tool settings,
+//! palette, grid and guides.
+//!
+//! Every field is optional. When a document is opened, each setting comes
+//! from the document if it has one, else from the global config, else from
+//! the built-in default.
+
+use serde::{Deserialize, Serialize};
+
+use crate::align::Guides;
+use crate::grid::Grid;
+use crate::tool::{ToolBox, ToolSettings};
+
+/// Colors offered when neither the document nor the config has a palette.
+pub const DEFAULT_PALETTE: [[u8; 3]; 8] = [
+    [0, 0, 0],
+    [255, 255, 255],
+    [220, 50, 47],
+    [133, 153, 0],
+    [38, 139, 210],
+    [181, 137, 0],
+    [211, 54, 130],
+    [42, 161, 152],
+];
+
+/// Settings layered over one another, as stored in a document or in the
+/// global config file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brush: Option<ToolSettings>,
+    /// The active tool and the brush of each tool. Older files only have
+    /// `brush`, which then becomes the settings of the active tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<ToolBox>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Vec<[u8; 3]>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<Grid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guides: Option<Guides>,
+}
+
+/// Settings with every field filled in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub brush: ToolSettings,
+    pub tools: ToolBox,
+    pub palette: Vec<[u8; 3]>,
+    pub grid: Grid,
+    pub guides: Guides,
+}
+
+impl Settings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// These settings where set, `fallback` elsewhere.
+    pub fn or(&self, fallback: &Settings) -> Settings {
+        Settings {
+            brush: self.brush.or(fallback.brush),
+            tools: self.tools.clone().or_else(|| fallback.tools.clone()),
+            palette: self.palette.clone().or_else(|| fallback.palette.clone()),
+            grid: self.grid.or(fallback.grid),
+            guides: self.guides.clone().or_else(|| fallback.guides.clone()),
+        }
+    }
+
+    /// Applies the precedence document (`self`) > `config` > defaults.
+    pub fn resolve(&self, config: &Settings) -> Resolved {
+        let merged = self.or(config);
+        let brush = merged.brush.unwrap_or_default();
+        let tools = merged.tools.unwrap_or_else(|| {
+            let mut tools = ToolBox::default();
+            tools.set_settings_for(tools.active(), brush);
+            tools
+        });
+        Resolved {
+            brush,
+            tools,
+            palette: merged.palette.unwrap_or_else(|| DEFAULT_PALETTE.to_vec()),
+            grid: merged.grid.unwrap_or_default(),
+            guides: merged.guides.unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use crate::tool::Tool;
+
+    fn toolbox() -> ToolBox {
+        let mut tools = ToolBox::new(Tool::Line);
+        tools.set_settings_for(
+            Tool::Freehand,
+            ToolSettings {
+                width: 8.0,
+                ..ToolSettings::default()
+            },
+        );
+        tools.settings_mut().color = [220, 50, 47];
+        tools
+    }
+
+    #[test]
+    fn configs_without_tools_still_load() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"brush":{"width":5.0,"color":[1,2,3]}}"#).unwrap();
+        assert!(settings.tools.is_none());
+        let resolved = settings.resolve(&Settings::default());
+        assert_eq!(resolved.brush.width, 5.0);
+        assert_eq!(resolved.tools.active(), Tool::Freehand);
+        assert_eq!(resolved.tools.settings(), resolved.brush);
+    }
+
+    #[test]
+    fn tool_settings_round_trip() {
+        let settings = Settings {
+            tools: Some(toolbox()),
+            ..Settings::default()
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains(r#""active":"Line""#));
+        let loaded: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, settings);
+        let tools = loaded.tools.unwrap();
+        assert_eq!(tools.settings().color, [220, 50, 47]);
+        assert_eq!(tools.settings_for(Tool::Freehand).width, 8.0);
+        assert_eq!(tools.settings_for(Tool::Eraser), ToolSettings::default());
+    }
+
+    #[test]
+    fn documents_keep_their_tools() {
+        let mut doc = Document::default();
+        doc.settings.tools = Some(toolbox());
+        let loaded = Document::from_binary(&doc.to_binary().unwrap()).unwrap();
+        assert_eq!(loaded.settings, doc.settings);
+        let loaded = Document::from_json(&doc.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.settings, doc.settings);
+    }
+
+    #[test]
+    fn document_tools_override_the_config() {
+        let document = Settings {
+            tools: Some(toolbox()),
+            ..Settings::default()
+        };
+        let config = Settings {
+            tools: Some(ToolBox::new(Tool::Eraser)),
+            ..Settings::default()
+        };
+        assert_eq!(document.resolve(&config).tools, toolbox());
+        assert_eq!(
+            Settings::default().resolve(&config).tools.active(),
+            Tool::Eraser
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{
+    adaptive_smooth, adaptive_smooth_segment, catmull_rom_point, detect_corners, quad_bezier,
+    relax, MAX_RELAX_PASSES,
+};
+use crate::stroke::Stroke;
+
+/// Samples per input segment of the fixed-rate interpolation methods.
+const SAMPLES_PER_SEGMENT: usize = 8;
+
+/// How a finished stroke's captured points are turned into its final curve.
+/// Every method is deterministic, so the same input always gives the same
+/// curve.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// The raw points, as captured.
+    None,
+    /// A uniform Catmull-Rom spline through every point, at a fixed number of
+    /// samples per segment.
+    CatmullRom,
+    /// Quadratic Béziers between segment midpoints using the points as
+    /// controls; smoother than Catmull-Rom but does not pass through them.
+    Bezier,
+    /// Catmull-Rom subdivided until within `tolerance` of the true curve.
+    #[default]
+    Adaptive,
+}
+
+impl Interpolation {
+    /// Runs `passes` rounds of `relax` over `points`, then the method itself.
+    pub fn apply(self, points: &[[f32; 2]], tolerance: f32, passes: u32) -> Vec<[f32; 2]> {
+        if points.len() < 3 {
+            return points.to_vec();
+        }
+        let relaxed;
+        let points = if passes > 0 {
+            relaxed = relax(points, passes);
+            &relaxed
+        } else {
+            points
+        };
+        match self {
+            Interpolation::None => points.to_vec(),
+            Interpolation::CatmullRom => catmull_rom(points),
+            Interpolation::Bezier => midpoint_bezier(points),
+            Interpolation::Adaptive => adaptive_smooth(points, tolerance),
+        }
+    }
+
+    /// Like `apply`, but keeps the corners found by `detect_corners` at
+    /// `corner_angle` degrees sharp: the runs between them are smoothed on
+    /// their own and meet at the corner point itself. Corners are found on
+    /// the points before relaxing, and stay put since `relax` keeps the ends
+    /// of each run.
+    pub fn apply_keeping_corners(
+        self,
+        points: &[[f32; 2]],
+        tolerance: f32,
+        corner_angle: f32,
+        passes: u32,
+    ) -> Vec<[f32; 2]> {
+        let corners = detect_corners(points, corner_angle);
+        if corners.is_empty() {
+            return self.apply(points, tolerance, passes);
+        }
+        let mut out = Vec::new();
+        let mut start = 0;
+        for end in corners.into_iter().chain(std::iter::once(points.len() - 1)) {
+            let run = self.apply(&points[start..=end], tolerance, passes);
+            // Each run starts on the corner the previous one ended on.
+            let skip = if out.is_empty() { 0 } else { 1 };
+            out.extend(run.into_iter().skip(skip));
+            start = end;
+        }
+        out
+    }
+}
+
+fn catmull_rom(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let last = points.len() - 1;
+    let mut out = vec![points[0]];
+    for i in 0..last {
+        let p0 = points[i.saturating_sub(1)];
+        let p3 = points[(i + 2).min(last)];
+        for s in 1..=SAMPLES_PER_SEGMENT {
+            let t = s as f32 / SAMPLES_PER_SEGMENT as f32;
+            out.push(catmull_rom_point(p0, points[i], points[i + 1], p3, t));
+        }
+    }
+    out
+}
+
+fn midpoint_bezier(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let mid = |a: [f32; 2], b: [f32; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+    let last = points.len() - 1;
+    let mut out = vec![points[0]];
+    for i in 1..last {
+        let start = if i == 1 {
+            points[0]
+        } else {
+            mid(points[i - 1], points[i])
+        };
+        let end = if i + 1 == last {
+            points[last]
+        } else {
+            mid(points[i], points[i + 1])
+        };
+        out.extend(
+            quad_bezier(start, points[i], end, SAMPLES_PER_SEGMENT)
+                .into_iter()
+                .skip(1),
+        );
+    }
+    out
+}
+
+/// Live smoothed view of the stroke being drawn. Since points are only ever
+/// appended while drawing, the smoothed output of all but the last couple of
+/// segments is final and kept between updates; only the tail is recomputed.
+/// With relaxation passes enabled, every point may still move, so the whole
+/// preview is recomputed on each update instead.
+#[derive(Debug, Clone)]
+pub struct SmoothPreview {
+    /// Shows the raw points instead, to compare with the smoothed result.
+    pub enabled: bool,
+    pub tolerance: f32,
+    /// Rounds of `relax` applied before the spline, for stronger smoothing.
+    passes: u32,
+    smoothed: Vec<[f32; 2]>,
+    /// Number of leading segments whose samples are final.
+    stable_segments: usize,
+    /// Length of `smoothed` covering exactly the stable segments.
+    stable_len: usize,
+}
+
+impl SmoothPreview {
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            enabled: true,
+            tolerance,
+            passes: 0,
+            smoothed: Vec::new(),
+            stable_segments: 0,
+            stable_len: 0,
+        }
+    }
+
+    /// Forgets the cached curve, e.g. when a new stroke starts or the
+    /// tolerance changes.
+    pub fn reset(&mut self) {
+        self.smoothed.clear();
+        self.stable_segments = 0;
+        self.stable_len = 0;
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Sets the number of relaxation passes, clamped to `MAX_RELAX_PASSES`.
+    pub fn set_passes(&mut self, passes: u32) {
+        self.passes = passes.min(MAX_RELAX_PASSES);
+        self.reset();
+    }
+
+    pub fn increase_passes(&mut self) {
+        self.set_passes(self.passes + 1);
+    }
+
+    pub fn decrease_passes(&mut self) {
+        self.set_passes(self.passes.saturating_sub(1));
+    }
+
+    /// Bakes the relaxation passes into the finished `stroke`, so that it
+    /// keeps the shape the preview showed, and forgets the cached curve.
+    pub fn finish(&mut self, stroke: &mut Stroke) {
+        stroke.relax(self.passes);
+        self.reset();
+    }
+
+    /// Returns the preview for the in-progress stroke `points`, which must
+    /// extend the points of the previous call.
+    pub fn update<'a>(&'a mut self, points: &'a [[f32; 2]]) -> &'a [[f32; 2]] {
+        if !self.enabled || points.len() < 3 {
+            self.reset();
+            return points;
+        }
+
+        if self.passes > 0 {
+            self.smoothed = Interpolation::Adaptive.apply(points, self.tolerance, self.passes);
+            return &self.smoothed;
+        }
+
+        if self.smoothed.is_empty() {
+            self.smoothed.push(points[0]);
+            self.stable_len = 1;
+        }
+        self.smoothed.truncate(self.stable_len);
+
+        // Segment i reads points up to i + 2, so it is final once that point
+        // exists.
+        let segments = points.len() - 1;
+        let final_segments = points.len() - 2;
+        for i in self.stable_segments..segments {
+            adaptive_smooth_segment(points, i, self.tolerance, &mut self.smoothed);
+            if i < final_segments {
+                self.stable_segments = i + 1;
+                self.stable_len = self.smoothed.len();
+            }
+        }
+        &self.smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn zigzag() -> Vec<[f32; 2]> {
+        (0..12)
+            .map(|i| [i as f32 * 0.1, if i % 2 == 0 { 0.0 } else { 0.05 }])
+            .collect()
+    }
+
+    fn wiggle(points: &[[f32; 2]]) -> f32 {
+        points.windows(2).map(|s| (s[1][1] - s[0][1]).abs()).sum()
+    }
+
+    #[test]
+    fn passes_relax_before_the_method() {
+        let points = zigzag();
+        assert_eq!(Interpolation::None.apply(&points, 0.001, 0), points);
+        let relaxed = Interpolation::None.apply(&points, 0.001, 3);
+        assert_eq!(relaxed, relax(&points, 3));
+        assert!(wiggle(&relaxed) < wiggle(&points) / 2.0);
+        assert_eq!(
+            Interpolation::Adaptive.apply(&points, 0.001, 3),
+            adaptive_smooth(&relax(&points, 3), 0.001)
+        );
+    }
+
+    #[test]
+    fn passes_keep_corners_in_place() {
+        let mut points: Vec<[f32; 2]> = (0..6).map(|i| [i as f32 * 0.1, 0.0]).collect();
+        points.extend((1..6).map(|i| [0.5, i as f32 * 0.1]));
+        let out = Interpolation::None.apply_keeping_corners(&points, 0.001, 60.0, 4);
+        assert!(out.contains(&[0.5, 0.0]));
+        assert_eq!(out[0], points[0]);
+        assert_eq!(out.last(), points.last());
+    }
+
+    #[test]
+    fn finishing_keeps_the_previewed_shape() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        for (i, &p) in zigzag().iter().enumerate() {
+            stroke.push(p, Duration::from_millis(i as u64));
+        }
+        let mut preview = SmoothPreview::new(0.001);
+        preview.set_passes(3);
+        let shown = preview.update(&stroke.points).to_vec();
+
+        preview.finish(&mut stroke);
+        assert_eq!(stroke.points.len(), stroke.timestamps.len());
+        assert_eq!(stroke.points[0], [0.0, 0.0]);
+        assert_eq!(stroke.points, relax(&zigzag(), 3));
+        assert_eq!(
+            Interpolation::Adaptive.apply(&stroke.points, 0.001, 0),
+            shown
+        );
+    }
+
+    #[test]
+    fn finishing_without_passes_leaves_the_stroke_alone() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        for (i, &p) in zigzag().iter().enumerate() {
+            stroke.push(p, Duration::from_millis(i as u64));
+        }
+        SmoothPreview::new(0.001).finish(&mut stroke);
+        assert_eq!(stroke.points, zigzag());
+    }
+
+    #[test]
+    fn passes_are_clamped() {
+        let mut preview = SmoothPreview::new(0.001);
+        preview.set_passes(100);
+        assert_eq!(preview.passes(), MAX_RELAX_PASSES);
+        preview.increase_passes();
+        assert_eq!(preview.passes(), MAX_RELAX_PASSES);
+        preview.set_passes(0);
+        preview.decrease_passes();
+        assert_eq!(preview.passes(), 0);
+    }
+}
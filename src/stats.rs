@@ -0,0 +1,105 @@
+//! Drawing statistics for the stats panel, kept up to date while drawing.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::geometry;
+use crate::layer::Layer;
+use crate::stroke::Stroke;
+use crate::width::arc_lengths;
+
+/// Totals over every stroke of a document.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DrawingStats {
+    pub strokes: usize,
+    pub points: usize,
+    /// Summed arc length of the strokes, in world units.
+    pub length: f32,
+    /// Bounding box of all strokes as drawn, decorations included.
+    pub bounds: Option<([f32; 2], [f32; 2])>,
+}
+
+impl DrawingStats {
+    /// Width and height of `bounds`, zero without strokes.
+    pub fn size(&self) -> [f32; 2] {
+        self.bounds
+            .map_or([0.0, 0.0], |(min, max)| [max[0] - min[0], max[1] - min[1]])
+    }
+
+    /// One HUD line per statistic.
+    pub fn lines(&self) -> Vec<String> {
+        let [w, h] = self.size();
+        vec![
+            format!("Strokes: {}", self.strokes),
+            format!("Points: {}", self.points),
+            format!("Length: {:.3}", self.length),
+            format!("Size: {:.3} \u{d7} {:.3}", w, h),
+        ]
+    }
+}
+
+impl fmt::Display for DrawingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lines().join(", "))
+    }
+}
+
+/// Arc length of a stroke, with what it was computed from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CachedLength {
+    points: usize,
+    last: [f32; 2],
+    length: f32,
+}
+
+/// Toggleable stats panel. Stroke lengths are cached by stroke ID and only
+/// recomputed when a stroke's point count or last point changes, so the
+/// live stroke is the only one measured again each frame.
+#[derive(Debug, Clone, Default)]
+pub struct StatsPanel {
+    pub visible: bool,
+    lengths: HashMap<u64, CachedLength>,
+}
+
+impl StatsPanel {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Statistics of every stroke in `layers`, plus the stroke being drawn,
+    /// if any. Returns `None` while the panel is hidden.
+    pub fn update(&mut self, layers: &[Layer], live: Option<&Stroke>) -> Option<DrawingStats> {
+        if !self.visible {
+            return None;
+        }
+        let mut stats = DrawingStats::default();
+        let mut seen = HashMap::with_capacity(self.lengths.len());
+        let strokes = layers.iter().flat_map(|l| &l.strokes).chain(live);
+        for stroke in strokes {
+            let last = match stroke.points.last() {
+                Some(&last) => last,
+                None => continue,
+            };
+            let cached = self
+                .lengths
+                .get(&stroke.id)
+                .copied()
+                .filter(|c| c.points == stroke.points.len() && c.last == last)
+                .unwrap_or_else(|| CachedLength {
+                    points: stroke.points.len(),
+                    last,
+                    length: arc_lengths(&stroke.points).last().copied().unwrap_or(0.0),
+                });
+            seen.insert(stroke.id, cached);
+            stats.strokes += 1;
+            stats.points += stroke.points.len();
+            stats.length += cached.length;
+            if let Some(b) = stroke.bounds() {
+                stats.bounds = Some(stats.bounds.map_or(b, |acc| geometry::union(acc, b)));
+            }
+        }
+        // Drops the entries of deleted strokes.
+        self.lengths = seen;
+        Some(stats)
+    }
+}
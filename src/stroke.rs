@@ -0,0 +1,1464 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::geometry::{self, simplify_indices};
+use crate::jitter::Jitter;
+use crate::path::{BezierPath, PATH_SAMPLES};
+use crate::width::WidthProfile;
+
+/// Default cap on the number of points of a single stroke.
+pub const DEFAULT_MAX_POINTS: usize = 100_000;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A new stroke ID, unique within the session.
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Makes sure IDs handed out from now on do not collide with those of
+/// `strokes`, e.g. after loading a file, and that new strokes count as
+/// created after them. Returns false, reserving nothing, when an ID or
+/// creation index is `u64::MAX`, as no ID could follow it.
+pub fn reserve_ids<'a, I: IntoIterator<Item = &'a Stroke>>(strokes: I) -> bool {
+    let max = match strokes.into_iter().map(|s| s.id.max(s.created)).max() {
+        Some(max) => max,
+        None => return true,
+    };
+    match max.checked_add(1) {
+        Some(next) => {
+            NEXT_ID.fetch_max(next, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// How a stroke's points are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum StrokeStyle {
+    /// A continuous line through the points.
+    #[default]
+    Line,
+    /// A square dot of side `size` (world units) at every point, for
+    /// stippling and dotted effects.
+    Points { size: f32 },
+}
+
+impl StrokeStyle {
+    fn is_line(&self) -> bool {
+        *self == StrokeStyle::Line
+    }
+}
+
+/// Which ends of a stroke get an arrowhead pointing along the stroke.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Arrowheads {
+    #[default]
+    None,
+    End,
+    Both,
+}
+
+impl Arrowheads {
+    fn is_none(&self) -> bool {
+        *self == Arrowheads::None
+    }
+}
+
+/// Arrowhead length relative to the stroke width.
+pub const ARROW_SCALE: f32 = 4.0;
+
+/// Shape of the outer corner where two segments of a line stroke meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JoinStyle {
+    /// Rounded by an arc around the point.
+    Round,
+    /// Extended to a sharp point, cut to a bevel past the miter limit.
+    Miter,
+    /// Cut straight across.
+    Bevel,
+}
+
+/// Longest miter, relative to the half-width, before a miter join is
+/// beveled instead; the SVG default, cutting corners sharper than about 29°.
+pub const MITER_LIMIT: f32 = 4.0;
+
+/// Contrasting halo drawn behind a stroke, such as white around a black
+/// line, to keep it readable over busy backgrounds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Outline {
+    /// How far the halo extends past each side of the stroke.
+    pub width: f32,
+    pub color: [u8; 3],
+}
+
+/// Cached bounding box of a stroke's points, without its width or
+/// decorations. It is not serialized and never takes part in comparisons,
+/// so a stroke with a filled cache still equals its copy without one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BboxCache(Option<([f32; 2], [f32; 2])>);
+
+impl PartialEq for BboxCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// A committed stroke. Strokes are rendered in list order, so the last stroke
+/// of a list is drawn on top.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+    /// Stable identity of the stroke, unaffected by reordering. Strokes from
+    /// files saved before IDs existed get a fresh one on load.
+    #[serde(default = "next_id")]
+    pub id: u64,
+    /// Position of the stroke in the order strokes were drawn. The stroke
+    /// list itself is in render order, which reordering changes; this keeps
+    /// the drawing history for replay. Files from before it existed load
+    /// with zero everywhere and replay in list order.
+    #[serde(default)]
+    pub created: u64,
+    pub points: Vec<[f32; 2]>,
+    /// Seconds elapsed since the start of the stroke for each point. Empty for
+    /// strokes created without timing information, e.g. from older files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timestamps: Vec<f32>,
+    pub color: [u8; 3],
+    /// Color of each point, for strokes whose color varies along their
+    /// length. Empty when the whole stroke uses `color`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub point_colors: Vec<[u8; 3]>,
+    pub width: f32,
+    /// How the width varies along the stroke, e.g. tapering at the ends.
+    #[serde(default, skip_serializing_if = "WidthProfile::is_constant")]
+    pub profile: WidthProfile,
+    #[serde(default, skip_serializing_if = "StrokeStyle::is_line")]
+    pub style: StrokeStyle,
+    /// Bézier path of strokes drawn with the pen tool, kept so their nodes
+    /// stay editable. `points` is the path flattened, and the mesher draws
+    /// the path itself. Edits that move individual points, such as
+    /// extending the stroke, turn it into a plain stroke.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<BezierPath>,
+    #[serde(default, skip_serializing_if = "Arrowheads::is_none")]
+    pub arrows: Arrowheads,
+    #[serde(default, skip_serializing_if = "Jitter::is_off")]
+    pub jitter: Jitter,
+    /// Corner shape, or `None` for the mesher's default of averaging the
+    /// normals of adjacent segments, which suits dense freehand points.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_style: Option<JoinStyle>,
+    /// Edge softness as a fraction of the width: 0 is crisp, and at
+    /// `MAX_FEATHER` the stroke fades out from its center line.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub feather: f32,
+    /// Halo of this stroke, over any global outline setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outline: Option<Outline>,
+    /// Free-form labels such as "construction" or "final", used to group
+    /// and filter strokes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Locked strokes cannot be selected or erased.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub locked: bool,
+    /// Filled by `bbox` and cleared by the methods that move points. Code
+    /// changing `points` directly must call `invalidate_bbox`.
+    #[serde(skip)]
+    pub bbox: BboxCache,
+}
+
+pub(crate) fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &f32) -> bool {
+    *value == 0.0
+}
+
+/// Largest `Stroke::feather`.
+pub const MAX_FEATHER: f32 = 1.0;
+
+impl Stroke {
+    pub fn new(color: [u8; 3], width: f32) -> Self {
+        let id = next_id();
+        Self {
+            id,
+            created: id,
+            points: Vec::new(),
+            timestamps: Vec::new(),
+            color,
+            point_colors: Vec::new(),
+            width,
+            profile: WidthProfile::default(),
+            style: StrokeStyle::Line,
+            path: None,
+            arrows: Arrowheads::None,
+            jitter: Jitter::default(),
+            join_style: None,
+            feather: 0.0,
+            outline: None,
+            tags: Vec::new(),
+            locked: false,
+            bbox: BboxCache::default(),
+        }
+    }
+
+    /// Appends a point captured `elapsed` after the stroke started. A point
+    /// identical to the current last one is skipped, as the zero-length
+    /// segment it would create has no direction to expand the width along.
+    /// Returns whether the point was added.
+    pub fn push(&mut self, point: [f32; 2], elapsed: Duration) -> bool {
+        if self.points.last() == Some(&point) {
+            return false;
+        }
+        self.path = None;
+        if self.has_point_colors() {
+            self.point_colors.push(self.color);
+        }
+        self.push_point(point, elapsed);
+        true
+    }
+
+    /// Like `push`, giving the point its own color. Points added before
+    /// without one get the stroke color.
+    pub fn push_colored(&mut self, point: [f32; 2], elapsed: Duration, color: [u8; 3]) -> bool {
+        if self.points.last() == Some(&point) {
+            return false;
+        }
+        self.path = None;
+        self.point_colors.resize(self.points.len(), self.color);
+        self.point_colors.push(color);
+        self.push_point(point, elapsed);
+        true
+    }
+
+    /// Appends `point`, with its timestamp only if every earlier point has
+    /// one: a stroke without timing, e.g. imported from SVG, stays without
+    /// it rather than getting timestamps that belong to the wrong points.
+    fn push_point(&mut self, point: [f32; 2], elapsed: Duration) {
+        if self.timestamps.len() == self.points.len() {
+            self.timestamps.push(elapsed.as_secs_f32());
+        }
+        self.invalidate_bbox();
+        self.points.push(point);
+    }
+
+    /// A stroke following `path`.
+    pub fn from_path(path: BezierPath, color: [u8; 3], width: f32) -> Self {
+        let mut stroke = Self::new(color, width);
+        stroke.path = Some(path);
+        stroke.flatten_path();
+        stroke
+    }
+
+    /// Moves node `index` of the stroke's path (see `BezierPath::nodes`) to
+    /// `pos`. Returns false if the stroke has no such node.
+    pub fn move_path_node(&mut self, index: usize, pos: [f32; 2]) -> bool {
+        match self.path.as_mut().and_then(|path| path.node_mut(index)) {
+            Some(node) => *node = pos,
+            None => return false,
+        }
+        self.flatten_path();
+        true
+    }
+
+    /// Replaces the points by the flattened path. Per-point times and
+    /// colors no longer match and are dropped.
+    fn flatten_path(&mut self) {
+        if let Some(path) = &self.path {
+            self.points = path.to_polyline(PATH_SAMPLES);
+            self.timestamps.clear();
+            self.point_colors.clear();
+            self.invalidate_bbox();
+        }
+    }
+
+    /// Width of the soft edge, with `feather` clamped to its valid range.
+    pub fn feather_width(&self) -> f32 {
+        self.width * self.feather.clamp(0.0, MAX_FEATHER)
+    }
+
+    pub fn has_timestamps(&self) -> bool {
+        !self.points.is_empty() && self.timestamps.len() == self.points.len()
+    }
+
+    pub fn has_point_colors(&self) -> bool {
+        !self.points.is_empty() && self.point_colors.len() == self.points.len()
+    }
+
+    /// Color of the point at `index`.
+    pub fn color_at(&self, index: usize) -> [u8; 3] {
+        self.point_colors.get(index).copied().unwrap_or(self.color)
+    }
+
+    /// Simplifies the points in `range` with Douglas-Peucker, keeping the
+    /// timestamps and colors of the remaining points. Strokes with a path
+    /// are left alone, as their points follow the path.
+    pub fn simplify_range(&mut self, range: std::ops::Range<usize>, epsilon: f32) {
+        if self.path.is_some() {
+            return;
+        }
+        let range = range.start.min(self.points.len())..range.end.min(self.points.len());
+        let kept: Vec<usize> = simplify_indices(&self.points[range.clone()], epsilon)
+            .into_iter()
+            .map(|i| i + range.start)
+            .collect();
+        if self.has_timestamps() {
+            let times: Vec<f32> = kept.iter().map(|&i| self.timestamps[i]).collect();
+            self.timestamps.splice(range.clone(), times);
+        }
+        if self.has_point_colors() {
+            let colors: Vec<[u8; 3]> = kept.iter().map(|&i| self.point_colors[i]).collect();
+            self.point_colors.splice(range.clone(), colors);
+        }
+        let points: Vec<[f32; 2]> = kept.iter().map(|&i| self.points[i]).collect();
+        self.points.splice(range, points);
+        self.invalidate_bbox();
+    }
+
+    pub fn simplify(&mut self, epsilon: f32) {
+        self.simplify_range(0..self.points.len(), epsilon);
+    }
+
+    /// Runs `passes` rounds of `geometry::relax` over the points. The point
+    /// count is unchanged, so timestamps and colors still line up. Strokes
+    /// with a path are left alone.
+    pub fn relax(&mut self, passes: u32) {
+        if passes == 0 || self.path.is_some() {
+            return;
+        }
+        self.points = geometry::relax(&self.points, passes);
+        self.invalidate_bbox();
+    }
+
+    /// Keeps the stroke under `max_points` by simplifying its oldest half,
+    /// starting at `epsilon` and relaxing the tolerance until the cap holds.
+    /// The most recent points are left untouched so that the live end of the
+    /// stroke stays precise.
+    pub fn enforce_point_cap(&mut self, max_points: usize, epsilon: f32) {
+        let mut epsilon = epsilon.max(f32::EPSILON);
+        for _ in 0..16 {
+            if self.points.len() <= max_points.max(2) {
+                return;
+            }
+            let oldest = self.points.len() / 2 + 1;
+            self.simplify_range(0..oldest, epsilon);
+            epsilon *= 2.0;
+        }
+    }
+
+    /// Bounding box of the stroke including everything drawn around its
+    /// points, as `(min, max)`. Uses the cached box of the points when
+    /// `bbox` has filled it; the width and decorations are always read
+    /// afresh, so changing them needs no invalidation.
+    pub fn bounds(&self) -> Option<([f32; 2], [f32; 2])> {
+        let (min, max) = match self.bbox.0 {
+            Some(cached) => cached,
+            None => geometry::bounds(&self.points)?,
+        };
+        let r = self.reach();
+        Some(([min[0] - r, min[1] - r], [max[0] + r, max[1] + r]))
+    }
+
+    /// How far the mesh may extend past the points: the largest of the
+    /// line's half width, feather or miter joins, the dots and the
+    /// arrowheads, plus the outline and the jitter scatter.
+    fn reach(&self) -> f32 {
+        let outline = self.outline.map_or(0.0, |o| o.width.max(0.0));
+        let body = match self.style {
+            // Feathered lines are meshed without joins.
+            StrokeStyle::Line if self.feather_width() > 0.0 => {
+                self.width / 2.0 + self.feather_width() / 2.0 + outline
+            }
+            // Miters grow with the outlined half width.
+            StrokeStyle::Line if self.join_style == Some(JoinStyle::Miter) => {
+                MITER_LIMIT * (self.width / 2.0 + outline)
+            }
+            StrokeStyle::Line => self.width / 2.0 + outline,
+            StrokeStyle::Points { size } => size.max(0.0) / 2.0 + outline,
+        };
+        // A head reaches up to its length past the end of the line. Outlines
+        // grow it about its incenter, which moves the tip sqrt(5) times as
+        // far as the edges.
+        let arrows = if self.arrows.is_none() {
+            0.0
+        } else {
+            self.width * ARROW_SCALE + outline * 5f32.sqrt()
+        };
+        body.max(arrows) + self.jitter.scatter.max(0.0)
+    }
+
+    /// Like `bounds`, caching the box of the points so that later calls to
+    /// either skip walking them until the points change.
+    pub fn bbox(&mut self) -> Option<([f32; 2], [f32; 2])> {
+        if self.bbox.0.is_none() {
+            self.bbox.0 = geometry::bounds(&self.points);
+        }
+        self.bounds()
+    }
+
+    pub fn invalidate_bbox(&mut self) {
+        self.bbox.0 = None;
+    }
+
+    /// Whether any part of the stroke passes inside the circle of `radius`
+    /// around `center`. Only touching the circle does not count, as in
+    /// `split_stroke`.
+    pub fn hit(&self, center: [f32; 2], radius: f32) -> bool {
+        match self.points.len() {
+            0 => false,
+            1 => geometry::distance(self.points[0], center) < radius,
+            _ => self
+                .points
+                .windows(2)
+                .any(|s| geometry::segment_distance(center, s[0], s[1]) < radius),
+        }
+    }
+
+    /// A stroke with the same style but no points.
+    pub fn empty_copy(&self) -> Self {
+        Self {
+            created: self.created,
+            profile: self.profile,
+            style: self.style,
+            arrows: self.arrows,
+            jitter: self.jitter,
+            join_style: self.join_style,
+            feather: self.feather,
+            outline: self.outline,
+            tags: self.tags.clone(),
+            ..Self::new(self.color, self.width)
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Adds `tag` unless the stroke already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|t| t != tag);
+    }
+
+    pub fn translate(&mut self, delta: [f32; 2]) {
+        for point in &mut self.points {
+            point[0] += delta[0];
+            point[1] += delta[1];
+        }
+        if let Some(path) = &mut self.path {
+            path.translate(delta);
+        }
+        self.invalidate_bbox();
+    }
+
+    /// Rotates the stroke counterclockwise by `angle` radians around
+    /// `center`.
+    pub fn rotate(&mut self, center: [f32; 2], angle: f32) {
+        for point in &mut self.points {
+            *point = geometry::rotate_point(*point, center, angle);
+        }
+        if let Some(path) = &mut self.path {
+            path.rotate(center, angle);
+        }
+        self.invalidate_bbox();
+    }
+}
+
+/// Total number of points of all `strokes`.
+pub fn point_count(strokes: &[Stroke]) -> usize {
+    strokes.iter().map(|s| s.points.len()).sum()
+}
+
+/// Stroke memory above which the user is told to simplify or save, 256 MiB.
+pub const MEMORY_WARNING_BYTES: usize = 256 << 20;
+
+/// Approximate heap and inline memory held by `strokes`, in bytes. Counts
+/// allocated capacity rather than length, since that is what is resident.
+pub fn estimate_memory(strokes: &[Stroke]) -> usize {
+    use std::mem::size_of;
+    strokes
+        .iter()
+        .map(|s| {
+            size_of::<Stroke>()
+                + s.points.capacity() * size_of::<[f32; 2]>()
+                + s.timestamps.capacity() * size_of::<f32>()
+                + s.point_colors.capacity() * size_of::<[u8; 3]>()
+                + s.tags.capacity() * size_of::<String>()
+                + s.tags.iter().map(String::capacity).sum::<usize>()
+                + s.path.as_ref().map_or(0, |p| {
+                    p.segments.capacity() * size_of::<crate::path::BezierSegment>()
+                })
+        })
+        .sum()
+}
+
+/// Human-readable size for the HUD, e.g. "12.3 MiB".
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Logs a warning when the strokes use more than `threshold` bytes, and
+/// returns whether they do, so the HUD can show it too.
+pub fn warn_if_large(strokes: &[Stroke], threshold: usize) -> bool {
+    let bytes = estimate_memory(strokes);
+    if bytes <= threshold {
+        return false;
+    }
+    log::warn!(
+        "strokes use about {} ({} points); consider simplifying or saving",
+        format_bytes(bytes),
+        point_count(strokes)
+    );
+    true
+}
+
+/// Bounding box of all `strokes`, or `None` if none of them has points.
+pub fn bounds(strokes: &[Stroke]) -> Option<([f32; 2], [f32; 2])> {
+    strokes
+        .iter()
+        .filter_map(Stroke::bounds)
+        .fold(None, |acc, b| {
+            Some(acc.map_or(b, |acc| geometry::union(acc, b)))
+        })
+}
+
+/// The stroke end point closest to `point` within `radius`, if any, for
+/// snapping new shapes onto existing strokes.
+pub fn nearest_endpoint(strokes: &[Stroke], point: [f32; 2], radius: f32) -> Option<[f32; 2]> {
+    strokes
+        .iter()
+        .flat_map(|s| s.points.first().into_iter().chain(s.points.last()))
+        .map(|&end| (geometry::distance(point, end), end))
+        .filter(|&(d, _)| d <= radius)
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, end)| end)
+}
+
+/// Closes `stroke` into a loop by snapping its last point onto its first one
+/// when they are less than `threshold` apart. Returns whether it was closed;
+/// locked strokes never are.
+pub fn maybe_close(stroke: &mut Stroke, threshold: f32) -> bool {
+    let n = stroke.points.len();
+    if n < 3 || stroke.locked {
+        return false;
+    }
+    let first = stroke.points[0];
+    if geometry::distance(first, stroke.points[n - 1]) > threshold {
+        return false;
+    }
+    stroke.points[n - 1] = first;
+    stroke.path = None;
+    stroke.invalidate_bbox();
+    true
+}
+
+/// Indices of the strokes lying entirely inside the lasso `poly`, which is
+/// closed automatically.
+pub fn lasso_select(strokes: &[Stroke], poly: &[[f32; 2]]) -> Vec<usize> {
+    if poly.len() < 3 {
+        return Vec::new();
+    }
+    strokes
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !s.locked && !s.points.is_empty())
+        .filter(|(_, s)| {
+            s.points
+                .iter()
+                .all(|&p| geometry::point_in_polygon(poly, p))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Number of evenly spaced samples compared by `strokes_similar`.
+const SIMILARITY_SAMPLES: usize = 32;
+
+/// `points` resampled to exactly `count` points evenly spaced along its
+/// length.
+fn resample_count(points: &[[f32; 2]], count: usize) -> Vec<[f32; 2]> {
+    let length: f32 = points
+        .windows(2)
+        .map(|s| geometry::distance(s[0], s[1]))
+        .sum();
+    let mut samples = geometry::resample_by_arclength(points, length / (count - 1) as f32);
+    // Rounding may leave the last sample short of the end point.
+    samples.truncate(count - 1);
+    samples.resize(count - 1, points[0]);
+    samples.push(*points.last().unwrap());
+    samples
+}
+
+/// Whether `a` and `b` have the same shape within `tol`: compared at evenly
+/// spaced points along their lengths, in either direction, no pair is
+/// farther apart than `tol`. Typical of a stroke accidentally drawn twice.
+pub fn strokes_similar(a: &Stroke, b: &Stroke, tol: f32) -> bool {
+    let (ba, bb) = match (geometry::bounds(&a.points), geometry::bounds(&b.points)) {
+        (Some(ba), Some(bb)) => (ba, bb),
+        _ => return false,
+    };
+    let corners_close =
+        (0..2).all(|i| (ba.0[i] - bb.0[i]).abs() <= tol && (ba.1[i] - bb.1[i]).abs() <= tol);
+    if !corners_close {
+        return false;
+    }
+
+    let sa = resample_count(&a.points, SIMILARITY_SAMPLES);
+    let sb = resample_count(&b.points, SIMILARITY_SAMPLES);
+    let forward = sa.iter().zip(sb.iter());
+    let backward = sa.iter().zip(sb.iter().rev());
+    all_within(forward, tol) || all_within(backward, tol)
+}
+
+fn all_within<'a, I: Iterator<Item = (&'a [f32; 2], &'a [f32; 2])>>(
+    mut pairs: I,
+    tol: f32,
+) -> bool {
+    pairs.all(|(&p, &q)| geometry::distance(p, q) <= tol)
+}
+
+/// Indices of the unlocked strokes similar to an earlier stroke of the list,
+/// in increasing order. Removing them leaves one copy of each duplicate.
+/// Locked strokes are never listed, though they may be the copy kept.
+pub fn duplicate_indices(strokes: &[Stroke], tol: f32) -> Vec<usize> {
+    let mut duplicates = Vec::new();
+    for (i, stroke) in strokes.iter().enumerate() {
+        let is_duplicate = !stroke.locked
+            && strokes[..i].iter().enumerate().any(|(j, earlier)| {
+                !duplicates.contains(&j) && strokes_similar(earlier, stroke, tol)
+            });
+        if is_duplicate {
+            duplicates.push(i);
+        }
+    }
+    duplicates
+}
+
+/// Joins strokes whose end points coincide within `threshold` into single
+/// continuous strokes, reversing pieces as needed so each chain runs in one
+/// direction. Only two ends meeting are joined: where three or more meet,
+/// the branches are left apart. Pieces must share color, width and style,
+/// and locked strokes and Bézier paths are never joined. A joined stroke takes the place and
+/// ID of its lowest piece in the draw order.
+pub fn join_strokes(strokes: &[Stroke], threshold: f32) -> Vec<Stroke> {
+    let n = strokes.len();
+    let joinable = |s: &Stroke| !s.locked && s.path.is_none() && s.points.len() >= 2;
+    let end = |s: usize, e: usize| {
+        let points = &strokes[s].points;
+        if e == 0 {
+            points[0]
+        } else {
+            points[points.len() - 1]
+        }
+    };
+
+    // The one other stroke end each end meets, if exactly one.
+    let mut partner: Vec<[Option<(usize, usize)>; 2]> = vec![[None, None]; n];
+    for s in (0..n).filter(|&s| joinable(&strokes[s])) {
+        for (e, slot) in partner[s].iter_mut().enumerate() {
+            let mut matches = (0..n)
+                .filter(|&t| t != s && joinable(&strokes[t]))
+                .flat_map(|t| (0..2).map(move |f| (t, f)))
+                .filter(|&(t, f)| geometry::distance(end(s, e), end(t, f)) <= threshold);
+            if let (Some(m), None) = (matches.next(), matches.next()) {
+                *slot = Some(m);
+            }
+        }
+    }
+    for s in 0..n {
+        for e in 0..2 {
+            if let Some((t, f)) = partner[s][e] {
+                let (a, b) = (&strokes[s], &strokes[t]);
+                let compatible = a.color == b.color && a.width == b.width && a.style == b.style;
+                if partner[t][f] != Some((s, e)) || !compatible {
+                    partner[s][e] = None;
+                }
+            }
+        }
+    }
+
+    let mut used = vec![false; n];
+    let mut joined = Vec::new();
+    for s in 0..n {
+        if used[s] {
+            continue;
+        }
+        // Walk back to the free end of the chain, or around a closed loop.
+        let mut head = (s, 0);
+        for _ in 0..n {
+            match partner[head.0][head.1] {
+                Some((t, f)) if t != s => head = (t, 1 - f),
+                Some(_) => {
+                    head = (s, 0);
+                    break;
+                }
+                None => break,
+            }
+        }
+        // Then forward, noting which pieces run backwards.
+        let mut chain = Vec::new();
+        let (mut t, mut entry) = head;
+        loop {
+            used[t] = true;
+            chain.push((t, entry == 1));
+            match partner[t][1 - entry] {
+                Some((u, f)) if !used[u] => {
+                    t = u;
+                    entry = f;
+                }
+                _ => break,
+            }
+        }
+        joined.push(concat_chain(strokes, &chain));
+    }
+    joined
+}
+
+/// Concatenates the pieces of a chain, given in order with whether each
+/// runs backwards. The first point of each following piece coincides with
+/// the previous end and is dropped.
+fn concat_chain(strokes: &[Stroke], chain: &[(usize, bool)]) -> Stroke {
+    let lowest = chain.iter().map(|&(i, _)| i).min().unwrap_or(0);
+    if chain.len() == 1 {
+        return strokes[lowest].clone();
+    }
+
+    let timed = chain.iter().all(|&(i, _)| strokes[i].has_timestamps());
+    let colored = chain.iter().any(|&(i, _)| strokes[i].has_point_colors());
+    let mut joined = strokes[lowest].empty_copy();
+    joined.id = strokes[lowest].id;
+    let mut time_offset = 0.0;
+    for (k, &(i, reversed)) in chain.iter().enumerate() {
+        let stroke = &strokes[i];
+        let len = stroke.points.len();
+        let indices: Vec<usize> = if reversed {
+            (0..len).rev().collect()
+        } else {
+            (0..len).collect()
+        };
+        let start_time = if timed {
+            stroke.timestamps[indices[0]]
+        } else {
+            0.0
+        };
+        for &j in indices.iter().skip(if k == 0 { 0 } else { 1 }) {
+            joined.points.push(stroke.points[j]);
+            if timed {
+                joined
+                    .timestamps
+                    .push(time_offset + (stroke.timestamps[j] - start_time).abs());
+            }
+            if colored {
+                joined.point_colors.push(stroke.color_at(j));
+            }
+        }
+        time_offset = joined.timestamps.last().copied().unwrap_or(0.0);
+    }
+    joined
+}
+
+/// Adds `tag` to the strokes at `indices`.
+pub fn tag_strokes(strokes: &mut [Stroke], indices: &[usize], tag: &str) {
+    for &i in indices {
+        if let Some(stroke) = strokes.get_mut(i) {
+            stroke.add_tag(tag);
+        }
+    }
+}
+
+/// Removes all tags from the strokes at `indices`.
+pub fn clear_tags(strokes: &mut [Stroke], indices: &[usize]) {
+    for &i in indices {
+        if let Some(stroke) = strokes.get_mut(i) {
+            stroke.tags.clear();
+        }
+    }
+}
+
+/// Which tags are currently hidden. A stroke is shown unless one of its tags
+/// is hidden, so untagged strokes are always visible.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagFilter {
+    pub hidden: HashSet<String>,
+}
+
+impl TagFilter {
+    /// Flips the visibility of `tag`, returning whether it is now shown.
+    pub fn toggle(&mut self, tag: &str) -> bool {
+        if self.hidden.remove(tag) {
+            true
+        } else {
+            self.hidden.insert(tag.to_string());
+            false
+        }
+    }
+
+    pub fn is_visible(&self, stroke: &Stroke) -> bool {
+        !stroke.tags.iter().any(|t| self.hidden.contains(t))
+    }
+}
+
+/// Recolors every unlocked stroke color and per-point color within
+/// `tolerance` of `from` (largest difference in any channel) to `to`,
+/// returning how many strokes changed.
+pub fn replace_color(strokes: &mut [Stroke], from: [u8; 3], to: [u8; 3], tolerance: u8) -> usize {
+    let matches = |c: [u8; 3]| (0..3).all(|i| c[i].abs_diff(from[i]) <= tolerance);
+    let mut changed = 0;
+    for stroke in strokes.iter_mut().filter(|s| !s.locked) {
+        let mut touched = false;
+        if matches(stroke.color) && stroke.color != to {
+            stroke.color = to;
+            touched = true;
+        }
+        for color in &mut stroke.point_colors {
+            if matches(*color) && *color != to {
+                *color = to;
+                touched = true;
+            }
+        }
+        changed += touched as usize;
+    }
+    changed
+}
+
+/// Locks or unlocks the strokes at `indices`.
+pub fn set_locked(strokes: &mut [Stroke], indices: &[usize], locked: bool) {
+    for &i in indices {
+        if let Some(stroke) = strokes.get_mut(i) {
+            stroke.locked = locked;
+        }
+    }
+}
+
+/// Removes the unlocked strokes touched by the eraser circle, returning how
+/// many were removed.
+pub fn erase_strokes(strokes: &mut Vec<Stroke>, center: [f32; 2], radius: f32) -> usize {
+    let before = strokes.len();
+    strokes.retain(|s| s.locked || !s.hit(center, radius));
+    before - strokes.len()
+}
+
+/// Erases the parts of `stroke` within `radius` of `eraser_center`, returning
+/// the remaining pieces: the stroke unchanged if untouched, two pieces when
+/// erased in the middle, one when erased at an end, and none when fully
+/// covered. Cut points are placed on the eraser circle. Only the inside of
+/// the circle erases: points on it and segments touching it are kept.
+/// Locked strokes are returned unchanged.
+pub fn split_stroke(stroke: &Stroke, eraser_center: [f32; 2], radius: f32) -> Vec<Stroke> {
+    if stroke.locked {
+        return vec![stroke.clone()];
+    }
+    // The same test as `circle_overlap`, so both agree on boundary points.
+    let inside = |p: [f32; 2]| {
+        let d = [p[0] - eraser_center[0], p[1] - eraser_center[1]];
+        d[0] * d[0] + d[1] * d[1] < radius * radius
+    };
+    match stroke.points.len() {
+        0 => return Vec::new(),
+        1 if inside(stroke.points[0]) => return Vec::new(),
+        1 => return vec![stroke.clone()],
+        _ => {}
+    }
+
+    let timed = stroke.has_timestamps();
+    let colored = stroke.has_point_colors();
+    let sample = |i: usize| Sample {
+        point: stroke.points[i],
+        time: if timed { stroke.timestamps[i] } else { 0.0 },
+        color: stroke.color_at(i),
+    };
+    let push = |piece: &mut Stroke, s: Sample| {
+        piece.points.push(s.point);
+        if timed {
+            piece.timestamps.push(s.time);
+        }
+        if colored {
+            piece.point_colors.push(s.color);
+        }
+    };
+
+    let mut pieces = Vec::new();
+    let mut current: Option<Stroke> = None;
+    if !inside(stroke.points[0]) {
+        let mut piece = stroke.empty_copy();
+        push(&mut piece, sample(0));
+        current = Some(piece);
+    }
+
+    for i in 0..stroke.points.len() - 1 {
+        let (a, b) = (sample(i), sample(i + 1));
+        match circle_overlap(a.point, b.point, eraser_center, radius) {
+            None => match current.as_mut() {
+                Some(piece) => push(piece, b),
+                // The previous segment ended right on the circle.
+                None => {
+                    let mut piece = stroke.empty_copy();
+                    push(&mut piece, a);
+                    push(&mut piece, b);
+                    current = Some(piece);
+                }
+            },
+            Some((enter, exit)) => {
+                if let Some(mut piece) = current.take() {
+                    if enter > 0.0 {
+                        push(&mut piece, a.lerp(&b, enter));
+                    }
+                    pieces.push(piece);
+                }
+                if exit < 1.0 {
+                    let mut piece = stroke.empty_copy();
+                    push(&mut piece, a.lerp(&b, exit));
+                    push(&mut piece, b);
+                    current = Some(piece);
+                }
+            }
+        }
+    }
+    pieces.extend(current);
+    pieces.retain(|piece| piece.points.len() >= 2);
+    pieces
+}
+
+/// A point of a stroke along with its attributes.
+#[derive(Clone, Copy)]
+struct Sample {
+    point: [f32; 2],
+    time: f32,
+    color: [u8; 3],
+}
+
+impl Sample {
+    fn lerp(&self, other: &Sample, t: f32) -> Sample {
+        let (a, b) = (self.point, other.point);
+        Sample {
+            point: [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t],
+            time: self.time + (other.time - self.time) * t,
+            color: color::lerp_rgb(self.color, other.color, t),
+        }
+    }
+}
+
+/// Parameter range of the segment from `a` to `b` that lies inside the
+/// circle, clipped to `[0, 1]`, or `None` if the segment misses it or only
+/// touches it, at a tangent or an end point.
+fn circle_overlap(a: [f32; 2], b: [f32; 2], center: [f32; 2], radius: f32) -> Option<(f32, f32)> {
+    let d = [b[0] - a[0], b[1] - a[1]];
+    let f = [a[0] - center[0], a[1] - center[1]];
+    let qa = d[0] * d[0] + d[1] * d[1];
+    let qb = 2.0 * (f[0] * d[0] + f[1] * d[1]);
+    let qc = f[0] * f[0] + f[1] * f[1] - radius * radius;
+    if qa <= f32::EPSILON {
+        return if qc < 0.0 { Some((0.0, 1.0)) } else { None };
+    }
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let enter = ((-qb - root) / (2.0 * qa)).max(0.0);
+    let exit = ((-qb + root) / (2.0 * qa)).min(1.0);
+    if enter < exit {
+        Some((enter, exit))
+    } else {
+        None
+    }
+}
+
+/// Current indices of the strokes with the given `ids`, in increasing order.
+/// IDs of strokes no longer in the list are skipped.
+pub fn indices_of(strokes: &[Stroke], ids: &[u64]) -> Vec<usize> {
+    strokes
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| ids.contains(&s.id))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Moves the strokes at `indices` to the top of the draw order, keeping their
+/// relative order. Out of range indices and locked strokes are ignored.
+/// `History::bring_to_front` does the same as an undoable step.
+pub fn bring_to_front(strokes: &mut Vec<Stroke>, indices: &[usize]) {
+    let (moved, rest) = partition(strokes, indices);
+    strokes.extend(rest);
+    strokes.extend(moved);
+}
+
+/// Moves the strokes at `indices` to the bottom of the draw order, keeping
+/// their relative order. Out of range indices and locked strokes are ignored.
+/// `History::send_to_back` does the same as an undoable step.
+pub fn send_to_back(strokes: &mut Vec<Stroke>, indices: &[usize]) {
+    let (moved, rest) = partition(strokes, indices);
+    strokes.extend(moved);
+    strokes.extend(rest);
+}
+
+/// Indices of `strokes` in the order they were created, which differs from
+/// the list (render) order once strokes have been reordered.
+pub fn creation_order(strokes: &[Stroke]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..strokes.len()).collect();
+    order.sort_by_key(|&i| strokes[i].created);
+    order
+}
+
+/// Appends `incoming` on top of `into`, moving every incoming stroke by
+/// `offset` so that merged sketches do not have to overlap. Both sets are
+/// expected to use the same coordinate space.
+pub fn merge_sketches(into: &mut Vec<Stroke>, incoming: Vec<Stroke>, offset: [f32; 2]) {
+    into.reserve(incoming.len());
+    for mut stroke in incoming {
+        stroke.translate(offset);
+        // The other sketch numbered its strokes independently.
+        stroke.id = next_id();
+        stroke.created = stroke.id;
+        into.push(stroke);
+    }
+}
+
+/// Extends the last stroke of `strokes` with a straight segment ending at
+/// `point`, or starts a new stroke from `point` when there is none yet or
+/// the last one is locked.
+pub fn continue_last_stroke(
+    strokes: &mut Vec<Stroke>,
+    point: [f32; 2],
+    color: [u8; 3],
+    width: f32,
+) {
+    match strokes.last_mut().filter(|s| !s.locked) {
+        Some(stroke) if stroke.points.last() == Some(&point) => {}
+        Some(stroke) if !stroke.points.is_empty() => {
+            if stroke.has_timestamps() {
+                let last = stroke.timestamps.last().copied().unwrap_or(0.0);
+                stroke.timestamps.push(last);
+            }
+            if stroke.has_point_colors() {
+                let last = stroke.color_at(stroke.points.len() - 1);
+                stroke.point_colors.push(last);
+            }
+            stroke.path = None;
+            stroke.invalidate_bbox();
+            stroke.points.push(point);
+        }
+        _ => {
+            let mut stroke = Stroke::new(color, width);
+            stroke.push(point, Duration::from_secs(0));
+            strokes.push(stroke);
+        }
+    }
+}
+
+fn partition(strokes: &mut Vec<Stroke>, indices: &[usize]) -> (Vec<Stroke>, Vec<Stroke>) {
+    let mut selected = vec![false; strokes.len()];
+    for &index in indices {
+        if let Some(flag) = selected.get_mut(index) {
+            *flag = !strokes[index].locked;
+        }
+    }
+
+    let mut moved = Vec::new();
+    let mut rest = Vec::new();
+    for (stroke, selected) in strokes.drain(..).zip(selected) {
+        if selected {
+            moved.push(stroke);
+        } else {
+            rest.push(stroke);
+        }
+    }
+    (moved, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn push_skips_a_point_equal_to_the_last() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        assert!(stroke.push([0.1, 0.2], ms(0)));
+        assert!(!stroke.push([0.1, 0.2], ms(5)));
+        assert!(stroke.push([0.3, 0.2], ms(10)));
+        assert!(stroke.push([0.1, 0.2], ms(15)));
+        assert_eq!(stroke.points, vec![[0.1, 0.2], [0.3, 0.2], [0.1, 0.2]]);
+        assert_eq!(stroke.timestamps, vec![0.0, 0.01, 0.015]);
+        assert!(stroke.points.windows(2).all(|s| s[0] != s[1]));
+    }
+
+    #[test]
+    fn push_colored_skips_a_point_equal_to_the_last() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        assert!(stroke.push_colored([0.0, 0.0], ms(0), [255, 0, 0]));
+        assert!(!stroke.push_colored([0.0, 0.0], ms(1), [0, 255, 0]));
+        assert_eq!(stroke.points.len(), 1);
+        assert_eq!(stroke.point_colors, vec![[255, 0, 0]]);
+        assert_eq!(stroke.timestamps.len(), 1);
+    }
+
+    fn assert_box(actual: Option<([f32; 2], [f32; 2])>, min: [f32; 2], max: [f32; 2]) {
+        let (a_min, a_max) = actual.expect("no bounding box");
+        for i in 0..2 {
+            assert!((a_min[i] - min[i]).abs() < 1e-5, "{:?} != {:?}", a_min, min);
+            assert!((a_max[i] - max[i]).abs() < 1e-5, "{:?} != {:?}", a_max, max);
+        }
+    }
+
+    #[test]
+    fn bbox_is_cached_until_the_points_change() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.2);
+        stroke.push([0.0, 0.0], ms(0));
+        stroke.push([1.0, 2.0], ms(10));
+        assert_box(stroke.bbox(), [-0.1, -0.1], [1.1, 2.1]);
+        assert_eq!(stroke.bounds(), stroke.bbox());
+
+        stroke.push([-1.0, 0.0], ms(20));
+        assert_box(stroke.bbox(), [-1.1, -0.1], [1.1, 2.1]);
+
+        stroke.translate([1.0, 1.0]);
+        assert_box(stroke.bbox(), [-0.1, 0.9], [2.1, 3.1]);
+
+        stroke.rotate([0.0, 0.0], std::f32::consts::PI);
+        assert_box(stroke.bbox(), [-2.1, -3.1], [0.1, -0.9]);
+
+        stroke.simplify(10.0);
+        assert_eq!(stroke.points.len(), 2);
+        let mut uncached = stroke.clone();
+        uncached.invalidate_bbox();
+        assert_eq!(stroke.bbox(), uncached.bounds());
+    }
+
+    #[test]
+    fn direct_edits_need_an_explicit_invalidation() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.0);
+        stroke.push([0.0, 0.0], ms(0));
+        stroke.push([1.0, 1.0], ms(1));
+        assert_eq!(stroke.bbox(), Some(([0.0, 0.0], [1.0, 1.0])));
+
+        stroke.points[1] = [3.0, 3.0];
+        assert_eq!(stroke.bbox(), Some(([0.0, 0.0], [1.0, 1.0])));
+        stroke.invalidate_bbox();
+        assert_eq!(stroke.bbox(), Some(([0.0, 0.0], [3.0, 3.0])));
+    }
+
+    #[test]
+    fn the_cache_is_ignored_by_comparisons() {
+        let mut cached = Stroke::new([0, 0, 0], 0.1);
+        cached.push([0.0, 0.0], ms(0));
+        let plain = cached.clone();
+        cached.bbox();
+        assert_eq!(cached, plain);
+    }
+
+    #[test]
+    fn reserve_ids_refuses_the_largest_id() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.1);
+        let taken = stroke.id + 1000;
+        stroke.id = taken;
+        assert!(reserve_ids(&[stroke.clone()]));
+        assert!(next_id() > taken);
+
+        stroke.created = u64::MAX;
+        assert!(!reserve_ids(&[stroke]));
+        // Nothing was reserved, so IDs keep counting normally.
+        assert!(next_id() < u64::MAX);
+    }
+
+    fn ordered(count: usize) -> Vec<Stroke> {
+        (0..count)
+            .map(|i| {
+                let mut stroke = Stroke::new([0, 0, 0], 0.01);
+                stroke.push([i as f32, 0.0], ms(0));
+                stroke
+            })
+            .collect()
+    }
+
+    fn order(strokes: &[Stroke], original: &[Stroke]) -> Vec<usize> {
+        strokes
+            .iter()
+            .map(|s| original.iter().position(|o| o.id == s.id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reordering_skips_locked_strokes() {
+        let mut original = ordered(4);
+        original[1].locked = true;
+
+        let mut strokes = original.clone();
+        bring_to_front(&mut strokes, &[0, 1]);
+        assert_eq!(order(&strokes, &original), [1, 2, 3, 0]);
+
+        let mut strokes = original.clone();
+        send_to_back(&mut strokes, &[1, 3]);
+        assert_eq!(order(&strokes, &original), [3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn locked_strokes_are_not_duplicates() {
+        let mut strokes = ordered(1);
+        strokes.push(strokes[0].clone());
+        strokes.push(strokes[0].clone());
+        strokes[1].locked = true;
+        assert_eq!(duplicate_indices(&strokes, 1e-3), [2]);
+    }
+
+    #[test]
+    fn width_profiles_are_saved_only_when_set() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.1);
+        stroke.push([0.0, 0.0], ms(0));
+        assert!(!serde_json::to_string(&stroke).unwrap().contains("profile"));
+
+        stroke.profile.taper = 0.25;
+        let json = serde_json::to_string(&stroke).unwrap();
+        let loaded: Stroke = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.profile.taper, 0.25);
+        assert_eq!(stroke.empty_copy().profile, stroke.profile);
+    }
+
+    fn curve() -> BezierPath {
+        let mut path = BezierPath::new([0.0, 0.0]);
+        path.push([0.0, 1.0], [1.0, 1.0], [1.0, 0.0]);
+        path
+    }
+
+    #[test]
+    fn path_strokes_follow_their_path() {
+        let stroke = Stroke::from_path(curve(), [0, 0, 0], 0.1);
+        assert_eq!(stroke.points, curve().to_polyline(PATH_SAMPLES));
+        assert_eq!(stroke.points.len(), PATH_SAMPLES + 1);
+
+        let json = serde_json::to_string(&stroke).unwrap();
+        let loaded: Stroke = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.path, Some(curve()));
+    }
+
+    #[test]
+    fn moving_a_node_reflattens_the_path() {
+        let mut stroke = Stroke::from_path(curve(), [0, 0, 0], 0.0);
+        assert_box(stroke.bbox(), [0.0, 0.0], [1.0, 0.75]);
+        assert!(stroke.move_path_node(3, [2.0, 0.0]));
+        assert_eq!(stroke.points.last(), Some(&[2.0, 0.0]));
+        assert_box(stroke.bbox(), [0.0, 0.0], [2.0, 0.75]);
+        assert!(!stroke.move_path_node(4, [0.0, 0.0]));
+        assert!(!Stroke::new([0, 0, 0], 0.1).move_path_node(0, [0.0, 0.0]));
+    }
+
+    #[test]
+    fn transforms_move_the_path_too() {
+        let mut stroke = Stroke::from_path(curve(), [0, 0, 0], 0.1);
+        stroke.translate([1.0, 2.0]);
+        let path = stroke.path.clone().unwrap();
+        assert_eq!(path.start, [1.0, 2.0]);
+        assert_eq!(path.segments[0].end, [2.0, 2.0]);
+        assert_eq!(stroke.points, path.to_polyline(PATH_SAMPLES));
+
+        stroke.rotate([1.0, 2.0], std::f32::consts::FRAC_PI_2);
+        let end = stroke.path.as_ref().unwrap().segments[0].end;
+        assert!((end[0] - 1.0).abs() < 1e-5 && (end[1] - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn point_edits_turn_paths_into_plain_strokes() {
+        let mut stroke = Stroke::from_path(curve(), [0, 0, 0], 0.1);
+        stroke.simplify(1.0);
+        assert_eq!(stroke.points.len(), PATH_SAMPLES + 1);
+        assert!(stroke.path.is_some());
+
+        stroke.push([2.0, 0.0], ms(0));
+        assert!(stroke.path.is_none());
+        assert_eq!(stroke.points.len(), PATH_SAMPLES + 2);
+
+        let mut strokes = vec![Stroke::from_path(curve(), [0, 0, 0], 0.1)];
+        continue_last_stroke(&mut strokes, [2.0, 0.0], [0, 0, 0], 0.1);
+        assert!(strokes[0].path.is_none());
+    }
+
+    #[test]
+    fn paths_are_not_joined() {
+        let path = Stroke::from_path(curve(), [0, 0, 0], 0.1);
+        let mut line = Stroke::new([0, 0, 0], 0.1);
+        line.push([1.0, 0.0], ms(0));
+        line.push([2.0, 0.0], ms(1));
+        let joined = join_strokes(&[path.clone(), line], 1e-3);
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0], path);
+    }
+
+    fn polyline(points: &[[f32; 2]]) -> Stroke {
+        let mut stroke = Stroke::new([0, 0, 0], 0.1);
+        for (i, &p) in points.iter().enumerate() {
+            stroke.push(p, ms(i as u64));
+        }
+        stroke
+    }
+
+    fn kept(stroke: &Stroke, center: [f32; 2], radius: f32) -> Vec<Vec<[f32; 2]>> {
+        let pieces = split_stroke(stroke, center, radius);
+        pieces.into_iter().map(|piece| piece.points).collect()
+    }
+
+    #[test]
+    fn erasing_the_middle_leaves_two_pieces() {
+        let stroke = polyline(&[[-2.0, 0.0], [0.0, 0.0], [2.0, 0.0]]);
+        let pieces = split_stroke(&stroke, [0.0, 0.0], 1.0);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].points, vec![[-2.0, 0.0], [-1.0, 0.0]]);
+        assert_eq!(pieces[1].points, vec![[1.0, 0.0], [2.0, 0.0]]);
+        assert!(split_stroke(&stroke, [0.0, 0.0], 3.0).is_empty());
+    }
+
+    #[test]
+    fn a_start_point_on_the_circle_is_kept() {
+        let stroke = polyline(&[[1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        assert_eq!(kept(&stroke, [0.0, 0.0], 1.0), vec![stroke.points.clone()]);
+        assert!(!stroke.hit([0.0, 0.0], 1.0));
+    }
+
+    #[test]
+    fn an_end_point_on_the_circle_is_kept() {
+        let stroke = polyline(&[[3.0, 0.0], [2.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(kept(&stroke, [0.0, 0.0], 1.0), vec![stroke.points]);
+    }
+
+    #[test]
+    fn the_stroke_continues_after_a_point_on_the_circle() {
+        let stroke = polyline(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        let pieces = split_stroke(&stroke, [0.0, 0.0], 1.0);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].points, vec![[1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        assert_eq!(pieces[0].timestamps, vec![0.001, 0.002, 0.003]);
+    }
+
+    #[test]
+    fn a_tangent_segment_is_kept_whole() {
+        let stroke = polyline(&[[-2.0, 1.0], [2.0, 1.0], [2.0, 3.0]]);
+        assert_eq!(kept(&stroke, [0.0, 0.0], 1.0), vec![stroke.points.clone()]);
+        assert!(!stroke.hit([0.0, 0.0], 1.0));
+    }
+
+    #[test]
+    fn untimed_strokes_stay_untimed() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.1);
+        stroke.points = vec![[0.0, 0.0], [1.0, 0.0]];
+        assert!(stroke.push([2.0, 0.0], ms(10)));
+        assert!(stroke.push_colored([3.0, 0.0], ms(20), [255, 0, 0]));
+        assert!(stroke.timestamps.is_empty());
+        assert_eq!(stroke.point_colors.len(), stroke.points.len());
+        assert!(stroke.push([4.0, 0.0], ms(30)));
+        assert_eq!(stroke.point_colors.len(), stroke.points.len());
+    }
+
+    #[test]
+    fn timestamps_follow_every_push_path() {
+        let mut stroke = Stroke::new([0, 0, 0], 0.1);
+        stroke.push_colored([0.0, 0.0], ms(0), [1, 2, 3]);
+        stroke.push([1.0, 0.0], ms(10));
+        assert!(!stroke.push_colored([1.0, 0.0], ms(15), [4, 5, 6]));
+        stroke.push_colored([2.0, 0.0], ms(20), [4, 5, 6]);
+        let mut strokes = vec![stroke];
+        continue_last_stroke(&mut strokes, [3.0, 0.0], [0, 0, 0], 0.1);
+        continue_last_stroke(&mut strokes, [3.0, 0.0], [0, 0, 0], 0.1);
+        let stroke = &strokes[0];
+        assert_eq!(stroke.points.len(), 4);
+        assert_eq!(stroke.timestamps, vec![0.0, 0.01, 0.02, 0.02]);
+        assert_eq!(stroke.point_colors.len(), 4);
+
+        let mut untimed = Stroke::new([0, 0, 0], 0.1);
+        untimed.points = vec![[0.0, 0.0], [1.0, 0.0]];
+        let mut strokes = vec![untimed];
+        continue_last_stroke(&mut strokes, [2.0, 0.0], [0, 0, 0], 0.1);
+        assert_eq!(strokes[0].points.len(), 3);
+        assert!(strokes[0].timestamps.is_empty());
+    }
+
+    #[test]
+    fn flattened_paths_take_no_stray_timestamps() {
+        let mut stroke = Stroke::from_path(curve(), [0, 0, 0], 0.1);
+        assert!(stroke.push([5.0, 5.0], ms(10)));
+        assert!(stroke.timestamps.is_empty());
+        assert!(stroke.path.is_none());
+    }
+
+    #[test]
+    fn bounds_grow_by_the_largest_decoration() {
+        let mut stroke = polyline(&[[0.0, 0.0], [1.0, 0.0]]);
+        assert_box(stroke.bounds(), [-0.05, -0.05], [1.05, 0.05]);
+
+        stroke.arrows = Arrowheads::End;
+        let r = 0.1 * ARROW_SCALE;
+        assert_box(stroke.bounds(), [-r, -r], [1.0 + r, r]);
+
+        stroke.arrows = Arrowheads::None;
+        stroke.outline = Some(Outline {
+            width: 0.2,
+            color: [0, 0, 0],
+        });
+        assert_box(stroke.bounds(), [-0.25, -0.25], [1.25, 0.25]);
+
+        stroke.outline = None;
+        stroke.style = StrokeStyle::Points { size: 0.6 };
+        assert_box(stroke.bounds(), [-0.3, -0.3], [1.3, 0.3]);
+    }
+
+    #[test]
+    fn the_cached_box_includes_decorations() {
+        let mut stroke = polyline(&[[0.0, 0.0], [1.0, 0.0]]);
+        stroke.arrows = Arrowheads::Both;
+        assert_eq!(stroke.bbox(), stroke.clone().bounds());
+        let r = 0.1 * ARROW_SCALE;
+        assert_box(stroke.bbox(), [-r, -r], [1.0 + r, r]);
+    }
+
+    #[test]
+    fn miter_joins_widen_the_bounds() {
+        let mut stroke = polyline(&[[0.0, 0.0], [1.0, 0.0]]);
+        stroke.join_style = Some(JoinStyle::Miter);
+        let r = MITER_LIMIT * 0.05;
+        assert_box(stroke.bounds(), [-r, -r], [1.0 + r, r]);
+        stroke.join_style = Some(JoinStyle::Round);
+        stroke.invalidate_bbox();
+        assert_box(stroke.bounds(), [-0.05, -0.05], [1.05, 0.05]);
+    }
+
+    #[test]
+    fn decoration_changes_reach_the_cached_box() {
+        let mut stroke = polyline(&[[0.0, 0.0], [1.0, 0.0]]);
+        assert_box(stroke.bbox(), [-0.05, -0.05], [1.05, 0.05]);
+        stroke.outline = Some(Outline {
+            width: 0.2,
+            color: [255, 255, 255],
+        });
+        assert_box(stroke.bounds(), [-0.25, -0.25], [1.25, 0.25]);
+        assert_box(stroke.bbox(), [-0.25, -0.25], [1.25, 0.25]);
+        stroke.width = 0.3;
+        stroke.arrows = Arrowheads::End;
+        let r = 0.3 * ARROW_SCALE + 0.2 * 5f32.sqrt();
+        assert_box(stroke.bounds(), [-r, -r], [1.0 + r, r]);
+    }
+}
@@ -0,0 +1,560 @@
+//! Importing strokes from SVG documents and from path data, the `d`
+//! attribute of `<path>`.
+
+use std::fmt;
+
+use crate::document::Page;
+use crate::geometry::{cubic_bezier, quad_bezier};
+use crate::stroke::Stroke;
+
+/// Segments each curve command is flattened into.
+pub const CURVE_SAMPLES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathDataError {
+    /// A character that is neither a command nor part of a number, with
+    /// its byte offset.
+    Unexpected(char, usize),
+    /// A command ran out of coordinates.
+    MissingNumber(char),
+    /// Elliptical arcs (`A`) are not supported.
+    Unsupported(char),
+}
+
+impl fmt::Display for PathDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathDataError::Unexpected(c, at) => {
+                write!(f, "unexpected '{}' at offset {} in path data", c, at)
+            }
+            PathDataError::MissingNumber(command) => {
+                write!(f, "missing coordinate for path command '{}'", command)
+            }
+            PathDataError::Unsupported(command) => {
+                write!(f, "unsupported path command '{}'", command)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathDataError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SvgError {
+    /// A tag or comment starting at this byte offset is never closed.
+    Unterminated(usize),
+    /// Invalid path data in the `<path>` element at this byte offset.
+    PathData(usize, PathDataError),
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::Unterminated(at) => write!(f, "unterminated tag at offset {}", at),
+            SvgError::PathData(at, err) => write!(f, "in <path> at offset {}: {}", at, err),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Command(char),
+    Number(f32),
+}
+
+fn tokenize(d: &str) -> Result<Vec<Token>, PathDataError> {
+    let bytes = d.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c.is_ascii_whitespace() || c == ',' {
+            i += 1;
+        } else if "MmLlHhVvCcSsQqTtZzAa".contains(c) {
+            tokens.push(Token::Command(c));
+            i += 1;
+        } else {
+            // A number ends at a second sign or dot, so "1-2" and ".5.5" are
+            // two numbers each, as SVG allows.
+            let start = i;
+            if matches!(bytes[i], b'+' | b'-') {
+                i += 1;
+            }
+            let mut dot = false;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'0'..=b'9' => i += 1,
+                    b'.' if !dot => {
+                        dot = true;
+                        i += 1;
+                    }
+                    b'e' | b'E' => {
+                        i += 1;
+                        if i < bytes.len() && matches!(bytes[i], b'+' | b'-') {
+                            i += 1;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            let number = d[start..i]
+                .parse()
+                .map_err(|_| PathDataError::Unexpected(c, start))?;
+            tokens.push(Token::Number(number));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses path data into one polyline per subpath, in SVG user units.
+/// Curves are flattened into `samples` segments each, and a closed subpath
+/// (`Z`) ends on its first point. Subpaths with fewer than two points are
+/// dropped.
+pub fn parse_path_data(d: &str, samples: usize) -> Result<Vec<Vec<[f32; 2]>>, PathDataError> {
+    let tokens = tokenize(d)?;
+    let mut subpaths = Vec::new();
+    let mut current: Vec<[f32; 2]> = Vec::new();
+    let mut pos = [0.0, 0.0];
+    let mut start = [0.0, 0.0];
+    // Control point the next smooth curve (`S`, `T`) reflects.
+    let mut last_ctrl: Option<(char, [f32; 2])> = None;
+    let mut command = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let c = match tokens[i] {
+            Token::Command(c) => {
+                i += 1;
+                c
+            }
+            // Repeated coordinates reuse the previous command, except that
+            // after a moveto they are linetos.
+            Token::Number(_) => match command {
+                Some('M') => 'L',
+                Some('m') => 'l',
+                Some(c) if !matches!(c, 'Z' | 'z') => c,
+                _ => return Err(PathDataError::MissingNumber('M')),
+            },
+        };
+        command = Some(c);
+        let relative = c.is_ascii_lowercase();
+        let mut number = || match tokens.get(i) {
+            Some(&Token::Number(n)) => {
+                i += 1;
+                Ok(n)
+            }
+            _ => Err(PathDataError::MissingNumber(c)),
+        };
+        let mut point = |origin: [f32; 2]| -> Result<[f32; 2], PathDataError> {
+            let (x, y) = (number()?, number()?);
+            Ok(if relative {
+                [origin[0] + x, origin[1] + y]
+            } else {
+                [x, y]
+            })
+        };
+
+        let mut ctrl = None;
+        match c.to_ascii_uppercase() {
+            'M' => {
+                pos = point(pos)?;
+                if current.len() >= 2 {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                current = vec![pos];
+                start = pos;
+            }
+            'L' => {
+                pos = point(pos)?;
+                current.push(pos);
+            }
+            'H' => {
+                let x = number()?;
+                pos[0] = if relative { pos[0] + x } else { x };
+                current.push(pos);
+            }
+            'V' => {
+                let y = number()?;
+                pos[1] = if relative { pos[1] + y } else { y };
+                current.push(pos);
+            }
+            'C' | 'S' => {
+                let c1 = if c.eq_ignore_ascii_case(&'C') {
+                    point(pos)?
+                } else {
+                    reflect(last_ctrl, 'C', pos)
+                };
+                let c2 = point(pos)?;
+                let end = point(pos)?;
+                let curve = cubic_bezier(pos, c1, c2, end, samples.max(1));
+                current.extend_from_slice(&curve[1..]);
+                ctrl = Some(('C', c2));
+                pos = end;
+            }
+            'Q' | 'T' => {
+                let c1 = if c.eq_ignore_ascii_case(&'Q') {
+                    point(pos)?
+                } else {
+                    reflect(last_ctrl, 'Q', pos)
+                };
+                let end = point(pos)?;
+                let curve = quad_bezier(pos, c1, end, samples.max(1));
+                current.extend_from_slice(&curve[1..]);
+                ctrl = Some(('Q', c1));
+                pos = end;
+            }
+            'Z' => {
+                if current.last() != Some(&start) {
+                    current.push(start);
+                }
+                if current.len() >= 2 {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                // A command after Z starts a new subpath at the same point.
+                current = vec![start];
+                pos = start;
+            }
+            _ => return Err(PathDataError::Unsupported(c)),
+        }
+        last_ctrl = ctrl;
+    }
+    if current.len() >= 2 {
+        subpaths.push(current);
+    }
+    Ok(subpaths)
+}
+
+/// First control point of a smooth curve: the previous curve's last control
+/// point mirrored about `pos` if that curve was of the same `kind`, else
+/// `pos` itself.
+fn reflect(last: Option<(char, [f32; 2])>, kind: char, pos: [f32; 2]) -> [f32; 2] {
+    match last {
+        Some((k, c)) if k == kind => [2.0 * pos[0] - c[0], 2.0 * pos[1] - c[1]],
+        _ => pos,
+    }
+}
+
+/// Size of one page pixel in world units, along x and y.
+fn pixel_scale(page: &Page) -> [f32; 2] {
+    let (min, max) = page.bounds();
+    [
+        (max[0] - min[0]) / page.width.max(1) as f32,
+        (max[1] - min[1]) / page.height.max(1) as f32,
+    ]
+}
+
+/// Strokes for each subpath of `d`, read as pixel coordinates on `page`
+/// (origin top left, y down) and mapped onto its world bounds. The strokes
+/// have no timestamps.
+pub fn path_strokes(
+    d: &str,
+    page: &Page,
+    color: [u8; 3],
+    width: f32,
+) -> Result<Vec<Stroke>, PathDataError> {
+    let (min, max) = page.bounds();
+    let scale = pixel_scale(page);
+    Ok(parse_path_data(d, CURVE_SAMPLES)?
+        .into_iter()
+        .map(|subpath| {
+            let mut stroke = Stroke::new(color, width);
+            stroke.points = subpath
+                .iter()
+                .map(|p| [min[0] + p[0] * scale[0], max[1] - p[1] * scale[1]])
+                .collect();
+            stroke.invalidate_bbox();
+            stroke
+        })
+        .collect())
+}
+
+/// Strokes for every `<path>` element of the SVG document `svg`, placed as
+/// in `path_strokes`. A path's `stroke` color (`#rgb` or `#rrggbb`) and
+/// `stroke-width` (pixels) are used when present, `color` and `width`
+/// otherwise. Transforms, `style` attributes and other shapes are ignored.
+pub fn parse_svg(
+    svg: &str,
+    page: &Page,
+    color: [u8; 3],
+    width: f32,
+) -> Result<Vec<Stroke>, SvgError> {
+    let mut strokes = Vec::new();
+    let mut i = 0;
+    while let Some(open) = svg[i..].find('<') {
+        let start = i + open;
+        if svg[start..].starts_with("<!--") {
+            let end = svg[start..]
+                .find("-->")
+                .ok_or(SvgError::Unterminated(start))?;
+            i = start + end + 3;
+            continue;
+        }
+        let end = tag_end(svg, start).ok_or(SvgError::Unterminated(start))?;
+        let tag = &svg[start + 1..end];
+        i = end + 1;
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        if &tag[..name_end] != "path" {
+            continue;
+        }
+        let attributes = &tag[name_end..];
+        let d = match attribute(attributes, "d") {
+            Some(d) => d,
+            None => continue,
+        };
+        let color = attribute(attributes, "stroke")
+            .and_then(parse_color)
+            .unwrap_or(color);
+        let width = attribute(attributes, "stroke-width")
+            .and_then(|w| w.trim().trim_end_matches("px").parse::<f32>().ok())
+            .filter(|w| w.is_finite() && *w >= 0.0)
+            .map_or(width, |w| w * pixel_scale(page)[0]);
+        let paths =
+            path_strokes(d, page, color, width).map_err(|e| SvgError::PathData(start, e))?;
+        strokes.extend(paths);
+    }
+    Ok(strokes)
+}
+
+/// Byte offset of the `>` closing the tag opened at `start`, skipping any
+/// inside quoted attribute values.
+fn tag_end(svg: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in svg[start..].char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(start + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Value of the attribute `name` in the attribute list of a tag.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        if !rest.starts_with('=') {
+            // An attribute without a value.
+            continue;
+        }
+        rest = rest[1..].trim_start();
+        let quote = match rest.chars().next() {
+            Some(q @ '"') | Some(q @ '\'') => q,
+            _ => return None,
+        };
+        let value_end = rest[1..].find(quote)? + 1;
+        if key == name {
+            return Some(&rest[1..value_end]);
+        }
+        rest = &rest[value_end + 1..];
+    }
+}
+
+/// A `#rgb` or `#rrggbb` color.
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.trim().strip_prefix('#')?;
+    if !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize, len: usize| u8::from_str_radix(&hex[i * len..(i + 1) * len], 16);
+    match hex.len() {
+        3 => {
+            let mut color = [0; 3];
+            for (i, c) in color.iter_mut().enumerate() {
+                *c = channel(i, 1).ok()? * 17;
+            }
+            Some(color)
+        }
+        6 => Some([
+            channel(0, 2).ok()?,
+            channel(1, 2).ok()?,
+            channel(2, 2).ok()?,
+        ]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DEFAULT_DPI;
+
+    fn parse(d: &str) -> Vec<Vec<[f32; 2]>> {
+        parse_path_data(d, 2).unwrap()
+    }
+
+    fn page() -> Page {
+        Page {
+            width: 960,
+            height: 540,
+            dpi: DEFAULT_DPI,
+        }
+    }
+
+    #[test]
+    fn absolute_lines_and_close() {
+        assert_eq!(
+            parse("M0 0 L10 0 L10 10 Z"),
+            vec![vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 0.0]]]
+        );
+        // Coordinates after a moveto are linetos, and commas separate too.
+        assert_eq!(
+            parse("M0,0 1,1 2-1"),
+            vec![vec![[0.0, 0.0], [1.0, 1.0], [2.0, -1.0]]]
+        );
+        assert_eq!(
+            parse("M0 0 H5 V5 h-5"),
+            vec![vec![[0.0, 0.0], [5.0, 0.0], [5.0, 5.0], [0.0, 5.0]]]
+        );
+    }
+
+    #[test]
+    fn relative_commands() {
+        assert_eq!(
+            parse("m1 1 l2 0 0 2 z"),
+            vec![vec![[1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 1.0]]]
+        );
+        assert_eq!(
+            parse("M10 10 c0 10 10 10 10 0"),
+            parse("M10 10 C10 20 20 20 20 10")
+        );
+        assert_eq!(parse("M10 10 q5 10 10 0"), parse("M10 10 Q15 20 20 10"));
+    }
+
+    #[test]
+    fn curves_are_flattened() {
+        assert_eq!(
+            parse("M0 0 C0 10 10 10 10 0"),
+            vec![vec![[0.0, 0.0], [5.0, 7.5], [10.0, 0.0]]]
+        );
+        assert_eq!(
+            parse("M0 0 Q5 10 10 0"),
+            vec![vec![[0.0, 0.0], [5.0, 5.0], [10.0, 0.0]]]
+        );
+        assert_eq!(parse_path_data("M0 0 Q5 10 10 0", 16).unwrap()[0].len(), 17);
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        let cubic = parse("M0 0 C0 10 10 10 10 0 S20 -10 20 0");
+        assert_eq!(cubic[0][3..], [[15.0, -7.5], [20.0, 0.0]]);
+        let quad = parse("M0 0 Q5 10 10 0 T20 0");
+        assert_eq!(quad[0][3..], [[15.0, -5.0], [20.0, 0.0]]);
+        // Without a previous curve, the control point is the current point.
+        assert_eq!(
+            parse("M0 0 T10 0"),
+            vec![vec![[0.0, 0.0], [2.5, 0.0], [10.0, 0.0]]]
+        );
+    }
+
+    #[test]
+    fn subpaths() {
+        assert_eq!(parse("M0 0 L1 0 M5 5 L6 5").len(), 2);
+        // A command after Z continues from the subpath start.
+        assert_eq!(
+            parse("M0 0 L1 0 L1 1 Z L2 2")[1],
+            vec![[0.0, 0.0], [2.0, 2.0]]
+        );
+        assert!(parse("M1 1").is_empty());
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn malformed_path_data_is_an_error() {
+        let error = |d| parse_path_data(d, 2).unwrap_err();
+        assert_eq!(error("M0 0 L1 x"), PathDataError::Unexpected('x', 8));
+        assert_eq!(error("M0 0 L1"), PathDataError::MissingNumber('L'));
+        assert_eq!(error("M0 0 C1 1 2 2"), PathDataError::MissingNumber('C'));
+        assert_eq!(error("10 10"), PathDataError::MissingNumber('M'));
+        assert_eq!(
+            error("M0 0 A1 1 0 0 1 5 5"),
+            PathDataError::Unsupported('A')
+        );
+        assert_eq!(error("M--1 2"), PathDataError::Unexpected('-', 1));
+        assert!(parse_path_data("M0 0 L\u{e9}", 2).is_err());
+    }
+
+    #[test]
+    fn path_strokes_map_pixels_onto_the_page() {
+        let strokes = path_strokes("M0 0 L960 540", &page(), [1, 2, 3], 0.05).unwrap();
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(strokes[0].points, vec![[-1.0, 1.0], [1.0, -1.0]]);
+        assert_eq!(strokes[0].color, [1, 2, 3]);
+        assert!(strokes[0].timestamps.is_empty());
+    }
+
+    #[test]
+    fn parse_svg_imports_path_elements() {
+        let svg = r##"<?xml version="1.0"?>
+            <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 960 540">
+              <!-- <path d="M0 0 L1 1"/> is commented out -->
+              <rect x="0" y="0" width="10" height="10"/>
+              <path d="M0 0 L960 540" stroke="#ff8000" stroke-width="48"/>
+              <g><path fill='none' d='M480 270 h480'></path></g>
+              <pathology d="M0 0 L5 5"/>
+            </svg>"##;
+        let strokes = parse_svg(svg, &page(), [0, 0, 0], 0.02).unwrap();
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(strokes[0].points, vec![[-1.0, 1.0], [1.0, -1.0]]);
+        assert_eq!(strokes[0].color, [255, 128, 0]);
+        assert!((strokes[0].width - 0.1).abs() < 1e-6);
+        assert_eq!(strokes[1].points, vec![[0.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(strokes[1].color, [0, 0, 0]);
+        assert_eq!(strokes[1].width, 0.02);
+    }
+
+    #[test]
+    fn parse_svg_reads_short_colors() {
+        let svg = r##"<path stroke="#0f8" d="M0 0 L1 1"/>"##;
+        assert_eq!(
+            parse_svg(svg, &page(), [0; 3], 0.01).unwrap()[0].color,
+            [0, 255, 136]
+        );
+        for svg in &[
+            r##"<path stroke="red" d="M0 0 L1 1"/>"##,
+            "<path stroke=\"#\u{e9}\u{e9}\u{e9}\" d=\"M0 0 L1 1\"/>",
+        ] {
+            assert_eq!(
+                parse_svg(svg, &page(), [9; 3], 0.01).unwrap()[0].color,
+                [9; 3]
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_svg_is_an_error() {
+        assert_eq!(
+            parse_svg("<svg><path d=\"M0 0 L1 1\"", &page(), [0; 3], 0.01),
+            Err(SvgError::Unterminated(5))
+        );
+        assert_eq!(
+            parse_svg("<svg><path d='M0 0 L1 1>", &page(), [0; 3], 0.01),
+            Err(SvgError::Unterminated(5))
+        );
+        assert_eq!(
+            parse_svg("<!-- <path d='M0 0 L1 1'/>", &page(), [0; 3], 0.01),
+            Err(SvgError::Unterminated(0))
+        );
+        assert_eq!(
+            parse_svg("<svg><path d=\"M0 0 L\"/></svg>", &page(), [0; 3], 0.01),
+            Err(SvgError::PathData(5, PathDataError::MissingNumber('L')))
+        );
+        assert!(parse_svg("no markup", &page(), [0; 3], 0.01)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,647 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{has_area, Camera};
+use crate::coords::{ScreenPos, WorldPos};
+use crate::geometry::distance;
+use crate::history::History;
+use crate::input::{DoubleClick, InputState, MouseButton};
+use crate::jitter::Jitter;
+use crate::stroke::{
+    point_count, Arrowheads, JoinStyle, Outline, Stroke, StrokeStyle, MAX_FEATHER,
+};
+use crate::width::WidthProfile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Tool {
+    Freehand,
+    Line,
+    Rectangle,
+    Ellipse,
+    Pen,
+    Eraser,
+    /// Shows the distance and angle of a drag without drawing anything.
+    Measure,
+}
+
+/// Unit brush widths are set and displayed in: screen pixels, which keep
+/// the same on-screen size at every zoom, or NDC, relative to the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BrushSizing {
+    #[default]
+    Pixels,
+    Ndc,
+}
+
+/// Factor the width changes by per `[`/`]` press or Alt+scroll unit.
+pub const WIDTH_STEP: f32 = 1.1;
+/// Width limits, in the current sizing unit.
+pub const MIN_WIDTH: f32 = 0.001;
+pub const MAX_WIDTH: f32 = 500.0;
+
+/// Current width for the HUD, or the window title while there is no HUD
+/// text: "Width: 3 px", or "Width: 0.0125" in NDC.
+pub fn width_label(width: f32, sizing: BrushSizing) -> String {
+    match sizing {
+        BrushSizing::Pixels => format!("Width: {:.0} px", width),
+        BrushSizing::Ndc => format!("Width: {:.4}", width),
+    }
+}
+
+/// Brush state remembered separately for each tool.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolSettings {
+    pub width: f32,
+    pub color: [u8; 3],
+    pub style: StrokeStyle,
+    pub arrows: Arrowheads,
+    /// Scatter and color jitter; the seed is replaced for each stroke.
+    pub jitter: Jitter,
+    pub join_style: Option<JoinStyle>,
+    /// Edge softness of new strokes, see `Stroke::feather`.
+    pub feather: f32,
+    pub outline: Option<Outline>,
+    /// Width variation of new strokes, such as their end taper.
+    pub profile: WidthProfile,
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        Self {
+            width: 2.0,
+            color: [0, 0, 0],
+            style: StrokeStyle::Line,
+            arrows: Arrowheads::None,
+            jitter: Jitter::default(),
+            join_style: None,
+            feather: 0.0,
+            outline: None,
+            profile: WidthProfile::default(),
+        }
+    }
+}
+
+impl ToolSettings {
+    /// Grows (positive `steps`) or shrinks the width geometrically, so each
+    /// step feels the same at any size.
+    pub fn adjust_width(&mut self, steps: f32) {
+        self.width = (self.width * WIDTH_STEP.powf(steps)).clamp(MIN_WIDTH, MAX_WIDTH);
+    }
+
+    /// An empty stroke drawn with these settings. The stroke's ID seeds its
+    /// jitter, so every stroke is scattered differently.
+    pub fn start_stroke(&self) -> Stroke {
+        let stroke = Stroke {
+            style: self.style,
+            arrows: self.arrows,
+            join_style: self.join_style,
+            feather: self.feather.clamp(0.0, MAX_FEATHER),
+            outline: self.outline,
+            profile: self.profile,
+            ..Stroke::new(self.color, self.width)
+        };
+        Stroke {
+            jitter: self.jitter.seeded(stroke.id),
+            ..stroke
+        }
+    }
+}
+
+/// The active tool along with the settings of every tool, so that switching
+/// back to a tool restores the brush it was last used with. Saved in the
+/// config, where the settings are keyed by tool name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolBox {
+    active: Tool,
+    settings: BTreeMap<Tool, ToolSettings>,
+}
+
+impl ToolBox {
+    pub fn new(active: Tool) -> Self {
+        Self {
+            active,
+            settings: BTreeMap::new(),
+        }
+    }
+
+    pub fn active(&self) -> Tool {
+        self.active
+    }
+
+    pub fn select(&mut self, tool: Tool) {
+        self.active = tool;
+    }
+
+    /// Settings of the active tool.
+    pub fn settings(&self) -> ToolSettings {
+        self.settings_for(self.active)
+    }
+
+    pub fn settings_mut(&mut self) -> &mut ToolSettings {
+        self.settings.entry(self.active).or_default()
+    }
+
+    pub fn settings_for(&self, tool: Tool) -> ToolSettings {
+        self.settings.get(&tool).copied().unwrap_or_default()
+    }
+
+    pub fn set_settings_for(&mut self, tool: Tool, settings: ToolSettings) {
+        self.settings.insert(tool, settings);
+    }
+}
+
+impl Default for ToolBox {
+    fn default() -> Self {
+        Self::new(Tool::Freehand)
+    }
+}
+
+/// Live readout of the measure tool, between the press position and the
+/// cursor, both in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub start: WorldPos,
+    pub end: WorldPos,
+    /// On-screen length at the current zoom.
+    pub pixels: f32,
+}
+
+impl Measurement {
+    pub fn new(start: WorldPos, end: WorldPos, camera: &Camera, size: (u32, u32)) -> Self {
+        let a = camera.world_to_screen(start, size);
+        let b = camera.world_to_screen(end, size);
+        Self {
+            start,
+            end,
+            pixels: distance(a.0, b.0),
+        }
+    }
+
+    /// Length in world units, independent of the zoom.
+    pub fn length(&self) -> f32 {
+        distance(self.start.0, self.end.0)
+    }
+
+    /// Angle from the start to the end in degrees, counterclockwise from the
+    /// positive x axis, in `(-180, 180]`.
+    pub fn angle(&self) -> f32 {
+        let dy = self.end.y() - self.start.y();
+        let dx = self.end.x() - self.start.x();
+        dy.atan2(dx).to_degrees()
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} px ({:.4} units) at {:.1}\u{b0}",
+            self.pixels,
+            self.length(),
+            self.angle()
+        )
+    }
+}
+
+/// Increment rotations snap to while Shift is held, in degrees.
+pub const ROTATION_SNAP_DEGREES: f32 = 15.0;
+
+/// A drag rotating the selection around `center`, which is usually the
+/// center of its bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rotation {
+    pub center: WorldPos,
+    /// Where the drag started.
+    pub start: WorldPos,
+    /// Rotation so far in degrees, counterclockwise.
+    pub angle: f32,
+}
+
+impl Rotation {
+    pub fn new(center: WorldPos, start: WorldPos) -> Self {
+        Self {
+            center,
+            start,
+            angle: 0.0,
+        }
+    }
+
+    /// Updates the angle swept from the start to `cursor`, rounded to
+    /// `ROTATION_SNAP_DEGREES` when `snap` (Shift) is set.
+    pub fn update(&mut self, cursor: WorldPos, snap: bool) {
+        let heading = |p: WorldPos| (p.y() - self.center.y()).atan2(p.x() - self.center.x());
+        let mut angle = (heading(cursor) - heading(self.start)).to_degrees();
+        // Keep within (-180, 180] so the readout does not jump to 350°.
+        if angle > 180.0 {
+            angle -= 360.0;
+        } else if angle <= -180.0 {
+            angle += 360.0;
+        }
+        if snap {
+            angle = (angle / ROTATION_SNAP_DEGREES).round() * ROTATION_SNAP_DEGREES;
+        }
+        self.angle = angle;
+    }
+
+    /// The angle in radians, for `Command::rotate`.
+    pub fn radians(&self) -> f32 {
+        self.angle.to_radians()
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rotate {:.1}\u{b0}", self.angle)
+    }
+}
+
+/// What a press did to a `ClickChain`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    /// A vertex was placed.
+    Added,
+    /// A double-click finished the chain with these vertices.
+    Finished(Vec<WorldPos>),
+}
+
+/// Multi-segment line built one click per vertex by the line and pen tools,
+/// finished by double-clicking.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClickChain {
+    pub vertices: Vec<WorldPos>,
+    pub double_click: DoubleClick,
+    last_press: Option<(Instant, ScreenPos)>,
+}
+
+impl ClickChain {
+    pub fn new(double_click: DoubleClick) -> Self {
+        Self {
+            double_click,
+            ..Self::default()
+        }
+    }
+
+    /// Handles a press at `screen`, which is `world` on the canvas. The first
+    /// press of a double-click has already placed the final vertex, so the
+    /// second one finishes the chain without adding another on top of it.
+    /// A chain with fewer than two vertices is dropped on finishing.
+    pub fn press(
+        &mut self,
+        screen: ScreenPos,
+        world: WorldPos,
+        now: Instant,
+    ) -> Option<ChainEvent> {
+        let double = self
+            .last_press
+            .is_some_and(|(time, pos)| self.double_click.matches(time, pos, now, screen));
+        if double {
+            self.last_press = None;
+            let vertices = std::mem::take(&mut self.vertices);
+            return (vertices.len() >= 2).then_some(ChainEvent::Finished(vertices));
+        }
+        self.last_press = Some((now, screen));
+        self.vertices.push(world);
+        Some(ChainEvent::Added)
+    }
+
+    /// Drops the chain, e.g. on Escape.
+    pub fn cancel(&mut self) {
+        self.vertices.clear();
+        self.last_press = None;
+    }
+}
+
+/// Factor the simplification tolerance changes by per scroll unit or key
+/// press while previewing.
+pub const EPSILON_STEP: f32 = 1.25;
+/// Tolerance limits of the simplification preview, in world units.
+pub const MIN_EPSILON: f32 = 1e-5;
+pub const MAX_EPSILON: f32 = 0.1;
+
+/// Live preview of simplifying some strokes, re-run as the tolerance is
+/// tuned and committed as a single undo step once it looks right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimplifyPreview {
+    pub epsilon: f32,
+    /// Strokes being simplified, in increasing order.
+    indices: Vec<usize>,
+    /// Simplified copies of the strokes at `indices`, in the same order.
+    preview: Vec<Stroke>,
+    before: usize,
+}
+
+impl SimplifyPreview {
+    /// Previews simplifying the strokes at `indices`, or every stroke when
+    /// empty, at `epsilon`. Locked strokes are left out either way.
+    pub fn new(strokes: &[Stroke], indices: &[usize], epsilon: f32) -> Self {
+        let unlocked = |&i: &usize| strokes.get(i).is_some_and(|s| !s.locked);
+        let mut indices: Vec<usize> = if indices.is_empty() {
+            (0..strokes.len()).filter(unlocked).collect()
+        } else {
+            indices.iter().copied().filter(unlocked).collect()
+        };
+        indices.sort_unstable();
+        indices.dedup();
+        let before = indices.iter().map(|&i| strokes[i].points.len()).sum();
+        let mut preview = Self {
+            epsilon: epsilon.clamp(MIN_EPSILON, MAX_EPSILON),
+            indices,
+            preview: Vec::new(),
+            before,
+        };
+        preview.refresh(strokes);
+        preview
+    }
+
+    /// Re-runs the simplifier on the original strokes at the current
+    /// tolerance.
+    pub fn refresh(&mut self, strokes: &[Stroke]) {
+        self.preview = self
+            .indices
+            .iter()
+            .filter_map(|&i| strokes.get(i))
+            .map(|stroke| {
+                let mut simplified = stroke.clone();
+                simplified.simplify(self.epsilon);
+                simplified
+            })
+            .collect();
+    }
+
+    /// Loosens (positive `steps`) or tightens the tolerance geometrically
+    /// and refreshes the preview.
+    pub fn adjust(&mut self, strokes: &[Stroke], steps: f32) {
+        self.epsilon = (self.epsilon * EPSILON_STEP.powf(steps)).clamp(MIN_EPSILON, MAX_EPSILON);
+        self.refresh(strokes);
+    }
+
+    /// Simplified version of the stroke at `index`, to draw in its place,
+    /// or `None` if it is not part of the preview.
+    pub fn preview_for(&self, index: usize) -> Option<&Stroke> {
+        let at = self.indices.binary_search(&index).ok()?;
+        self.preview.get(at)
+    }
+
+    /// Point counts of the previewed strokes before and after.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.before, point_count(&self.preview))
+    }
+
+    /// Replaces the strokes by their previewed versions as one undoable
+    /// step. Returns the point counts before and after.
+    pub fn commit(self, strokes: &mut Vec<Stroke>, history: &mut History) -> (usize, usize) {
+        let counts = self.counts();
+        let mut after = strokes.clone();
+        for (&i, stroke) in self.indices.iter().zip(self.preview) {
+            if let Some(slot) = after.get_mut(i) {
+                *slot = stroke;
+            }
+        }
+        history.replace_all(strokes, after);
+        counts
+    }
+}
+
+impl fmt::Display for SimplifyPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = self.counts();
+        write!(
+            f,
+            "simplify {:.5}: {} \u{2192} {} points",
+            self.epsilon, before, after
+        )
+    }
+}
+
+/// Key held to navigate by default, winit's `VirtualKeyCode::Space`.
+pub const KEY_SPACE: u32 = 76;
+
+/// Temporary navigation while a key is held, Space by default: left drags
+/// pan the view whatever the active tool. A stroke in progress is paused
+/// rather than ended, since the tool sees no input meanwhile, and resumes
+/// once the key is released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavigationHold {
+    pub key: u32,
+    held: bool,
+    /// Last cursor position of a pan drag. Set from the press until the
+    /// button goes up, so a pan outliving the key does not turn into a
+    /// stroke.
+    drag: Option<ScreenPos>,
+}
+
+impl NavigationHold {
+    pub fn new(key: u32) -> Self {
+        Self {
+            key,
+            held: false,
+            drag: None,
+        }
+    }
+
+    /// Whether input currently goes to navigation instead of the tool.
+    pub fn is_active(&self) -> bool {
+        self.held || self.drag.is_some()
+    }
+
+    /// Handles this frame's `input`, panning `camera` for drags. Returns
+    /// whether the frame was taken by navigation, in which case the tool must
+    /// not see it.
+    pub fn update(&mut self, input: &InputState, camera: &mut Camera, size: (u32, u32)) -> bool {
+        self.held = input.is_key_down(self.key);
+        let left = MouseButton::Left;
+        if self.held && input.was_pressed(left) {
+            self.drag = Some(input.cursor);
+        }
+        if let Some(from) = self.drag {
+            if has_area(size) {
+                let (w, h) = (size.0 as f32, size.1 as f32);
+                let delta = [input.cursor.x() - from.x(), input.cursor.y() - from.y()];
+                camera.pan_by_ndc([-2.0 * delta[0] / w, 2.0 * delta[1] / h]);
+            }
+            self.drag = input.is_down(left).then_some(input.cursor);
+            return true;
+        }
+        self.held
+    }
+}
+
+impl Default for NavigationHold {
+    fn default() -> Self {
+        Self::new(KEY_SPACE)
+    }
+}
+
+/// Actions of the keyboard plotting mode, which the event loop maps from the
+/// arrow keys, Space and Enter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotAction {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Drops a point at the virtual cursor.
+    Drop,
+    /// Finishes the current stroke.
+    Finish,
+}
+
+/// Draws without a pointing device: a virtual cursor moved in world space,
+/// one step per key press, placing points into a stroke.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardPlotter {
+    pub cursor: WorldPos,
+    /// Cursor movement per key press, in screen pixels, so the step feels
+    /// the same at every zoom.
+    pub step_px: f32,
+    stroke: Option<Stroke>,
+    /// Frame time the current stroke started at.
+    started: Option<Instant>,
+}
+
+impl KeyboardPlotter {
+    pub fn new(cursor: WorldPos) -> Self {
+        Self {
+            cursor,
+            step_px: 8.0,
+            stroke: None,
+            started: None,
+        }
+    }
+
+    /// The stroke being plotted, to draw as the live stroke.
+    pub fn stroke(&self) -> Option<&Stroke> {
+        self.stroke.as_ref()
+    }
+
+    /// Handles one action at frame time `now`. Returns the finished stroke
+    /// on `Finish`, if it has at least two points; `settings` style strokes
+    /// as they start.
+    pub fn handle(
+        &mut self,
+        action: PlotAction,
+        settings: ToolSettings,
+        camera: &Camera,
+        size: (u32, u32),
+        now: Instant,
+    ) -> Option<Stroke> {
+        // World distance of one step horizontally and vertically.
+        let step = [
+            2.0 * self.step_px / (size.0.max(1) as f32 * camera.zoom),
+            2.0 * self.step_px / (size.1.max(1) as f32 * camera.zoom),
+        ];
+        let [x, y] = self.cursor.0;
+        match action {
+            PlotAction::Left => self.cursor = WorldPos([x - step[0], y]),
+            PlotAction::Right => self.cursor = WorldPos([x + step[0], y]),
+            PlotAction::Up => self.cursor = WorldPos([x, y + step[1]]),
+            PlotAction::Down => self.cursor = WorldPos([x, y - step[1]]),
+            PlotAction::Drop => {
+                let started = *self.started.get_or_insert(now);
+                let stroke = self.stroke.get_or_insert_with(|| settings.start_stroke());
+                stroke.push(self.cursor.0, now.saturating_duration_since(started));
+            }
+            PlotAction::Finish => {
+                self.started = None;
+                return self.stroke.take().filter(|s| s.points.len() >= 2);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A straight stroke of `points` points along y = `y`.
+    fn dense(y: f32, points: usize) -> Stroke {
+        let mut stroke = Stroke::new([0, 0, 0], 0.01);
+        for i in 0..points {
+            stroke.push([i as f32 * 0.01, y], Duration::from_millis(i as u64));
+        }
+        stroke
+    }
+
+    #[test]
+    fn simplify_preview_leaves_locked_strokes_out() {
+        let mut strokes = vec![dense(0.0, 10), dense(1.0, 10)];
+        strokes[1].locked = true;
+        let mut history = History::new();
+        for selection in &[&[][..], &[0, 1][..]] {
+            let preview = SimplifyPreview::new(&strokes, selection, 0.001);
+            assert!(preview.preview_for(1).is_none());
+            assert_eq!(preview.counts(), (10, 2));
+        }
+        SimplifyPreview::new(&strokes, &[], 0.001).commit(&mut strokes, &mut history);
+        assert_eq!(strokes[0].points.len(), 2);
+        assert_eq!(strokes[1].points.len(), 10);
+    }
+
+    #[test]
+    fn simplify_preview_shows_the_selected_strokes() {
+        let strokes = vec![dense(0.0, 10), dense(1.0, 6), dense(2.0, 4)];
+        let preview = SimplifyPreview::new(&strokes, &[2, 0, 2, 9], 0.001);
+        assert_eq!(preview.preview_for(0).unwrap().points.len(), 2);
+        assert!(preview.preview_for(1).is_none());
+        assert_eq!(preview.preview_for(2).unwrap().points.len(), 2);
+        assert!(preview.preview_for(9).is_none());
+        assert_eq!(preview.counts(), (14, 4));
+        assert_eq!(
+            preview.to_string(),
+            "simplify 0.00100: 14 \u{2192} 4 points"
+        );
+    }
+
+    #[test]
+    fn simplify_preview_tolerance_stays_in_range() {
+        let strokes = vec![dense(0.0, 10)];
+        let mut preview = SimplifyPreview::new(&strokes, &[], 1.0);
+        assert_eq!(preview.epsilon, MAX_EPSILON);
+        preview.adjust(&strokes, -1000.0);
+        assert_eq!(preview.epsilon, MIN_EPSILON);
+    }
+
+    #[test]
+    fn committing_a_simplify_preview_undoes_in_one_step() {
+        let mut strokes = vec![dense(0.0, 10), dense(1.0, 6)];
+        let original = strokes.clone();
+        let mut history = History::new();
+        let preview = SimplifyPreview::new(&strokes, &[1], 0.001);
+        assert_eq!(preview.commit(&mut strokes, &mut history), (6, 2));
+        assert_eq!(strokes[0], original[0]);
+        assert_eq!(strokes[1].points.len(), 2);
+        assert!(history.undo(&mut strokes));
+        assert_eq!(strokes, original);
+        assert!(!history.undo(&mut strokes));
+    }
+
+    #[test]
+    fn plotted_points_are_timed_by_the_frame_time() {
+        let mut plotter = KeyboardPlotter::new(WorldPos([0.0, 0.0]));
+        let camera = Camera::default();
+        let start = Instant::now();
+        let mut act = |action, ms| {
+            let now = start + Duration::from_millis(ms);
+            plotter.handle(action, ToolSettings::default(), &camera, (800, 600), now)
+        };
+        act(PlotAction::Drop, 1000);
+        act(PlotAction::Right, 1100);
+        act(PlotAction::Drop, 1250);
+        let stroke = act(PlotAction::Finish, 1300).unwrap();
+        assert_eq!(stroke.timestamps, vec![0.0, 0.25]);
+
+        // The next stroke starts its own clock.
+        act(PlotAction::Drop, 5000);
+        act(PlotAction::Up, 5000);
+        act(PlotAction::Drop, 5100);
+        let stroke = act(PlotAction::Finish, 5100).unwrap();
+        assert_eq!(stroke.timestamps, vec![0.0, 0.1]);
+    }
+}
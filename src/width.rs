@@ -0,0 +1,353 @@
+//! Per-point width profiles, consumed by the variable-width mesher.
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::distance;
+
+/// Cumulative arc length at each point of `points`.
+pub fn arc_lengths(points: &[[f32; 2]]) -> Vec<f32> {
+    let mut total = 0.0;
+    let mut lengths = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        if i > 0 {
+            total += distance(points[i - 1], p);
+        }
+        lengths.push(total);
+    }
+    lengths
+}
+
+/// Width at each point of a stroke of `width` whose ends taper to zero over
+/// `taper` (a fraction of the total length, at most 0.5) at each end. On
+/// strokes too short for both ramps the narrower of the two wins, so the
+/// width peaks in the middle instead of jumping.
+pub fn taper_widths(points: &[[f32; 2]], width: f32, taper: f32) -> Vec<f32> {
+    let lengths = arc_lengths(points);
+    let total = lengths.last().copied().unwrap_or(0.0);
+    let taper = taper.clamp(0.0, 0.5);
+    if taper <= 0.0 || total <= f32::EPSILON {
+        return vec![width; points.len()];
+    }
+
+    let ramp = taper * total;
+    lengths
+        .iter()
+        .map(|&s| {
+            let from_start = (s / ramp).min(1.0);
+            let from_end = ((total - s) / ramp).min(1.0);
+            width * from_start.min(from_end)
+        })
+        .collect()
+}
+
+/// How the width of a stroke varies along its length. The default keeps the
+/// stroke width everywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WidthProfile {
+    /// Fraction of the length over which each end tapers to zero, see
+    /// `taper_widths`.
+    pub taper: f32,
+    /// Points on either side averaged over by `smooth_widths` once the
+    /// widths are computed, 0 to leave them as they are.
+    pub smoothing: usize,
+    /// Taper from the drawing speed at the ends, on top of `taper`.
+    pub speed: SpeedTaper,
+}
+
+impl WidthProfile {
+    /// Whether the width is the same everywhere. Smoothing alone leaves a
+    /// constant width unchanged.
+    pub fn is_constant(&self) -> bool {
+        self.taper <= 0.0 && !self.speed.enabled
+    }
+
+    /// Width at each of `points` for a stroke of `width`. `timestamps` are
+    /// the capture times the speed taper is measured from, see
+    /// `SpeedTaper::widths`.
+    pub fn widths(&self, points: &[[f32; 2]], timestamps: &[f32], width: f32) -> Vec<f32> {
+        let speed = self.speed.widths(points, timestamps, 1.0);
+        let mut widths: Vec<f32> = taper_widths(points, width, self.taper)
+            .into_iter()
+            .zip(speed)
+            .map(|(w, s)| w * s)
+            .collect();
+        smooth_widths(&mut widths, self.smoothing);
+        widths
+    }
+}
+
+/// `values` given at each of `points`, linearly interpolated at `samples`,
+/// a resampled version of the same line, by the fraction of the length
+/// travelled.
+pub fn resample(points: &[[f32; 2]], values: &[f32], samples: &[[f32; 2]]) -> Vec<f32> {
+    let source = arc_lengths(points);
+    let target = arc_lengths(samples);
+    if source.len() < 2 || values.len() != source.len() {
+        let value = values.first().copied().unwrap_or(0.0);
+        return vec![value; samples.len()];
+    }
+    let source_total = source[source.len() - 1].max(f32::EPSILON);
+    let target_total = target.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+    target
+        .iter()
+        .map(|&s| {
+            let along = s / target_total * source_total;
+            let i = source
+                .partition_point(|&l| l < along)
+                .clamp(1, source.len() - 1);
+            let span = (source[i] - source[i - 1]).max(f32::EPSILON);
+            let t = ((along - source[i - 1]) / span).clamp(0.0, 1.0);
+            values[i - 1] + (values[i] - values[i - 1]) * t
+        })
+        .collect()
+}
+
+/// Replaces each width by the average of the widths within `window` points on
+/// either side, smoothing out abrupt changes from noisy pressure or
+/// velocity. The window shrinks near the ends of the stroke.
+pub fn smooth_widths(widths: &mut [f32], window: usize) {
+    if window == 0 || widths.len() < 2 {
+        return;
+    }
+
+    // Prefix sums give every windowed average in a single pass.
+    let mut prefix = Vec::with_capacity(widths.len() + 1);
+    prefix.push(0.0);
+    for &w in widths.iter() {
+        prefix.push(prefix[prefix.len() - 1] + w);
+    }
+    for (i, w) in widths.iter_mut().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(prefix.len() - 1);
+        *w = (prefix[end] - prefix[start]) / (end - start) as f32;
+    }
+}
+
+/// Number of points at each end of a stroke used to estimate its entry and
+/// exit speed.
+const SPEED_SAMPLES: usize = 4;
+
+/// Mouse-friendly stand-in for pressure: strokes that start or end while the
+/// cursor is moving fast taper, as a flicked pen stroke would. The speed is
+/// measured from the timestamps `Stroke::push` records with each point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedTaper {
+    pub enabled: bool,
+    /// Longest taper, as a fraction of the stroke length, reached at
+    /// `reference_speed` and above.
+    pub max_taper: f32,
+    /// Speed in world units per second giving the full taper.
+    pub reference_speed: f32,
+}
+
+impl SpeedTaper {
+    /// Width at each point of a stroke of `width`. `timestamps` are the
+    /// capture times of the points in seconds; without one per point the
+    /// width stays constant.
+    pub fn widths(&self, points: &[[f32; 2]], timestamps: &[f32], width: f32) -> Vec<f32> {
+        let lengths = arc_lengths(points);
+        let total = lengths.last().copied().unwrap_or(0.0);
+        if !self.enabled || timestamps.len() != points.len() || total <= f32::EPSILON {
+            return vec![width; points.len()];
+        }
+
+        let n = SPEED_SAMPLES.min(points.len() - 1);
+        let last = points.len() - 1;
+        let entry = self.strength(lengths[n], timestamps[n] - timestamps[0]);
+        let exit = self.strength(
+            total - lengths[last - n],
+            timestamps[last] - timestamps[last - n],
+        );
+        let max_taper = self.max_taper.clamp(0.0, 0.5);
+        let ramp = |strength: f32, s: f32| {
+            let length = strength * max_taper * total;
+            if length <= f32::EPSILON {
+                1.0
+            } else {
+                // Faster strokes also end on a thinner tip.
+                let tip = 1.0 - strength;
+                tip + (1.0 - tip) * (s / length).min(1.0)
+            }
+        };
+        lengths
+            .iter()
+            .map(|&s| width * ramp(entry, s).min(ramp(exit, total - s)))
+            .collect()
+    }
+
+    /// Taper strength in `[0, 1]` for covering `length` in `duration`.
+    fn strength(&self, length: f32, duration: f32) -> f32 {
+        if duration <= f32::EPSILON || self.reference_speed <= 0.0 {
+            return 0.0;
+        }
+        (length / duration / self.reference_speed).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for SpeedTaper {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_taper: 0.3,
+            reference_speed: 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(n: usize) -> Vec<[f32; 2]> {
+        (0..n).map(|i| [i as f32, 0.0]).collect()
+    }
+
+    #[test]
+    fn taper_ramps_both_ends_to_zero() {
+        let widths = taper_widths(&line(11), 2.0, 0.2);
+        assert_eq!(widths[0], 0.0);
+        assert_eq!(widths[10], 0.0);
+        assert_eq!(widths[1], 1.0);
+        assert!(widths[2..=8].iter().all(|&w| w == 2.0));
+        assert_eq!(widths[9], 1.0);
+    }
+
+    #[test]
+    fn overlapping_tapers_peak_in_the_middle() {
+        // Tapers longer than half the stroke are capped at half.
+        let widths = taper_widths(&line(5), 1.0, 0.9);
+        assert_eq!(widths, [0.0, 0.5, 1.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn no_taper_or_no_length_keeps_the_width() {
+        assert_eq!(taper_widths(&line(3), 1.5, 0.0), [1.5; 3]);
+        assert_eq!(taper_widths(&[[1.0, 1.0]; 3], 1.5, 0.3), [1.5; 3]);
+        assert!(taper_widths(&[], 1.5, 0.3).is_empty());
+    }
+
+    #[test]
+    fn profiles_apply_their_taper() {
+        let profile = WidthProfile {
+            taper: 0.2,
+            ..WidthProfile::default()
+        };
+        assert!(!profile.is_constant());
+        assert_eq!(
+            profile.widths(&line(11), &[], 2.0),
+            taper_widths(&line(11), 2.0, 0.2)
+        );
+        assert!(WidthProfile::default().is_constant());
+    }
+
+    #[test]
+    fn smoothing_flattens_spikes() {
+        let mut widths = [1.0, 1.0, 10.0, 1.0, 1.0, 1.0, 1.0];
+        smooth_widths(&mut widths, 1);
+        assert_eq!(widths, [1.0, 4.0, 4.0, 4.0, 1.0, 1.0, 1.0]);
+
+        let mut spiky = [1.0, 5.0, 1.0, 5.0, 1.0, 5.0, 1.0, 5.0, 1.0];
+        let jump = |w: &[f32]| {
+            w.windows(2)
+                .map(|p| (p[1] - p[0]).abs())
+                .fold(0.0, f32::max)
+        };
+        let before = jump(&spiky);
+        smooth_widths(&mut spiky, 2);
+        assert!(jump(&spiky) < before / 4.0);
+        assert!(spiky.iter().all(|&w| (1.0..=5.0).contains(&w)));
+    }
+
+    #[test]
+    fn smoothing_without_a_window_changes_nothing() {
+        let mut widths = [1.0, 10.0, 1.0];
+        smooth_widths(&mut widths, 0);
+        assert_eq!(widths, [1.0, 10.0, 1.0]);
+        let mut single = [3.0];
+        smooth_widths(&mut single, 4);
+        assert_eq!(single, [3.0]);
+    }
+
+    #[test]
+    fn profiles_smooth_their_widths() {
+        let profile = WidthProfile {
+            taper: 0.2,
+            smoothing: 1,
+            ..WidthProfile::default()
+        };
+        let mut expected = taper_widths(&line(11), 2.0, 0.2);
+        smooth_widths(&mut expected, 1);
+        assert_eq!(profile.widths(&line(11), &[], 2.0), expected);
+        // The kink where the taper ends is rounded off.
+        assert!(expected[2] < 2.0);
+    }
+
+    fn speed_taper() -> SpeedTaper {
+        SpeedTaper {
+            enabled: true,
+            ..SpeedTaper::default()
+        }
+    }
+
+    /// Timestamps of points one unit apart drawn at `speed` units per second.
+    fn times(n: usize, speed: f32) -> Vec<f32> {
+        (0..n).map(|i| i as f32 / speed).collect()
+    }
+
+    #[test]
+    fn fast_ends_taper_to_a_point() {
+        let widths = speed_taper().widths(&line(11), &times(11, 100.0), 2.0);
+        assert_eq!(widths[0], 0.0);
+        assert_eq!(widths[10], 0.0);
+        assert_eq!(widths[5], 2.0);
+    }
+
+    #[test]
+    fn slower_ends_taper_less() {
+        // Half the reference speed: a tip of half the width, over half the
+        // longest taper.
+        let widths = speed_taper().widths(&line(11), &times(11, 1.0), 2.0);
+        assert!((widths[0] - 1.0).abs() < 1e-6);
+        assert!(widths[1] > 1.0 && widths[1] < 2.0);
+        assert_eq!(widths[2], 2.0);
+    }
+
+    #[test]
+    fn speed_taper_needs_timestamps() {
+        assert_eq!(speed_taper().widths(&line(4), &[], 1.0), [1.0; 4]);
+        assert_eq!(
+            SpeedTaper::default().widths(&line(4), &times(4, 100.0), 1.0),
+            [1.0; 4]
+        );
+    }
+
+    #[test]
+    fn profiles_combine_both_tapers() {
+        let profile = WidthProfile {
+            taper: 0.5,
+            speed: speed_taper(),
+            ..WidthProfile::default()
+        };
+        assert!(!profile.is_constant());
+        let points = line(11);
+        let timestamps = times(11, 1.0);
+        let taper = taper_widths(&points, 1.0, 0.5);
+        let speed = speed_taper().widths(&points, &timestamps, 1.0);
+        let widths = profile.widths(&points, &timestamps, 2.0);
+        for i in 0..11 {
+            assert!((widths[i] - 2.0 * taper[i] * speed[i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn resampling_interpolates_along_the_length() {
+        let samples: Vec<[f32; 2]> = (0..5).map(|i| [i as f32 * 0.5, 0.0]).collect();
+        assert_eq!(
+            resample(&line(3), &[0.0, 1.0, 4.0], &samples),
+            [0.0, 0.5, 1.0, 2.5, 4.0]
+        );
+        assert_eq!(resample(&line(1), &[3.0], &samples), [3.0; 5]);
+    }
+}
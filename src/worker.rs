@@ -0,0 +1,163 @@
+//! Mesh building off the main thread, for documents large enough that
+//! meshing stalls the UI.
+//!
+//! Strokes are cloned into jobs and sent to a worker thread over a channel;
+//! built meshes come back over another one and are picked up with `poll`,
+//! which never blocks. Only the main thread, which owns the GL context,
+//! should upload what `poll` returns.
+//!
+//! Synchronization: the worker shares nothing with the main thread but the
+//! two channels, so there is no locking. Results can arrive after their
+//! stroke changed again, so every job carries a per-stroke generation and
+//! `poll` drops results older than the stroke's latest submission. Dropping
+//! the `MeshWorker` closes the job channel, which ends the worker loop once
+//! queued jobs are done, and then joins the thread.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::mesh::{stroke_to_mesh_with, Mesh, MeshQuality};
+use crate::stroke::Stroke;
+
+struct Job {
+    generation: u64,
+    stroke: Stroke,
+    quality: MeshQuality,
+}
+
+struct Built {
+    id: u64,
+    generation: u64,
+    mesh: Mesh,
+}
+
+fn build(job: Job) -> Built {
+    Built {
+        id: job.stroke.id,
+        generation: job.generation,
+        mesh: stroke_to_mesh_with(&job.stroke, job.quality),
+    }
+}
+
+enum Mode {
+    Threaded {
+        jobs: Sender<Job>,
+        handle: JoinHandle<()>,
+    },
+    Inline,
+}
+
+pub struct MeshWorker {
+    mode: Mode,
+    /// Sending side of `results`, cloned into the worker thread and used
+    /// directly when meshing inline.
+    result_tx: Sender<Built>,
+    results: Receiver<Built>,
+    /// Latest generation submitted for each stroke ID.
+    latest: HashMap<u64, u64>,
+    next_generation: u64,
+}
+
+impl MeshWorker {
+    /// Starts a worker thread when `threaded` is set; otherwise meshes are
+    /// built inline by `submit`, behind the same interface.
+    pub fn new(threaded: bool) -> Self {
+        let (result_tx, results) = mpsc::channel();
+        let mode = if threaded {
+            Self::spawn(result_tx.clone())
+        } else {
+            Mode::Inline
+        };
+        Self {
+            mode,
+            result_tx,
+            results,
+            latest: HashMap::new(),
+            next_generation: 0,
+        }
+    }
+
+    fn spawn(result_tx: Sender<Built>) -> Mode {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let spawned = thread::Builder::new()
+            .name("mesh-worker".into())
+            .spawn(move || {
+                for job in job_rx {
+                    if result_tx.send(build(job)).is_err() {
+                        break;
+                    }
+                }
+            });
+        match spawned {
+            Ok(handle) => Mode::Threaded { jobs, handle },
+            Err(err) => {
+                log::warn!("could not start the mesh worker, meshing inline: {}", err);
+                Mode::Inline
+            }
+        }
+    }
+
+    pub fn is_threaded(&self) -> bool {
+        matches!(self.mode, Mode::Threaded { .. })
+    }
+
+    /// Queues `stroke` for meshing, superseding any earlier submission of
+    /// the same stroke whose result has not been picked up yet.
+    pub fn submit(&mut self, stroke: &Stroke, quality: MeshQuality) {
+        self.next_generation += 1;
+        self.latest.insert(stroke.id, self.next_generation);
+        let job = Job {
+            generation: self.next_generation,
+            stroke: stroke.clone(),
+            quality,
+        };
+        let job = match &self.mode {
+            Mode::Threaded { jobs, .. } => match jobs.send(job) {
+                Ok(()) => return,
+                // The worker is gone, e.g. after a panic; mesh here from now on.
+                Err(mpsc::SendError(job)) => {
+                    log::warn!("mesh worker stopped, meshing inline");
+                    self.mode = Mode::Inline;
+                    job
+                }
+            },
+            Mode::Inline => job,
+        };
+        // Cannot fail: `self.results` is alive.
+        let _ = self.result_tx.send(build(job));
+    }
+
+    /// Stops tracking stroke `id`, e.g. after it was deleted, so a result
+    /// still in flight is dropped.
+    pub fn forget(&mut self, id: u64) {
+        self.latest.remove(&id);
+    }
+
+    /// Meshes finished since the last call, by stroke ID, skipping any that
+    /// were superseded or forgotten. Never blocks.
+    pub fn poll(&mut self) -> Vec<(u64, Mesh)> {
+        let mut ready = Vec::new();
+        while let Ok(built) = self.results.try_recv() {
+            if self.latest.get(&built.id) == Some(&built.generation) {
+                self.latest.remove(&built.id);
+                ready.push((built.id, built.mesh));
+            }
+        }
+        ready
+    }
+
+    /// Whether submitted strokes are still waiting for their mesh.
+    pub fn is_busy(&self) -> bool {
+        !self.latest.is_empty()
+    }
+}
+
+impl Drop for MeshWorker {
+    fn drop(&mut self) {
+        if let Mode::Threaded { jobs, handle } = std::mem::replace(&mut self.mode, Mode::Inline) {
+            drop(jobs);
+            let _ = handle.join();
+        }
+    }
+}